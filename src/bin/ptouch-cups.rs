@@ -0,0 +1,182 @@
+//! CUPS backend, install as `/usr/lib/cups/backend/ptouch` (mode 0700)
+//!
+//! Device URIs have the form `ptouch://labelprinter_1:9100`. The job is either
+//! CUPS raster (`application/vnd.cups-raster`, e.g. from the `pdftoraster` filter)
+//! or an image (PNG, PNM, ...). Pages are scaled to the width of the loaded tape,
+//! thresholded and sent over TCP as a single job, printer state is reported back
+//! through `STATE:` messages. Copies come from the `copies` argument when the job
+//! is a file argument, on stdin the filters made them already. The `NumCopies` of
+//! raster pages is ignored.
+use std::{
+    fs,
+    io::{self, Read},
+    net::{IpAddr, ToSocketAddrs},
+    process::ExitCode,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use embedded_graphics::prelude::Point;
+use image::{imageops, GrayImage};
+use ql_raster::{
+    commands::{Commands, PrintOptions},
+    cups::{self, STATE_REASONS},
+    printer,
+    render::{Render, RenderConfig},
+    status::Status,
+};
+
+/// Backend exit codes, see `backend(7)`
+const CUPS_BACKEND_OK: u8 = 0;
+const CUPS_BACKEND_FAILED: u8 = 1;
+const CUPS_BACKEND_RETRY_CURRENT: u8 = 7;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+
+    // Without arguments CUPS asks for the devices we can find
+    if args.len() == 1 {
+        list_devices();
+        return ExitCode::from(CUPS_BACKEND_OK);
+    }
+
+    if args.len() < 6 || args.len() > 7 {
+        eprintln!("Usage: {} job-id user title copies options [file]", args[0]);
+        return ExitCode::from(CUPS_BACKEND_FAILED);
+    }
+
+    match run(&args) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("ERROR: {e:#}");
+            ExitCode::from(CUPS_BACKEND_FAILED)
+        }
+    }
+}
+
+fn list_devices() {
+    match printer::discover(Duration::from_secs(2)) {
+        Ok(found) => {
+            for p in found {
+                println!(
+                    "network ptouch://{}:9100 \"{}\" \"{} ({})\"",
+                    p.ip_addr, p.model, p.model, p.ip_addr
+                );
+            }
+        }
+        Err(e) => eprintln!("DEBUG: Discovery failed: {e}"),
+    }
+    println!("network ptouch \"Unknown\" \"Brother QL/P-touch (TCP)\"");
+}
+
+fn run(args: &[String]) -> anyhow::Result<u8> {
+    let uri = std::env::var("DEVICE_URI").unwrap_or_else(|_| args[0].clone());
+    let addr = uri
+        .strip_prefix("ptouch://")
+        .ok_or_else(|| anyhow!("Invalid device URI {uri}"))?
+        .trim_end_matches('/')
+        .to_string();
+    let addr = if addr.contains(':') { addr } else { format!("{addr}:9100") };
+
+    // With a file argument the backend makes the copies, on stdin the filters already did (backend(7))
+    let mut data = Vec::new();
+    let copies: u32 = match args.get(6) {
+        Some(path) => {
+            data = fs::read(path).with_context(|| format!("Unable to read {path}"))?;
+            args[4].parse().unwrap_or(1).max(1)
+        }
+        None => {
+            io::stdin().read_to_end(&mut data)?;
+            1
+        }
+    };
+
+    let pages: Vec<GrayImage> = if data.starts_with(b"RaS") || data.get(1..4) == Some(b"SaR") {
+        cups::read_raster(&data)?.into_iter().map(|p| p.image).collect()
+    } else {
+        vec![image::load_from_memory(&data).context("Unsupported document format")?.to_luma8()]
+    };
+
+    let ip_addr = resolve(&addr)?;
+    let status = read_status(ip_addr);
+    if let Some(code) = status.as_ref().and_then(report_state) {
+        return Ok(code);
+    }
+
+    let options = PrintOptions { copies, ..Default::default() };
+    let options = match status.as_ref().and_then(|s| options.clone().with_loaded_media(s)) {
+        Some(options) => options,
+        None => {
+            eprintln!("DEBUG: Unknown loaded media, printing for {} mm tape", options.width);
+            options
+        }
+    };
+    let cfg = RenderConfig { y: options.printable_dots().unwrap_or(RenderConfig::default().y), ..Default::default() };
+
+    let total = pages.len();
+    let mut rasters = Vec::with_capacity(total);
+    for (i, page) in pages.iter().enumerate() {
+        eprintln!("INFO: Rendering page {} of {total}", i + 1);
+        rasters.push(rasterise(page, cfg)?);
+    }
+
+    eprintln!("INFO: Printing {total} pages, {copies} copies");
+    printer::from_addr(&addr)?.print_pages(&rasters, &options)?;
+    for i in 0..total {
+        eprintln!("PAGE: {} {copies}", i + 1);
+    }
+
+    // The job was sent, errors from here on only update the printer state
+    if let Some(status) = read_status(ip_addr) {
+        report_state(&status);
+    }
+    Ok(CUPS_BACKEND_OK)
+}
+
+/// Scale the page to the tape width and threshold it into a display.
+/// Page rows run along the tape, i.e. the top of the page is printed first.
+fn rasterise(page: &GrayImage, cfg: RenderConfig) -> anyhow::Result<Vec<Vec<u8>>> {
+    let (width, height) = page.dimensions();
+    let scaled_height = (height as u64 * cfg.y as u64 / width.max(1) as u64).max(1) as u32;
+    let scaled = imageops::resize(page, cfg.y as u32, scaled_height, imageops::FilterType::Triangle);
+
+    // Transpose, page rows become display columns
+    let transposed = GrayImage::from_fn(scaled.height(), scaled.width(), |x, y| *scaled.get_pixel(y, x));
+
+    let mut render = Render::new(cfg);
+    render.render_bitmap(&transposed, Point::zero())?;
+    Ok(render.raster()?)
+}
+
+fn read_status(ip_addr: IpAddr) -> Option<Status> {
+    match printer::snmp_status(ip_addr) {
+        Ok(status) => {
+            eprintln!("DEBUG: Printer status {status:?}");
+            Some(status)
+        }
+        Err(e) => {
+            eprintln!("DEBUG: Unable to read printer status: {e}");
+            None
+        }
+    }
+}
+
+/// Report the printer state to CUPS, returns an exit code if the job can not be printed now
+fn report_state(status: &Status) -> Option<u8> {
+    let reasons = cups::state_reasons(status.error_status);
+    eprintln!("STATE: -{}", STATE_REASONS.join(","));
+    if reasons.is_empty() {
+        return None;
+    }
+
+    eprintln!("STATE: +{}", reasons.join(","));
+    eprintln!("ERROR: Printer reports {:?}", status.error_status);
+    Some(CUPS_BACKEND_RETRY_CURRENT)
+}
+
+fn resolve(addr: &str) -> anyhow::Result<IpAddr> {
+    addr.to_socket_addrs()?
+        .next()
+        .map(|sa| sa.ip())
+        .ok_or_else(|| anyhow!("Unable to resolve {addr}"))
+}
@@ -0,0 +1,344 @@
+//! HTTP print server
+//!
+//! ```text
+//! ptouch-server [--listen 0.0.0.0:8080] [--printer labelprinter_1:9100] [--output label.bin]
+//! ```
+//!
+//! - `POST /print`: label description as JSON (`application/json`), TOML (`application/toml`) or YAML
+//!   (`application/yaml`), or a PNG (`image/png`)
+//! - `GET /status`: printer `Status` as JSON
+//! - `GET /preview`: PNG preview, same body and content types as `POST /print`
+//! - `GET /printers`: configured and discovered printers
+//!
+//! Labels are rendered for the media loaded in the printer unless the label
+//! description names its own.
+//!
+//! Without `--printer` jobs are written to the `--output` file and a fixed
+//! status is reported, so the server can be used without a printer.
+use std::{
+    io::Cursor,
+    net::{IpAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use embedded_graphics::prelude::Point;
+use image::{GrayImage, ImageFormat};
+use ql_raster::{
+    commands::{Commands, PrintOptions},
+    printer,
+    render::{
+        label::{Format, LabelDescription},
+        template::Record,
+        Render, RenderConfig,
+    },
+    status::Status,
+};
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Where the print jobs go
+enum Target {
+    Tcp(String),
+    File(String),
+}
+
+impl Target {
+    fn print(&self, raster: Vec<Vec<u8>>, options: &PrintOptions) -> anyhow::Result<()> {
+        // Connect per job, the printer only accepts a single connection at a time
+        match self {
+            Target::Tcp(addr) => printer::from_addr(addr)?.print_pages(&[raster], options)?,
+            Target::File(path) => printer::from_file(path)?.print_pages(&[raster], options)?,
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> anyhow::Result<Status> {
+        match self {
+            Target::Tcp(addr) => Ok(printer::snmp_status(resolve(addr)?)?),
+            Target::File(_) => Ok(Status::from(MOCK_STATUS)),
+        }
+    }
+
+    /// Options for the media loaded in the printer
+    fn media(&self) -> anyhow::Result<PrintOptions> {
+        let status = self.status().context("Unable to read the loaded media")?;
+        PrintOptions::default().with_loaded_media(&status).ok_or_else(|| {
+            anyhow!("Unsupported media {} x {} mm loaded", status.media_width, status.media_length)
+        })
+    }
+}
+
+/// Idle QL-820NWB with 62mm continuous tape, reported when printing to a file
+const MOCK_STATUS: [u8; 32] = [
+    0x80, 0x20, 0x42, 0x34, 0x41, 0x30, 0, 0, 0, 0, 62, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01,
+    0x08, 0, 0, 0, 0, 0, 0,
+];
+
+#[derive(Serialize)]
+struct PrinterEntry {
+    name: String,
+    address: String,
+    model: Option<String>,
+    source: &'static str,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+struct Args {
+    listen: String,
+    printer: Option<String>,
+    output: String,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut args = Args {
+        listen: "0.0.0.0:8080".into(),
+        printer: None,
+        output: "ptouch-server.bin".into(),
+    };
+
+    let mut it = std::env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| anyhow!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--listen" => args.listen = value()?,
+            "--printer" => args.printer = Some(value()?),
+            "--output" => args.output = value()?,
+            _ => bail!("Unknown argument {arg}"),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = parse_args()?;
+    let target = match &args.printer {
+        Some(addr) => Target::Tcp(addr.clone()),
+        None => {
+            eprintln!("Warning: no printer configured, writing jobs to {}", args.output);
+            Target::File(args.output.clone())
+        }
+    };
+
+    let server = Server::http(&args.listen).map_err(|e| anyhow!("Unable to listen on {}: {e}", args.listen))?;
+    eprintln!("Listening on {}", args.listen);
+
+    for mut request in server.incoming_requests() {
+        let response = match handle(&mut request, &target, &args) {
+            Ok(response) => response,
+            Err(e) => json(&ErrorBody { error: format!("{e:#}") }).with_status_code(400),
+        };
+
+        if let Err(e) = request.respond(response) {
+            eprintln!("Warning: failed to send response: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+fn handle(request: &mut Request, target: &Target, args: &Args) -> anyhow::Result<Response<Cursor<Vec<u8>>>> {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    let method = request.method().clone();
+
+    match (method, path.as_str()) {
+        (Method::Post, "/print") => {
+            let (render, options) = render_body(request, target)?;
+            target.print(render.raster()?, &options)?;
+            Ok(Response::from_string("").with_status_code(204))
+        }
+        (Method::Get, "/preview") | (Method::Post, "/preview") => {
+            let (render, _) = render_body(request, target)?;
+            let png = render.to_png()?;
+            Ok(Response::from_data(png).with_header(content_type("image/png")))
+        }
+        (Method::Get, "/status") => match target.status() {
+            Ok(status) => Ok(json(&status)),
+            Err(e) => Ok(json(&ErrorBody { error: format!("{e:#}") }).with_status_code(503)),
+        },
+        (Method::Get, "/printers") => Ok(json(&printers(args))),
+        _ => Ok(json(&ErrorBody { error: format!("No route for {path}") }).with_status_code(404)),
+    }
+}
+
+/// Render the request body, a label description or a PNG depending on the content type, and the options to print
+/// it with
+fn render_body(request: &mut Request, target: &Target) -> anyhow::Result<(Render, PrintOptions)> {
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Content-Type"))
+        .map(|h| h.value.as_str().to_string())
+        .unwrap_or_default();
+
+    let mut body = Vec::new();
+    request.as_reader().read_to_end(&mut body)?;
+
+    if content_type.starts_with("image/png") {
+        let image = image::load_from_memory_with_format(&body, ImageFormat::Png)
+            .context("Invalid PNG")?
+            .to_luma8();
+        let options = target.media()?;
+        Ok((render_png(&image, &options)?, options))
+    } else {
+        let text = String::from_utf8(body).context("Label description is not UTF-8")?;
+        let label = LabelDescription::parse(&text, Format::from_content_type(&content_type))?;
+        let options = match label.media {
+            Some(_) => label.print_options(PrintOptions::default())?,
+            None => target.media()?,
+        };
+        let cfg = label.render_config(render_config(&options))?;
+        // Fills in counters and the current date, there are no record fields
+        let mut pages = label.render_pages(&[Record::new()], cfg)?;
+        Ok((pages.remove(0), options))
+    }
+}
+
+/// Draw a PNG as is, its height runs across the tape
+fn render_png(image: &GrayImage, options: &PrintOptions) -> anyhow::Result<Render> {
+    let cfg = render_config(options);
+    if image.height() as usize > cfg.y {
+        bail!(
+            "Image taller than tape: {} dots high, the {} mm tape prints {} dots",
+            image.height(),
+            options.width,
+            cfg.y
+        );
+    }
+    let mut render = Render::new(cfg);
+    render.render_bitmap(image, Point::zero())?;
+    Ok(render)
+}
+
+fn render_config(options: &PrintOptions) -> RenderConfig {
+    let cfg = RenderConfig::default();
+    RenderConfig { y: options.printable_dots().unwrap_or(cfg.y), ..cfg }
+}
+
+fn printers(args: &Args) -> Vec<PrinterEntry> {
+    let mut entries = vec![match &args.printer {
+        Some(addr) => PrinterEntry {
+            name: "default".into(),
+            address: addr.clone(),
+            model: None,
+            source: "configured",
+        },
+        None => PrinterEntry {
+            name: "file".into(),
+            address: args.output.clone(),
+            model: None,
+            source: "configured",
+        },
+    }];
+
+    match printer::discover(Duration::from_secs(1)) {
+        Ok(found) => entries.extend(found.into_iter().map(|p| PrinterEntry {
+            name: p.ip_addr.to_string(),
+            address: format!("{}:9100", p.ip_addr),
+            model: Some(p.model),
+            source: "discovered",
+        })),
+        Err(e) => eprintln!("Warning: printer discovery failed: {e}"),
+    }
+
+    entries
+}
+
+fn resolve(addr: &str) -> anyhow::Result<IpAddr> {
+    addr.to_socket_addrs()?
+        .next()
+        .map(|sa| sa.ip())
+        .ok_or_else(|| anyhow!("Unable to resolve {addr}"))
+}
+
+fn json<T: Serialize>(value: &T) -> Response<Cursor<Vec<u8>>> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    Response::from_data(body).with_header(content_type("application/json"))
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], value.as_bytes()).expect("valid header")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tiny_http::TestRequest;
+
+    fn file_target(name: &str) -> (Target, Args) {
+        let path = std::env::temp_dir().join(format!("ptouch-server-{name}-{}.bin", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let args = Args { listen: String::new(), printer: None, output: path.clone() };
+        (Target::File(path), args)
+    }
+
+    fn request(method: Method, path: &str, mime: &str, body: &'static str) -> Request {
+        TestRequest::new()
+            .with_method(method)
+            .with_path(path)
+            .with_header(content_type(mime))
+            .with_body(body)
+            .into()
+    }
+
+    #[test]
+    fn print_to_file() {
+        let (target, args) = file_target("print");
+        let body = r#"{ "elements": [ { "type": "text", "text": "Hello" } ] }"#;
+        let response = handle(&mut request(Method::Post, "/print", "application/json", body), &target, &args).unwrap();
+        assert_eq!(response.status_code().0, 204);
+
+        // The job is for the 62 mm tape of the mock status
+        let Target::File(path) = &target else { unreachable!() };
+        let job = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let print_info = job.windows(3).position(|w| w == [0x1b, 0x69, 0x7a]).unwrap();
+        assert_eq!(job[print_info + 5], 62);
+    }
+
+    #[test]
+    fn preview_formats() {
+        let (target, args) = file_target("preview");
+        let toml = "[[elements]]\ntype = \"text\"\ntext = \"Hello\"\n";
+        let response = handle(&mut request(Method::Post, "/preview", "application/toml", toml), &target, &args).unwrap();
+        assert_eq!(response.status_code().0, 200);
+        let png = image::load_from_memory(&response.into_reader().into_inner()).unwrap();
+        // Rendered across the 696 dots of the loaded 62 mm tape
+        assert_eq!(png.height(), 696);
+
+        let yaml = "media: \"29\"\nelements:\n  - type: text\n    text: Hello\n";
+        let response = handle(&mut request(Method::Get, "/preview", "application/yaml", yaml), &target, &args).unwrap();
+        let png = image::load_from_memory(&response.into_reader().into_inner()).unwrap();
+        assert_eq!(png.height(), 306);
+
+        let bad = r#"{ "elements": [ { "type": "text", "txt": "Hello" } ] }"#;
+        let error = handle(&mut request(Method::Post, "/preview", "application/json", bad), &target, &args).err().unwrap();
+        assert!(error.to_string().contains("unknown field `txt`"), "{error}");
+    }
+
+    #[test]
+    fn status_and_routes() {
+        let (target, args) = file_target("status");
+        let response = handle(&mut request(Method::Get, "/status", "application/json", ""), &target, &args).unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&response.into_reader().into_inner()).unwrap();
+        assert_eq!(status["media_width"], 62);
+
+        let response = handle(&mut request(Method::Get, "/nothing", "text/plain", ""), &target, &args).unwrap();
+        assert_eq!(response.status_code().0, 404);
+    }
+
+    #[test]
+    fn png_taller_than_tape() {
+        let options = PrintOptions::default().with_media("29").unwrap();
+        let render = render_png(&GrayImage::new(40, 306), &options).unwrap();
+        assert_eq!(render.to_image().height(), 306);
+
+        let error = render_png(&GrayImage::new(40, 307), &options).err().unwrap();
+        assert_eq!(error.to_string(), "Image taller than tape: 307 dots high, the 29 mm tape prints 306 dots");
+    }
+}
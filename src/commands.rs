@@ -1,6 +1,12 @@
 use bitflags::bitflags;
 
-use crate::{commands, prelude::*, printer::PTouchPrinter, status::MediaKind};
+use crate::{
+    commands,
+    prelude::*,
+    printer::PTouchPrinter,
+    status::{MediaKind, Status},
+    PTouchError,
+};
 // Raw command API for the PTouch device.
 /// This provides low-level access to the device (if desired)
 pub trait Commands {
@@ -14,10 +20,10 @@ pub trait Commands {
     fn invalidate(&mut self) -> Result<()>;
 
     /// Issue a status request
-    // fn status_req(&mut self) -> Result<()>;
+    fn status_req(&mut self) -> Result<()>;
 
-    /// Read a status response with the provided timeout
-    // fn read_status(&mut self) -> Result<Status>;
+    /// Read a status response, using the read timeout of the interface
+    fn read_status(&mut self) -> Result<Status>;
 
     /// Switch mode, required for raster printing
     fn switch_mode(&mut self, mode: Mode) -> Result<()>;
@@ -58,6 +64,9 @@ pub trait Commands {
     
     /// prints rendered data shape vecvecu8
     fn print_data(&mut self, data: Vec<Vec<u8>>) -> Result<()>;
+
+    /// Print one or more rendered pages as a single job
+    fn print_pages(&mut self, pages: &[Vec<Vec<u8>>], options: &PrintOptions) -> Result<()>;
 }
 
 /// Low-level command API implementation
@@ -74,18 +83,25 @@ impl<I: PTouchInterface> Commands for PTouchPrinter<I> {
         self.write([0x1b, 0x40])
     }
 
-    // fn read_status(&mut self) -> Result<Status> {
-    //     let response = self.send_raw_with_response([0x1b, 0x69, 0x53])?;
-
-    //     println!("RESPONSE: {response:?}");
+    fn status_req(&mut self) -> Result<()> {
+        self.write([0x1b, 0x69, 0x53])?;
+        self.flush()
+    }
 
-    //     // let status = Status::from(status_raw);
-    //     // debug!("Status: {:?}", status);
-    //     // trace!("Raw status: {:?}", &status_raw);
-    //     // Ok(status)
+    fn read_status(&mut self) -> Result<Status> {
+        // The reply is always 32 bytes, but may arrive in pieces (e.g. over serial)
+        let mut status_raw = [0u8; 32];
+        let mut len = 0;
+        while len < status_raw.len() {
+            match self.interface.read(&mut status_raw[len..])? {
+                0 => return Err(PTouchError::InvalidStatusPayload),
+                n => len += n,
+            }
+        }
 
-    //     Status::try_from(response.as_slice())
-    // }
+        // trace!("Raw status: {:?}", &status_raw);
+        Ok(Status::from(status_raw))
+    }
 
     fn switch_mode(&mut self, mode: Mode) -> Result<()> {
         self.write([0x1b, 0x69, 0x61, mode as u8])
@@ -128,6 +144,11 @@ impl<I: PTouchInterface> Commands for PTouchPrinter<I> {
         let raster_bytes = info.raster_no.to_le_bytes();
         buff[7..11].copy_from_slice(&raster_bytes);
 
+        buff[11] = match info.first_page {
+            true => 0,
+            false => 1,
+        };
+
         if info.recover {
             buff[3] |= 0x80;
         }
@@ -186,37 +207,52 @@ impl<I: PTouchInterface> Commands for PTouchPrinter<I> {
         self.write(buff.as_slice())
     }
     
-    fn print_data(&mut self, data: Vec<Vec<u8>>) -> Result<()>{ 
+    fn print_data(&mut self, data: Vec<Vec<u8>>) -> Result<()> {
+        self.print_pages(&[data], &PrintOptions::default())
+    }
+
+    fn print_pages(&mut self, pages: &[Vec<Vec<u8>>], options: &PrintOptions) -> Result<()> {
         self.invalidate()?;
         self.init()?;
-        
-        self.switch_mode(Mode::Raster)?;
-        
-        let pi = PrintInfo {
-            kind: Some(crate::status::MediaKind::ContinuousLengthTape),
-            width: Some(62),
-            length: None,
-            raster_no: 1,
-            recover: true,
-        };
-        self.set_print_info(&pi)?;
-
-        self.set_various_mode(VariousMode::AUTO_CUT)?;
-        // printer.set_advanced_mode(AdvancedMode::);
-        self.set_page_no(1)?;
 
-        self.set_margin(0)?;
-        self.set_compression_mode(CompressionMode::None)?;
+        self.switch_mode(Mode::Raster)?;
 
-        for line in data {
-            self.transfer_raster_line(&line).expect("Failed to transfer raster line");
+        let total = pages.len() * options.copies.max(1) as usize;
+        let (various, advanced) = options.mode_flags();
+
+        for (i, page) in pages.iter().cycle().take(total).enumerate() {
+            let pi = PrintInfo {
+                kind: Some(options.media_kind),
+                width: Some(options.width),
+                length: Some(options.length),
+                raster_no: page.len() as u32,
+                recover: true,
+                first_page: i == 0,
+            };
+            self.set_print_info(&pi)?;
+
+            self.set_various_mode(various)?;
+            self.set_advanced_mode(advanced)?;
+            self.set_page_no(match options.cut {
+                CutMode::End => total.min(u8::MAX as usize) as u8,
+                _ => 1,
+            })?;
+
+            self.set_margin(options.margin)?;
+            self.set_compression_mode(CompressionMode::None)?;
+
+            for line in page {
+                self.transfer_raster_line(line)?;
+            }
+
+            // Only the last page feeds, the others just print
+            match i + 1 == total {
+                true => self.print_and_feed()?,
+                false => self.print()?,
+            }
         }
 
-        self.print_and_feed()?;
-
-        self.flush();
-        Ok(())
-        
+        self.flush()
     }
 
     fn raster_zero(&mut self) -> Result<()> {
@@ -282,6 +318,8 @@ pub struct PrintInfo {
     pub raster_no: u32,
     /// Enable print recovery
     pub recover: bool,
+    /// First page of a (multi page) job
+    pub first_page: bool,
 }
 
 impl Default for PrintInfo {
@@ -292,6 +330,125 @@ impl Default for PrintInfo {
             length: Some(0),
             raster_no: 0,
             recover: true,
+            first_page: true,
+        }
+    }
+}
+
+/// When the cutter is used
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum CutMode {
+    /// Cut after every label
+    #[default]
+    Auto,
+    /// Half cut between labels, full cut at the end (P-touch laminated tapes only)
+    Half,
+    /// Cut once after the last label
+    End,
+    /// Never cut
+    None,
+}
+
+/// Settings for a print job
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrintOptions {
+    /// Media kind
+    pub media_kind: MediaKind,
+    /// Tape width in mm
+    pub width: u8,
+    /// Label length in mm, 0 for continuous tape
+    pub length: u8,
+    pub cut: CutMode,
+    /// Number of times the pages are printed
+    pub copies: u32,
+    /// Double vertical resolution, the pages need twice the number of raster lines
+    pub high_res: bool,
+    /// Feed margin in dots
+    pub margin: u16,
+}
+
+impl PrintOptions {
+    /// Various and advanced mode flags for these options
+    pub fn mode_flags(&self) -> (VariousMode, AdvancedMode) {
+        let mut various = VariousMode::empty();
+        let mut advanced = AdvancedMode::empty();
+
+        match self.cut {
+            CutMode::Auto | CutMode::End => {
+                various |= VariousMode::AUTO_CUT;
+                advanced |= AdvancedMode::NO_CHAIN;
+            }
+            CutMode::Half => {
+                various |= VariousMode::AUTO_CUT;
+                advanced |= AdvancedMode::HALF_CUT | AdvancedMode::NO_CHAIN;
+            }
+            CutMode::None => {}
+        }
+
+        if self.high_res {
+            advanced |= AdvancedMode::HIGH_RES;
+        }
+
+        (various, advanced)
+    }
+
+    /// Set the media from its name, the tape width in mm for continuous tape (e.g. `62`) or WIDTHxLENGTH for
+    /// die-cut labels (e.g. `62x29`). `None` for names that don't match a known media.
+    pub fn with_media(mut self, media: &str) -> Option<Self> {
+        let (width, length) = match media.trim().split_once('x') {
+            Some((w, l)) => (w.parse().ok()?, l.parse().ok()?),
+            None => (media.trim().parse().ok()?, 0),
+        };
+        self.media_kind = match length {
+            0 => MediaKind::ContinuousLengthTape,
+            _ => MediaKind::DieCutLabels,
+        };
+        (self.width, self.length) = (width, length);
+        self.printable_dots().map(|_| self)
+    }
+
+    /// Set the media reported in a printer status, `None` if no known media is loaded
+    pub fn with_loaded_media(self, status: &Status) -> Option<Self> {
+        match status.media_length {
+            0 => self.with_media(&status.media_width.to_string()),
+            length => self.with_media(&format!("{}x{length}", status.media_width)),
+        }
+    }
+
+    /// Printable dots across the tape for the 300 dpi QL models, i.e. the render height
+    pub fn printable_dots(&self) -> Option<usize> {
+        let dots = match (self.width, self.length) {
+            (12, 0) => 106,
+            (29, 0) => 306,
+            (38, 0) => 413,
+            (50, 0) => 554,
+            (54, 0) => 590,
+            (62, 0) => 696,
+            (102, 0) => 1164,
+            (17, 54) | (17, 87) => 165,
+            (23, 23) => 202,
+            (29, 42) | (29, 90) => 306,
+            (38, 90) => 413,
+            (39, 48) => 425,
+            (52, 29) => 578,
+            (62, 29) | (62, 100) => 696,
+            (102, 51) | (102, 152) => 1164,
+            _ => return None,
+        };
+        Some(dots)
+    }
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            media_kind: MediaKind::ContinuousLengthTape,
+            width: 62,
+            length: 0,
+            cut: CutMode::Auto,
+            copies: 1,
+            high_res: false,
+            margin: 0,
         }
     }
 }
@@ -0,0 +1,362 @@
+use std::io;
+
+use image::GrayImage;
+
+use crate::{status::ErrorStatus, PTouchError, Result};
+
+/// A page from a CUPS raster stream, converted to greyscale (0 is black)
+pub struct RasterPage {
+    /// Horizontal and vertical resolution in dpi
+    pub hw_resolution: [u32; 2],
+    pub num_copies: u32,
+    pub image: GrayImage,
+}
+
+/// Colour spaces we know how to convert to grey
+const CSPACE_W: u32 = 0;
+const CSPACE_RGB: u32 = 1;
+const CSPACE_K: u32 = 3;
+const CSPACE_SW: u32 = 18;
+const CSPACE_SRGB: u32 = 19;
+
+/// Largest page accepted in pixels, A4 at 600 dpi has 35 million
+const MAX_PIXELS: u64 = 100_000_000;
+
+/// Page header fields we use, see `cups_page_header2_t`
+struct PageHeader {
+    hw_resolution: [u32; 2],
+    num_copies: u32,
+    width: u32,
+    height: u32,
+    bits_per_pixel: u32,
+    bytes_per_line: u32,
+    color_space: u32,
+}
+
+/// Parse a CUPS raster stream (`application/vnd.cups-raster`, version 1, 2 or 3)
+pub fn read_raster(data: &[u8]) -> Result<Vec<RasterPage>> {
+    let sync = data.get(..4).ok_or_else(|| invalid("Missing sync word"))?;
+    let (big_endian, version) = match sync {
+        b"RaSt" => (true, 1),
+        b"tSaR" => (false, 1),
+        b"RaS2" => (true, 2),
+        b"2SaR" => (false, 2),
+        b"RaS3" => (true, 3),
+        b"3SaR" => (false, 3),
+        _ => return Err(invalid("Not a CUPS raster stream")),
+    };
+    let header_len = if version == 1 { 420 } else { 1796 };
+
+    let mut pages = Vec::new();
+    let mut rest = &data[4..];
+    while !rest.is_empty() {
+        let raw = rest.get(..header_len).ok_or_else(|| invalid("Truncated page header"))?;
+        let header = PageHeader::parse(raw, big_endian);
+        rest = &rest[header_len..];
+        header.validate(rest.len(), version == 2)?;
+
+        let (image, used) = match version {
+            2 => header.decode_compressed(rest)?,
+            _ => header.decode(rest)?,
+        };
+        rest = &rest[used..];
+
+        pages.push(RasterPage {
+            hw_resolution: header.hw_resolution,
+            num_copies: header.num_copies.max(1),
+            image,
+        });
+    }
+
+    Ok(pages)
+}
+
+impl PageHeader {
+    fn parse(raw: &[u8], big_endian: bool) -> Self {
+        let field = |offset: usize| {
+            let b = [raw[offset], raw[offset + 1], raw[offset + 2], raw[offset + 3]];
+            match big_endian {
+                true => u32::from_be_bytes(b),
+                false => u32::from_le_bytes(b),
+            }
+        };
+
+        Self {
+            hw_resolution: [field(276), field(280)],
+            num_copies: field(340),
+            width: field(372),
+            height: field(376),
+            bits_per_pixel: field(388),
+            bytes_per_line: field(392),
+            color_space: field(400),
+        }
+    }
+
+    /// Reject headers the page data can't be decoded with, before anything is allocated
+    fn validate(&self, data_len: usize, compressed: bool) -> Result<()> {
+        let supported = matches!(
+            (self.bits_per_pixel, self.color_space),
+            (1 | 8 | 16, CSPACE_W | CSPACE_SW | CSPACE_K) | (24, CSPACE_RGB | CSPACE_SRGB)
+        );
+        if !supported {
+            return Err(invalid(&format!(
+                "Unsupported raster format: {} bits, colour space {}",
+                self.bits_per_pixel, self.color_space
+            )));
+        }
+
+        let (width, height, bpl) = (u64::from(self.width), u64::from(self.height), u64::from(self.bytes_per_line));
+        let line_len = width.checked_mul(u64::from(self.bits_per_pixel)).map(|bits| bits.div_ceil(8));
+        if bpl == 0 || line_len.is_none_or(|len| bpl < len) {
+            return Err(invalid(&format!("{} bytes per line is too short for {} pixels", bpl, self.width)));
+        }
+
+        // Compressed data holds at least a repeat count and a code per 256 lines
+        let data_len = data_len as u64;
+        let fits = match compressed {
+            true => height.div_ceil(256) * 2 <= data_len,
+            false => bpl.checked_mul(height).is_some_and(|len| len <= data_len),
+        };
+        if !fits || width.saturating_mul(height) > MAX_PIXELS {
+            return Err(invalid(&format!("Page of {}x{} pixels doesn't match the data", self.width, self.height)));
+        }
+        Ok(())
+    }
+
+    /// Bytes per pixel (or per group of pixels for less than 8 bits)
+    fn pixel_len(&self) -> usize {
+        (self.bits_per_pixel as usize).div_ceil(8)
+    }
+
+    /// Value used for "clear to end of line" in compressed lines
+    fn blank(&self) -> u8 {
+        match self.color_space {
+            CSPACE_W | CSPACE_SW | CSPACE_RGB | CSPACE_SRGB => 0xff,
+            _ => 0x00,
+        }
+    }
+
+    /// Uncompressed lines (version 1 and 3)
+    fn decode(&self, data: &[u8]) -> Result<(GrayImage, usize)> {
+        let len = self.bytes_per_line as usize * self.height as usize;
+        let lines = data.get(..len).ok_or_else(|| invalid("Truncated page data"))?;
+
+        let mut image = GrayImage::new(self.width, self.height);
+        for (y, line) in lines.chunks(self.bytes_per_line as usize).enumerate() {
+            self.convert_line(line, y as u32, &mut image)?;
+        }
+        Ok((image, len))
+    }
+
+    /// PackBits-like compressed lines (version 2)
+    fn decode_compressed(&self, data: &[u8]) -> Result<(GrayImage, usize)> {
+        let bpl = self.bytes_per_line as usize;
+        let pixel_len = self.pixel_len();
+        let mut image = GrayImage::new(self.width, self.height);
+        let mut pos = 0;
+        let mut y = 0;
+
+        let mut next = |len: usize| {
+            let bytes = data.get(pos..pos + len).ok_or_else(|| invalid("Truncated page data"));
+            pos += len;
+            bytes
+        };
+
+        while y < self.height {
+            let repeat = next(1)?[0] as u32 + 1;
+
+            let mut line = Vec::with_capacity(bpl);
+            while line.len() < bpl {
+                let n = next(1)?[0];
+                match n {
+                    128 => line.resize(bpl, self.blank()),
+                    0..=127 => {
+                        let pixel = next(pixel_len)?;
+                        for _ in 0..=n {
+                            line.extend_from_slice(pixel);
+                        }
+                    }
+                    _ => line.extend_from_slice(next((257 - n as usize) * pixel_len)?),
+                }
+            }
+            line.truncate(bpl);
+
+            for _ in 0..repeat.min(self.height - y) {
+                self.convert_line(&line, y, &mut image)?;
+                y += 1;
+            }
+        }
+
+        Ok((image, pos))
+    }
+
+    fn convert_line(&self, line: &[u8], y: u32, image: &mut GrayImage) -> Result<()> {
+        let inverted = !matches!(self.color_space, CSPACE_W | CSPACE_SW | CSPACE_RGB | CSPACE_SRGB);
+
+        for x in 0..self.width {
+            let i = x as usize;
+            let value = match (self.bits_per_pixel, self.color_space) {
+                (1, CSPACE_W | CSPACE_SW | CSPACE_K) => ((line[i / 8] >> (7 - i % 8)) & 1) * 255,
+                (8, CSPACE_W | CSPACE_SW | CSPACE_K) => line[i],
+                (16, CSPACE_W | CSPACE_SW | CSPACE_K) => line[i * 2],
+                (24, CSPACE_RGB | CSPACE_SRGB) => {
+                    let p = &line[i * 3..i * 3 + 3];
+                    ((p[0] as u16 + p[1] as u16 + p[2] as u16) / 3) as u8
+                }
+                (bits, space) => {
+                    return Err(invalid(&format!("Unsupported raster format: {bits} bits, colour space {space}")))
+                }
+            };
+
+            image.put_pixel(x, y, image::Luma([if inverted { 255 - value } else { value }]));
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(msg: &str) -> PTouchError {
+    PTouchError::IoError(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+/// All `printer-state-reasons` keywords reported by `state_reasons`
+pub const STATE_REASONS: &[&str] = &[
+    "media-empty-error",
+    "media-jam-error",
+    "media-needed-error",
+    "cover-open-error",
+    "offline-report",
+    "other-error",
+];
+
+/// Map the printer error flags to CUPS `printer-state-reasons` keywords
+pub fn state_reasons(error: ErrorStatus) -> Vec<&'static str> {
+    let mapping = [
+        (ErrorStatus::NO_MEDIA | ErrorStatus::END_OF_MEDIA | ErrorStatus::MEDIA_END, "media-empty-error"),
+        (ErrorStatus::CUTTER_JAM, "media-jam-error"),
+        (ErrorStatus::WRONG_MEDIA, "media-needed-error"),
+        (ErrorStatus::COVER_OPEN, "cover-open-error"),
+        (ErrorStatus::PRINTER_OFF, "offline-report"),
+        (
+            ErrorStatus::HIGH_VOLT
+                | ErrorStatus::FAN_MOTOR_ERROR
+                | ErrorStatus::EXP_BUFFER_FULL
+                | ErrorStatus::COMMS_ERROR
+                | ErrorStatus::COMMS_BUFFFER_FULL
+                | ErrorStatus::OVERHEAT
+                | ErrorStatus::SYSTEM_ERROR,
+            "other-error",
+        ),
+    ];
+
+    mapping
+        .into_iter()
+        .filter(|(flags, _)| error.intersects(*flags))
+        .map(|(_, keyword)| keyword)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(sync: &[u8; 4], width: u32, height: u32, bits: u32, bpl: u32, space: u32) -> Vec<u8> {
+        let mut h = vec![0u8; 1796];
+        let mut set = |offset: usize, v: u32| h[offset..offset + 4].copy_from_slice(&v.to_be_bytes());
+        set(276, 300);
+        set(280, 300);
+        set(340, 1);
+        set(372, width);
+        set(376, height);
+        set(384, bits);
+        set(388, bits);
+        set(392, bpl);
+        set(400, space);
+
+        let mut data = sync.to_vec();
+        data.extend(h);
+        data
+    }
+
+    #[test]
+    fn uncompressed_k() {
+        let mut data = header(b"RaS3", 8, 2, 1, 1, CSPACE_K);
+        data.extend([0b1000_0001, 0b0000_0000]);
+
+        let pages = read_raster(&data).unwrap();
+        assert_eq!(pages.len(), 1);
+        let image = &pages[0].image;
+        assert_eq!(image.dimensions(), (8, 2));
+        assert_eq!(image.get_pixel(0, 0).0, [0]);
+        assert_eq!(image.get_pixel(1, 0).0, [255]);
+        assert_eq!(image.get_pixel(7, 0).0, [0]);
+        assert_eq!(image.get_pixel(0, 1).0, [255]);
+    }
+
+    #[test]
+    fn compressed_w() {
+        let mut data = header(b"RaS2", 4, 3, 8, 4, CSPACE_W);
+        // Line repeated twice: 2x black (repeat), 2 literal pixels
+        data.extend([1, 1, 0x00, 255, 0x80, 0xff]);
+        // Clear to end of line
+        data.extend([0, 128]);
+
+        let image = &read_raster(&data).unwrap()[0].image;
+        for y in 0..2 {
+            assert_eq!(image.get_pixel(0, y).0, [0x00]);
+            assert_eq!(image.get_pixel(1, y).0, [0x00]);
+            assert_eq!(image.get_pixel(2, y).0, [0x80]);
+            assert_eq!(image.get_pixel(3, y).0, [0xff]);
+        }
+        assert_eq!(image.get_pixel(0, 2).0, [0xff]);
+    }
+
+    #[test]
+    fn invalid_headers() {
+        let error = |data: Vec<u8>| read_raster(&data).err().unwrap().to_string();
+
+        // No bytes per line
+        let mut data = header(b"RaS3", 8, 2, 1, 0, CSPACE_K);
+        data.extend([0; 2]);
+        assert!(error(data).contains("0 bytes per line"));
+
+        // Lines shorter than the width, for each pixel size
+        for (bits, bpl, space) in [(1, 1, CSPACE_K), (8, 8, CSPACE_W), (16, 17, CSPACE_SW), (24, 29, CSPACE_RGB)] {
+            let mut data = header(b"RaS3", 10, 1, bits, bpl, space);
+            data.extend(vec![0; bpl as usize]);
+            assert!(error(data).contains("too short for 10 pixels"), "{bits} bits");
+        }
+        let mut data = header(b"RaS2", 10, 1, 8, 4, CSPACE_W);
+        data.extend([0, 128]);
+        assert!(error(data).contains("too short"));
+
+        // Width times bits overflows
+        let data = header(b"RaS3", u32::MAX, 1, 24, u32::MAX, CSPACE_RGB);
+        assert!(error(data).contains("too short"));
+
+        let data = header(b"RaS3", 8, 2, 4, 4, CSPACE_K);
+        assert!(error(data).contains("Unsupported raster format: 4 bits, colour space 3"));
+        let data = header(b"RaS3", 8, 2, 8, 8, CSPACE_RGB);
+        assert!(error(data).contains("Unsupported raster format"));
+
+        // Dimensions beyond the data, uncompressed and compressed
+        let mut data = header(b"RaS3", 8, 1_000_000, 8, 8, CSPACE_W);
+        data.extend([0; 64]);
+        assert!(error(data).contains("doesn't match the data"));
+        let mut data = header(b"RaS2", 8, 1_000_000, 8, 8, CSPACE_W);
+        data.extend([255, 128]);
+        assert!(error(data).contains("doesn't match the data"));
+        let mut data = header(b"RaS2", 1_000_000, 1_000, 8, 1_000_000, CSPACE_W);
+        data.extend([255, 128, 255, 128, 255, 128, 255, 128]);
+        assert!(error(data).contains("doesn't match the data"));
+    }
+
+    #[test]
+    fn reasons() {
+        assert!(state_reasons(ErrorStatus::empty()).is_empty());
+        assert_eq!(
+            state_reasons(ErrorStatus::NO_MEDIA | ErrorStatus::COVER_OPEN),
+            vec!["media-empty-error", "cover-open-error"]
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use std::{
+    fs::File,
     io::{Read, Write},
     net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -18,6 +20,16 @@ pub trait PTouchInterface: Sized {
     fn flush(&mut self) -> Result<()> {
         Ok(())
     }
+
+    /// Limit how long a read waits for data, `None` waits forever. Interfaces that can't block ignore it.
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Does the printer send status replies over this interface
+    fn reports_status(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -65,4 +77,199 @@ impl PTouchInterface for PTouchTcpInterface {
         self.socket.read_to_end(&mut buf)?;
         Ok(buf)
     }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)?;
+        Ok(())
+    }
+}
+
+/// Writes the command stream to a file instead of a printer, e.g. for offline testing
+/// or for capturing a job to send later. Reads never return any data.
+#[derive(Debug)]
+pub struct PTouchFileInterface {
+    path: PathBuf,
+    file: File,
+}
+
+impl PTouchFileInterface {
+    /// Create (or truncate) the output file
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path)?;
+
+        Ok(PTouchFileInterface { path, file })
+    }
+}
+
+impl PTouchInterface for PTouchFileInterface {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn name(&self) -> String {
+        format!("PTouch file interface on {}", self.path.display())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.file.write_all(data)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn reports_status(&self) -> bool {
+        false
+    }
+}
+
+/// Serial port settings, used for the RS-232 models and for Bluetooth SPP
+/// (e.g. the QL-820NWB bound to `/dev/rfcomm0`)
+#[cfg(feature = "serial")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerialConfig {
+    pub baud_rate: u32,
+    pub flow_control: serialport::FlowControl,
+    pub read_timeout: Duration,
+}
+
+#[cfg(feature = "serial")]
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            baud_rate: 9600,
+            flow_control: serialport::FlowControl::Hardware,
+            read_timeout: Duration::from_millis(1500),
+        }
+    }
+}
+
+#[cfg(feature = "serial")]
+pub struct PTouchSerialInterface {
+    name: String,
+    port: Box<dyn serialport::SerialPort>,
+}
+
+#[cfg(feature = "serial")]
+impl PTouchSerialInterface {
+    /// Open the serial device at `path` (e.g. `/dev/ttyUSB0` or `/dev/rfcomm0`), 8N1
+    pub fn new(path: &str, cfg: SerialConfig) -> Result<Self> {
+        let port = serialport::new(path, cfg.baud_rate)
+            .data_bits(serialport::DataBits::Eight)
+            .parity(serialport::Parity::None)
+            .stop_bits(serialport::StopBits::One)
+            .flow_control(cfg.flow_control)
+            .timeout(cfg.read_timeout)
+            .open()
+            .map_err(std::io::Error::from)?;
+
+        Ok(Self::from_port(port))
+    }
+
+    /// Wrap an already opened port, e.g. one end of a pseudo-terminal pair
+    pub fn from_port(port: Box<dyn serialport::SerialPort>) -> Self {
+        let name = port.name().unwrap_or_default();
+        PTouchSerialInterface { name, port }
+    }
+}
+
+#[cfg(feature = "serial")]
+impl std::fmt::Debug for PTouchSerialInterface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PTouchSerialInterface").field("name", &self.name).finish()
+    }
+}
+
+#[cfg(feature = "serial")]
+impl PTouchInterface for PTouchSerialInterface {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.port.read(buf)?)
+    }
+
+    fn name(&self) -> String {
+        format!("PTouch serial interface on {}", self.name)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.port.write_all(data)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.port.flush()?;
+        Ok(())
+    }
+
+    /// A serial line never reaches EOF, so read until the port times out
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 64];
+        loop {
+            match self.port.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(buf)
+    }
+}
+
+#[cfg(all(test, feature = "serial", unix))]
+mod test {
+    use super::*;
+    use crate::commands::Commands;
+    use crate::printer::PTouchPrinter;
+    use crate::status::{MediaKind, Model};
+    use serialport::{SerialPort, TTYPort};
+
+    // Pseudo-terminal pair, `device` stands in for the printer
+    fn pty_interface() -> (TTYPort, PTouchSerialInterface) {
+        let (mut device, mut host) = TTYPort::pair().expect("Unable to create pty pair");
+        device.set_timeout(Duration::from_millis(500)).unwrap();
+        host.set_timeout(Duration::from_millis(500)).unwrap();
+        (device, PTouchSerialInterface::from_port(Box::new(host)))
+    }
+
+    #[test]
+    fn serial_write() {
+        let (mut device, interface) = pty_interface();
+        let mut printer = PTouchPrinter::with_interface(interface);
+        printer.init().unwrap();
+
+        let mut buf = [0u8; 2];
+        device.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0x1b, 0x40]);
+    }
+
+    #[test]
+    fn serial_read_status() {
+        let (mut device, interface) = pty_interface();
+        let mut printer = PTouchPrinter::with_interface(interface);
+        printer.status_req().unwrap();
+
+        let mut req = [0u8; 3];
+        device.read_exact(&mut req).unwrap();
+        assert_eq!(req, [0x1b, 0x69, 0x53]);
+
+        let mut reply = [0u8; 32];
+        reply[..4].copy_from_slice(&[0x80, 0x20, 0x42, 0x34]);
+        reply[4] = 0x41; // QL-820NWB
+        reply[10] = 62;
+        reply[11] = 0x0a;
+        device.write_all(&reply).unwrap();
+
+        let status = printer.read_status().unwrap();
+        assert_eq!(status.model, Model::QL820NWB);
+        assert_eq!(status.media_width, 62);
+        assert_eq!(status.media_kind, MediaKind::ContinuousLengthTape);
+    }
 }
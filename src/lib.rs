@@ -3,12 +3,16 @@ use std::io;
 pub mod interface;
 pub mod printer;
 pub mod commands;
+pub mod cups;
 pub mod status;
+pub mod spooler;
 // In src/lib.rs
 pub mod render;
 
 pub mod prelude {
-    pub use super::interface::{PTouchInterface, PTouchTcpInterface};
+    pub use super::interface::{PTouchFileInterface, PTouchInterface, PTouchTcpInterface};
+    #[cfg(feature = "serial")]
+    pub use super::interface::{PTouchSerialInterface, SerialConfig};
     pub use super::printer;
     pub use super::status::Status;
     pub use super::Result;
@@ -25,6 +29,49 @@ pub enum PTouchError {
     InvalidStatusPayload,
     SNMPError,
     RenderError,
+    InvalidFont,
+    BarcodeError(render::barcode::BarcodeError),
+    PayloadError(render::qr_payload::PayloadError),
+    ImageError(image::ImageError),
+    #[cfg(feature = "svg")]
+    SvgError(resvg::usvg::Error),
+    #[cfg(feature = "serde")]
+    LabelError(render::label::LabelError),
+}
+
+impl std::fmt::Display for PTouchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PTouchError::IoError(e) => write!(f, "IO error: {e}"),
+            PTouchError::InvalidStatusPayload => write!(f, "Invalid status payload"),
+            PTouchError::SNMPError => write!(f, "SNMP request failed"),
+            PTouchError::RenderError => write!(f, "Render error"),
+            PTouchError::InvalidFont => write!(f, "Invalid font data"),
+            PTouchError::BarcodeError(e) => write!(f, "Barcode error: {e}"),
+            PTouchError::PayloadError(e) => write!(f, "QR payload error: {e}"),
+            PTouchError::ImageError(e) => write!(f, "Image error: {e}"),
+            #[cfg(feature = "svg")]
+            PTouchError::SvgError(e) => write!(f, "SVG error: {e}"),
+            #[cfg(feature = "serde")]
+            PTouchError::LabelError(e) => write!(f, "Label error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PTouchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PTouchError::IoError(e) => Some(e),
+            PTouchError::BarcodeError(e) => Some(e),
+            PTouchError::PayloadError(e) => Some(e),
+            PTouchError::ImageError(e) => Some(e),
+            #[cfg(feature = "svg")]
+            PTouchError::SvgError(e) => Some(e),
+            #[cfg(feature = "serde")]
+            PTouchError::LabelError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<io::Error> for PTouchError {
@@ -32,3 +79,35 @@ impl From<io::Error> for PTouchError {
         PTouchError::IoError(io_error)
     }
 }
+
+impl From<image::ImageError> for PTouchError {
+    fn from(image_error: image::ImageError) -> Self {
+        PTouchError::ImageError(image_error)
+    }
+}
+
+#[cfg(feature = "svg")]
+impl From<resvg::usvg::Error> for PTouchError {
+    fn from(svg_error: resvg::usvg::Error) -> Self {
+        PTouchError::SvgError(svg_error)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<render::label::LabelError> for PTouchError {
+    fn from(label_error: render::label::LabelError) -> Self {
+        PTouchError::LabelError(label_error)
+    }
+}
+
+impl From<render::barcode::BarcodeError> for PTouchError {
+    fn from(barcode_error: render::barcode::BarcodeError) -> Self {
+        PTouchError::BarcodeError(barcode_error)
+    }
+}
+
+impl From<render::qr_payload::PayloadError> for PTouchError {
+    fn from(payload_error: render::qr_payload::PayloadError) -> Self {
+        PTouchError::PayloadError(payload_error)
+    }
+}
@@ -1,47 +1,446 @@
-use std::time::Duration;
-use embedded_graphics::prelude::Dimensions;
+use std::{fs, net::ToSocketAddrs, path::{Path, PathBuf}, time::Duration};
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use embedded_graphics::{prelude::*, primitives::Rectangle};
 use ql_raster::{
-    commands::{Commands, PrintInfo, VariousMode},
-    prelude::*,
-    printer::PTouchPrinter,
-};
-use ql_raster::prelude::display::Display;
-use embedded_graphics::{
-    mono_font::{ascii::FONT_6X10, MonoTextStyle},
-    pixelcolor::BinaryColor,
+    commands::{Commands, CutMode, PrintOptions},
     prelude::*,
+    printer::{self, PTouchPrinter},
+    render::{
+        font::{Font, TtfTextStyle},
+        label::{Format, LabelDescription},
+        layout::{Node, Stack},
+        ops::{Dither, FontKind, HAlign, ImageOptions, QrOptions, TextOptions},
+        template::{self, Record},
+        Orientation, Render, RenderConfig,
+    },
 };
-use embedded_graphics::mono_font::iso_8859_13::FONT_10X20;
-use embedded_graphics::text::Text;
-use ql_raster::render::{Render, RenderConfig};
+#[cfg(feature = "svg")]
+use ql_raster::render::svg::Svg;
+
+/// Print labels on Brother QL / P-touch printers
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(flatten)]
+    device: DeviceArgs,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render and print a label
+    Print {
+        #[command(flatten)]
+        content: ContentArgs,
+        #[command(flatten)]
+        job: JobArgs,
+    },
+    /// Show a preview window, or write a PNG/PBM file
+    Preview {
+        #[command(flatten)]
+        content: ContentArgs,
+        #[command(flatten)]
+        job: JobArgs,
+        /// Write the preview to this file (.png or .pbm) instead of opening a window, numbered for several pages
+        #[arg(long, short = 'f')]
+        file: Option<PathBuf>,
+    },
+    /// Read the printer status
+    Status,
+    /// Show printer name, model and serial (SNMP)
+    Info,
+    /// Find printers on the local network
+    Discover {
+        /// Seconds to wait for replies
+        #[arg(long, default_value_t = 2)]
+        timeout: u64,
+    },
+    /// Send a raw command capture (.bin) to the printer
+    Raw { file: PathBuf },
+}
+
+/// Where the printer is, `--printer` (TCP), `--serial` or `--output` (file)
+#[derive(Args)]
+struct DeviceArgs {
+    /// Printer address, e.g. labelprinter_1:9100
+    #[arg(long, short, global = true, env = "PTOUCH_PRINTER")]
+    printer: Option<String>,
+    /// Serial or Bluetooth device, e.g. /dev/rfcomm0
+    #[arg(long, global = true, conflicts_with = "printer")]
+    serial: Option<String>,
+    /// Serial baud rate
+    #[arg(long, global = true, default_value_t = 9600)]
+    baud: u32,
+    /// Write the command stream to a file instead of a printer
+    #[arg(long, short, global = true, conflicts_with_all = ["printer", "serial"])]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ContentArgs {
+    /// Text to print
+    #[arg(long, short)]
+    text: Option<String>,
+    /// TrueType / OpenType font for the text (.ttf, .otf), uses the built-in font if not given
+    #[arg(long)]
+    font: Option<PathBuf>,
+    /// Font size in points
+    #[arg(long, default_value_t = 24.0, requires = "font")]
+    font_size: f32,
+    /// Built-in font, used without --font
+    #[arg(long, value_enum, default_value_t = BitmapFont::Font24x32)]
+    bitmap_font: BitmapFont,
+    /// QR code content
+    #[arg(long)]
+    qr: Option<String>,
+    /// Code 128 barcode content, GS1-128 when starting with an application identifier like (01)
+    #[arg(long)]
+    code128: Option<String>,
+    /// Image file, scaled to the tape width
+    #[arg(long)]
+    image: Option<PathBuf>,
+    /// How the image is converted to dots
+    #[arg(long, value_enum, default_value_t = DitherArg::Threshold, requires = "image")]
+    dither: DitherArg,
+    /// Print the light parts of the image
+    #[arg(long, requires = "image")]
+    invert_image: bool,
+    /// SVG artwork, rasterised at the printer resolution and scaled to the tape width
+    #[cfg(feature = "svg")]
+    #[arg(long, conflicts_with = "template")]
+    svg: Option<PathBuf>,
+    /// Label description (JSON, or TOML / YAML by extension) with its own media and elements, replaces the other
+    /// content options
+    #[arg(long, conflicts_with_all = ["text", "qr", "code128", "image"])]
+    template: Option<PathBuf>,
+    /// Records for the template's {{field}} placeholders, one label per record: CSV with a header row, or JSON
+    /// lines (.jsonl, .ndjson)
+    #[arg(long, requires = "template")]
+    data: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct JobArgs {
+    /// Media, tape width in mm for continuous tape (e.g. 62) or WIDTHxLENGTH for die-cut labels (e.g. 62x29).
+    /// Defaults to the template's media or 62.
+    #[arg(long, short)]
+    media: Option<String>,
+    #[arg(long, value_enum, default_value_t = Cut::Auto)]
+    cut: Cut,
+    #[arg(long, short = 'n', default_value_t = 1)]
+    copies: u32,
+    /// Print at double vertical resolution
+    #[arg(long)]
+    high_res: bool,
+    /// Defaults to the template's orientation or vertical
+    #[arg(long, value_enum)]
+    orientation: Option<OrientationArg>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Cut {
+    Auto,
+    Half,
+    End,
+    None,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BitmapFont {
+    #[value(name = "6x6")]
+    Font6x6,
+    #[value(name = "6x8")]
+    Font6x8,
+    #[value(name = "6x12")]
+    Font6x12,
+    #[value(name = "8x16")]
+    Font8x16,
+    #[value(name = "12x16")]
+    Font12x16,
+    #[value(name = "24x32")]
+    Font24x32,
+}
+
+impl From<BitmapFont> for FontKind {
+    fn from(font: BitmapFont) -> Self {
+        match font {
+            BitmapFont::Font6x6 => FontKind::Font6x6,
+            BitmapFont::Font6x8 => FontKind::Font6x8,
+            BitmapFont::Font6x12 => FontKind::Font6x12,
+            BitmapFont::Font8x16 => FontKind::Font8x16,
+            BitmapFont::Font12x16 => FontKind::Font12x16,
+            BitmapFont::Font24x32 => FontKind::Font24x32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DitherArg {
+    Threshold,
+    FloydSteinberg,
+    Atkinson,
+    Ordered,
+}
 
-fn main() {
-    println!("Hello, world!");
-    // let mut printer =
-    //     printer::from_addr("labelprinter_1:9100").expect("Unable to connect to printer!");
-    //
-    // let status = printer.get_snmp_status();
-    // println!("Status {:?}", status);
-    // let name = printer.get_snmp_name();
-    // println!("name {:?}", name);
-    // let model = printer.get_snmp_model();
-    // println!("model {:?}", model);
-    //
-    // let status = printer.get_snmp_status();
-    // println!("Status {:?}", status);
+impl From<DitherArg> for Dither {
+    fn from(dither: DitherArg) -> Self {
+        match dither {
+            DitherArg::Threshold => Dither::Threshold,
+            DitherArg::FloydSteinberg => Dither::FloydSteinberg,
+            DitherArg::Atkinson => Dither::Atkinson,
+            DitherArg::Ordered => Dither::Ordered,
+        }
+    }
+}
 
+#[derive(Clone, Copy, ValueEnum)]
+enum OrientationArg {
+    Horizontal,
+    Vertical,
+}
 
+/// Connected printer, over any of the supported interfaces
+enum Device {
+    Tcp(PTouchPrinter<PTouchTcpInterface>),
+    Serial(PTouchPrinter<PTouchSerialInterface>),
+    File(PTouchPrinter<PTouchFileInterface>),
+}
 
-    let mut render = Render::new(RenderConfig::default());
+impl DeviceArgs {
+    fn open(&self) -> anyhow::Result<Device> {
+        if let Some(addr) = &self.printer {
+            Ok(Device::Tcp(printer::from_addr(addr.as_str())?))
+        } else if let Some(path) = &self.serial {
+            let cfg = SerialConfig { baud_rate: self.baud, ..Default::default() };
+            Ok(Device::Serial(printer::from_serial(path, cfg)?))
+        } else if let Some(path) = &self.output {
+            Ok(Device::File(printer::from_file(path)?))
+        } else {
+            bail!("No printer given, use --printer, --serial or --output")
+        }
+    }
+}
 
+impl Device {
+    fn print(&mut self, pages: &[Vec<Vec<u8>>], options: &PrintOptions) -> ql_raster::Result<()> {
+        match self {
+            Device::Tcp(p) => p.print_pages(pages, options),
+            Device::Serial(p) => p.print_pages(pages, options),
+            Device::File(p) => p.print_pages(pages, options),
+        }
+    }
 
-    render.render_text("Hello Rust!", Point::new(1,61)).expect("Failed to render text");
-    render.render_qrcode("https://lib.rs/crates/embedded-graphics-transform",Point::new(1,0)).expect("TODO: panic message");
-    render.show().expect("cannot show");
+    fn write(&mut self, data: &[u8]) -> ql_raster::Result<()> {
+        match self {
+            Device::Tcp(p) => p.write(data).and_then(|_| p.interface.flush()),
+            Device::Serial(p) => p.write(data).and_then(|_| p.interface.flush()),
+            Device::File(p) => p.write(data).and_then(|_| p.interface.flush()),
+        }
+    }
 
-    // sleep 1 sec
-    std::thread::sleep(Duration::from_secs(1));    
-    // printer.print_data(raster_lines).unwrap()
+    fn status(&mut self) -> anyhow::Result<Status> {
+        match self {
+            Device::Tcp(p) => Ok(p.get_snmp_status()?),
+            Device::Serial(p) => {
+                p.status_req()?;
+                Ok(p.read_status()?)
+            }
+            Device::File(_) => bail!("No status available when writing to a file"),
+        }
+    }
 }
 
+impl JobArgs {
+    /// Print options, the template's media is used when `--media` isn't given
+    fn print_options(&self, label: Option<&LabelDescription>) -> anyhow::Result<PrintOptions> {
+        let media = self.media.as_deref().or(label.and_then(|l| l.media.as_deref())).unwrap_or("62");
+        let options = PrintOptions {
+            cut: match self.cut {
+                Cut::Auto => CutMode::Auto,
+                Cut::Half => CutMode::Half,
+                Cut::End => CutMode::End,
+                Cut::None => CutMode::None,
+            },
+            copies: self.copies,
+            high_res: self.high_res,
+            ..Default::default()
+        };
+        options.with_media(media).with_context(|| format!("Unknown media {media}"))
+    }
+
+    fn render_config(&self, options: &PrintOptions, label: Option<&LabelDescription>) -> anyhow::Result<RenderConfig> {
+        let mut cfg = RenderConfig::default();
+        if let Some(label) = label {
+            cfg = label.render_config(cfg)?;
+        }
+        cfg.y = options.printable_dots().unwrap_or(cfg.y);
+        match self.orientation {
+            Some(OrientationArg::Horizontal) => cfg.orientation = Orientation::Horizontal,
+            Some(OrientationArg::Vertical) => cfg.orientation = Orientation::Vertical,
+            None => {}
+        }
+        Ok(cfg)
+    }
+}
+
+impl ContentArgs {
+    /// The label description given with `--template`
+    fn label(&self) -> anyhow::Result<Option<LabelDescription>> {
+        let Some(path) = &self.template else {
+            return Ok(None);
+        };
+        let text = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        let label = LabelDescription::parse(&text, Format::from_path(path)).with_context(|| path.display().to_string())?;
+        Ok(Some(label))
+    }
+
+    /// The records given with `--data`, a single empty one without
+    fn records(&self) -> anyhow::Result<Vec<Record>> {
+        let Some(path) = &self.data else {
+            return Ok(vec![Record::new()]);
+        };
+        let text = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        let records = match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl" | "ndjson") => template::read_json_lines(&text),
+            _ => template::read_csv(&text),
+        }
+        .with_context(|| format!("Invalid records {}", path.display()))?;
+        if records.is_empty() {
+            bail!("No records in {}", path.display());
+        }
+        Ok(records)
+    }
+
+    /// Draw the content, one page per record for templates. Other elements are placed next to each other along the
+    /// tape.
+    fn render(&self, cfg: RenderConfig, label: Option<&LabelDescription>) -> anyhow::Result<Vec<Render>> {
+        if let Some(label) = label {
+            return Ok(label.render_pages(&self.records()?, cfg)?);
+        }
+
+        let image = match &self.image {
+            Some(path) => Some(image::open(path).with_context(|| format!("Unable to open {}", path.display()))?),
+            None => None,
+        };
+        #[cfg(feature = "svg")]
+        let svg = match &self.svg {
+            Some(path) => Some(Svg::from_file(path).with_context(|| format!("Unable to open {}", path.display()))?),
+            None => None,
+        };
+
+        // Everything is scaled to or centred on the tape width, next to each other along the tape
+        let height = cfg.y as u32;
+        let mut row = Stack::horizontal().with_spacing(4);
+        if let Some(image) = &image {
+            let options = ImageOptions { dither: self.dither.into(), invert: self.invert_image, ..Default::default() };
+            row = row.push(Node::image(image, &options));
+        }
+        #[cfg(feature = "svg")]
+        if let Some(svg) = &svg {
+            let options = ImageOptions { dither: Dither::Threshold, h_align: HAlign::Left, ..Default::default() };
+            row = row.push(Node::draw(move |render, area| render.render_svg(svg, area, &options)));
+        }
+        if let Some(qr) = &self.qr {
+            row = row.push(Node::qrcode(qr, &QrOptions { fit: Some(Size::new(height, height)), ..Default::default() }));
+        }
+        if let Some(data) = &self.code128 {
+            row = row.push(Node::code128(data));
+        }
+        if let Some(text) = &self.text {
+            let font = match &self.font {
+                Some(path) => {
+                    let font = Font::from_file(path).with_context(|| format!("Unable to load font {}", path.display()))?;
+                    FontKind::TrueType(TtfTextStyle::new(font, self.font_size))
+                }
+                None => self.bitmap_font.into(),
+            };
+            row = row.push(Node::text(text, &TextOptions { font, ..Default::default() }));
+        }
+
+        if row.is_empty() {
+            bail!("Nothing to print, use --text, --qr, --code128, --image, --svg or --template");
+        }
+
+        let mut render = Render::new(cfg);
+        let size = render.measure_layout(&row, Size::new(cfg.max_x as u32, height))?;
+        render.render_layout(&row, Rectangle::new(Point::zero(), Size::new(size.width, height)))?;
+        Ok(vec![render])
+    }
+}
+
+/// `path` with the page number before the extension when there are several pages, e.g. `label-2.png`
+fn page_path(path: &Path, page: usize, pages: usize) -> PathBuf {
+    if pages == 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{page}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{page}"),
+    };
+    path.with_file_name(name)
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Print { content, job } => {
+            let label = content.label()?;
+            let options = job.print_options(label.as_ref())?;
+            let pages = content.render(job.render_config(&options, label.as_ref())?, label.as_ref())?;
+            let pages = pages.iter().map(Render::raster).collect::<ql_raster::Result<Vec<_>>>()?;
+            cli.device.open()?.print(&pages, &options)?;
+        }
+        Command::Preview { content, job, file } => {
+            let label = content.label()?;
+            let options = job.print_options(label.as_ref())?;
+            let pages = content.render(job.render_config(&options, label.as_ref())?, label.as_ref())?;
+            for (i, render) in pages.iter().enumerate() {
+                match &file {
+                    Some(path) if path.extension().is_some_and(|e| e == "pbm") => {
+                        render.save_pbm(page_path(path, i + 1, pages.len()))?
+                    }
+                    Some(path) => render.save_png(page_path(path, i + 1, pages.len()))?,
+                    None => render.show()?,
+                }
+            }
+        }
+        Command::Status => {
+            let status = cli.device.open()?.status()?;
+            println!("{status:#?}");
+        }
+        Command::Info => {
+            let addr = cli.device.printer.as_deref().ok_or_else(|| anyhow!("Info needs a network printer (--printer)"))?;
+            // SNMP only, without taking the printer's single print connection
+            let ip_addr = addr
+                .to_socket_addrs()?
+                .next()
+                .map(|sa| sa.ip())
+                .ok_or_else(|| anyhow!("Unable to resolve {addr}"))?;
+            println!("Name:   {}", printer::snmp_name(ip_addr)?);
+            println!("Model:  {}", printer::snmp_model(ip_addr)?);
+            println!("Serial: {}", printer::snmp_serial(ip_addr)?);
+        }
+        Command::Discover { timeout } => {
+            let found = printer::discover(Duration::from_secs(timeout))?;
+            if found.is_empty() {
+                println!("No printers found");
+            }
+            for p in found {
+                println!("{}\t{}", p.ip_addr, p.model);
+            }
+        }
+        Command::Raw { file } => {
+            let data = fs::read(&file).with_context(|| format!("Unable to read {}", file.display()))?;
+            cli.device.open()?.write(&data)?;
+        }
+    }
+
+    Ok(())
+}
 
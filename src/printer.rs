@@ -1,7 +1,9 @@
 use crate::{prelude::*, PTouchError};
 use std::{
-    net::{IpAddr, SocketAddr, ToSocketAddrs},
-    time::Duration,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    path::Path,
+    time::{Duration, Instant},
 };
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1500);
@@ -31,6 +33,56 @@ pub struct PTouchPrinter<D> {
     send_buffer: Option<Vec<u8>>, // Probably use a type (of PTouchPrinter) to diff between buffered and direct io
 }
 
+/// Printer that answered a discovery broadcast
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DiscoveredPrinter {
+    pub ip_addr: IpAddr,
+    pub model: String,
+}
+
+/// Find Brother printers on the local network by broadcasting an SNMP request for the model
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredPrinter>> {
+    use snmp::{pdu, SnmpPdu, Value};
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+
+    let mut request = pdu::Buf::default();
+    pdu::build_get(b"public", 1, snmp_oid::MODEL, &mut request);
+    socket.send_to(&request, (Ipv4Addr::BROADCAST, 161))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found: Vec<DiscoveredPrinter> = Vec::new();
+    let mut buf = [0u8; 1500];
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let Ok(mut response) = SnmpPdu::from_bytes(&buf[..len]) else {
+            continue;
+        };
+        if let Some((_oid, Value::OctetString(model))) = response.varbinds.next() {
+            let model = String::from_utf8_lossy(model).to_string();
+            // Other SNMP devices answer the broadcast as well
+            if model.contains("Brother") && !found.iter().any(|p| p.ip_addr == from.ip()) {
+                found.push(DiscoveredPrinter { ip_addr: from.ip(), model });
+            }
+        }
+    }
+
+    Ok(found)
+}
+
 impl PTouchPrinter<PTouchTcpInterface> {
     pub fn new<A: ToSocketAddrs>(addr: A) -> Result<Self> {
         from_addr(addr)
@@ -53,26 +105,54 @@ impl PTouchPrinter<PTouchTcpInterface> {
     }
 
     fn get_snmp_string(&self, oid: &[u32]) -> Result<String> {
-        let r = self.get_snmp(oid)?;
-        Ok(String::from_utf8_lossy(r.as_slice()).into())
+        snmp_string(self.get_ip_addr()?, oid)
     }
 
     fn get_snmp(&self, oid: &[u32]) -> Result<Vec<u8>> {
-        use snmp::{SyncSession, Value};
+        snmp_get(self.get_ip_addr()?, oid)
+    }
 
-        if let Some(ip_addr) = self.ip_addr {
-            let addr = SocketAddr::new(ip_addr, 161);
-            let timeout = Duration::from_millis(500);
-            let mut snmp_session = SyncSession::new(addr, b"public", Some(timeout), 0)?;
+    fn get_ip_addr(&self) -> Result<IpAddr> {
+        self.ip_addr.ok_or(PTouchError::SNMPError)
+    }
+}
 
-            let mut response = snmp_session.get(oid).map_err(|_| PTouchError::SNMPError)?;
-            if let Some((_oid, Value::OctetString(response_data))) = response.varbinds.next() {
-                return Ok(response_data.into());
-            }
-        }
+/// Query the status over SNMP without opening a print connection
+/// (the printer only accepts one connection on port 9100 at a time)
+pub fn snmp_status(ip_addr: IpAddr) -> Result<Status> {
+    Status::try_from(snmp_get(ip_addr, snmp_oid::STATUS)?.as_slice())
+}
 
-        Err(PTouchError::SNMPError)
+/// Query the name, model and serial over SNMP, see [`snmp_status`]
+pub fn snmp_name(ip_addr: IpAddr) -> Result<String> {
+    snmp_string(ip_addr, snmp_oid::NAME)
+}
+
+pub fn snmp_model(ip_addr: IpAddr) -> Result<String> {
+    snmp_string(ip_addr, snmp_oid::MODEL)
+}
+
+pub fn snmp_serial(ip_addr: IpAddr) -> Result<String> {
+    snmp_string(ip_addr, snmp_oid::SERIAL)
+}
+
+fn snmp_string(ip_addr: IpAddr, oid: &[u32]) -> Result<String> {
+    Ok(String::from_utf8_lossy(&snmp_get(ip_addr, oid)?).into())
+}
+
+fn snmp_get(ip_addr: IpAddr, oid: &[u32]) -> Result<Vec<u8>> {
+    use snmp::{SyncSession, Value};
+
+    let addr = SocketAddr::new(ip_addr, 161);
+    let timeout = Duration::from_millis(500);
+    let mut snmp_session = SyncSession::new(addr, b"public", Some(timeout), 0)?;
+
+    let mut response = snmp_session.get(oid).map_err(|_| PTouchError::SNMPError)?;
+    if let Some((_oid, Value::OctetString(response_data))) = response.varbinds.next() {
+        return Ok(response_data.into());
     }
+
+    Err(PTouchError::SNMPError)
 }
 
 pub fn from_addr<A: ToSocketAddrs>(addr: A) -> Result<PTouchPrinter<PTouchTcpInterface>> {
@@ -90,7 +170,27 @@ pub fn from_addr<A: ToSocketAddrs>(addr: A) -> Result<PTouchPrinter<PTouchTcpInt
     })
 }
 
+/// Write the command stream to a file instead of a printer
+pub fn from_file<P: AsRef<Path>>(path: P) -> Result<PTouchPrinter<PTouchFileInterface>> {
+    Ok(PTouchPrinter::with_interface(PTouchFileInterface::new(path)?))
+}
+
+/// Open a printer on a serial port, e.g. `/dev/ttyUSB0` or a bound Bluetooth `/dev/rfcomm0`
+#[cfg(feature = "serial")]
+pub fn from_serial(path: &str, cfg: SerialConfig) -> Result<PTouchPrinter<PTouchSerialInterface>> {
+    Ok(PTouchPrinter::with_interface(PTouchSerialInterface::new(path, cfg)?))
+}
+
 impl<D: PTouchInterface> PTouchPrinter<D> {
+    /// Create a printer on top of an already opened interface
+    pub fn with_interface(interface: D) -> Self {
+        PTouchPrinter {
+            interface,
+            ip_addr: None,
+            send_buffer: None,
+        }
+    }
+
     // pub fn get_status(&mut self) -> Result<Status> {
     //     Ok(Status)
     // }
@@ -0,0 +1,830 @@
+//! Aztec code encoder, compact and full range symbols
+use embedded_graphics::prelude::Size;
+
+use crate::render::barcode::BarcodeError;
+use crate::render::matrix::{MatrixSymbol, StyledMatrix};
+
+/// Character modes of the high level encodation
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Upper,
+    Lower,
+    Mixed,
+    Punct,
+    Digit,
+}
+
+const MODES: [Mode; 5] = [Mode::Upper, Mode::Lower, Mode::Mixed, Mode::Punct, Mode::Digit];
+
+/// Binary shift, the same code in upper, lower and mixed mode
+const BINARY_SHIFT: u16 = 31;
+/// Longest binary shift run
+const MAX_BINARY: usize = 31 + 2047;
+
+impl Mode {
+    /// Bits per character
+    fn bits(self) -> usize {
+        match self {
+            Mode::Digit => 4,
+            _ => 5,
+        }
+    }
+
+    /// Code of a single character
+    fn code(self, c: u8) -> Option<u16> {
+        let code = match (self, c) {
+            (Mode::Punct, _) => return punct_code(c),
+            (_, b' ') => 1,
+            (Mode::Upper, b'A'..=b'Z') => c - b'A' + 2,
+            (Mode::Lower, b'a'..=b'z') => c - b'a' + 2,
+            (Mode::Mixed, 1..=13) => c + 1,
+            (Mode::Mixed, 27..=31) => c - 27 + 15,
+            (Mode::Mixed, b'@') => 20,
+            (Mode::Mixed, b'\\') => 21,
+            (Mode::Mixed, b'^') => 22,
+            (Mode::Mixed, b'_') => 23,
+            (Mode::Mixed, b'`') => 24,
+            (Mode::Mixed, b'|') => 25,
+            (Mode::Mixed, b'~') => 26,
+            (Mode::Mixed, 127) => 27,
+            (Mode::Digit, b'0'..=b'9') => c - b'0' + 2,
+            (Mode::Digit, b',') => 12,
+            (Mode::Digit, b'.') => 13,
+            _ => return None,
+        };
+        Some(code as u16)
+    }
+
+    /// Codes switching permanently to another mode
+    fn latch(self, to: Mode) -> &'static [(u16, usize)] {
+        use Mode::*;
+        match (self, to) {
+            (Upper, Lower) | (Mixed, Lower) => &[(28, 5)],
+            (Upper, Mixed) | (Lower, Mixed) => &[(29, 5)],
+            (Upper, Digit) | (Lower, Digit) => &[(30, 5)],
+            (Upper, Punct) | (Lower, Punct) => &[(29, 5), (30, 5)],
+            (Lower, Upper) => &[(29, 5), (29, 5)],
+            (Mixed, Upper) => &[(29, 5)],
+            (Mixed, Punct) => &[(30, 5)],
+            (Mixed, Digit) => &[(29, 5), (30, 5)],
+            (Punct, Upper) => &[(31, 5)],
+            (Punct, Lower) => &[(31, 5), (28, 5)],
+            (Punct, Mixed) => &[(31, 5), (29, 5)],
+            (Punct, Digit) => &[(31, 5), (30, 5)],
+            (Digit, Upper) => &[(14, 4)],
+            (Digit, Lower) => &[(14, 4), (28, 5)],
+            (Digit, Mixed) => &[(14, 4), (29, 5)],
+            (Digit, Punct) => &[(14, 4), (29, 5), (30, 5)],
+            _ => &[],
+        }
+    }
+
+    fn latch_bits(self, to: Mode) -> usize {
+        self.latch(to).iter().map(|(_, bits)| bits).sum()
+    }
+}
+
+fn punct_code(c: u8) -> Option<u16> {
+    const PUNCT: &[u8] = b"!\"#$%&'()*+,-./:;<=>?[]{}";
+    match c {
+        b'\r' => Some(1),
+        _ => PUNCT.iter().position(|&p| p == c).map(|i| i as u16 + 6),
+    }
+}
+
+/// Two character punctuation codes: CR LF, ". ", ", " and ": "
+fn punct_pair(a: u8, b: Option<&u8>) -> Option<u16> {
+    match (a, b?) {
+        (b'\r', b'\n') => Some(2),
+        (b'.', b' ') => Some(3),
+        (b',', b' ') => Some(4),
+        (b':', b' ') => Some(5),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct BitBuffer {
+    bits: Vec<bool>,
+}
+
+impl BitBuffer {
+    fn push(&mut self, value: u16, bits: usize) {
+        self.bits.extend((0..bits).rev().map(|i| value >> i & 1 == 1));
+    }
+}
+
+/// High level encodation into a bit stream, greedy with shifts for single characters
+fn encode_bits(data: &[u8]) -> Vec<bool> {
+    let mut out = BitBuffer::default();
+    let mut mode = Mode::Upper;
+    let mut i = 0;
+
+    while i < data.len() {
+        let c = data[i];
+        let next = data.get(i + 1);
+        let pair = punct_pair(c, next);
+
+        if let (Mode::Punct, Some(pair)) = (mode, pair) {
+            out.push(pair, 5);
+            i += 2;
+            continue;
+        }
+        if let Some(code) = mode.code(c) {
+            out.push(code, mode.bits());
+            i += 1;
+            continue;
+        }
+
+        // A single character from another mode is shifted, unless the next one needs that mode too
+        let single = next.is_none_or(|&n| mode.code(n).is_some() || pair.is_some());
+        if let Some(code) = Mode::Punct.code(c).filter(|_| single || pair.is_some()) {
+            // P/S is code 0 in every mode but punctuation
+            out.push(0, mode.bits());
+            match pair {
+                Some(pair) => {
+                    out.push(pair, 5);
+                    i += 2;
+                }
+                None => {
+                    out.push(code, 5);
+                    i += 1;
+                }
+            }
+            continue;
+        }
+        if let Some(code) = Mode::Upper.code(c).filter(|_| single && matches!(mode, Mode::Lower | Mode::Digit)) {
+            // U/S
+            out.push(if mode == Mode::Digit { 15 } else { 28 }, mode.bits());
+            out.push(code, 5);
+            i += 1;
+            continue;
+        }
+
+        let target = MODES.into_iter().filter(|m| m.code(c).is_some()).min_by_key(|&m| mode.latch_bits(m));
+        match target {
+            Some(target) => {
+                for &(code, bits) in mode.latch(target) {
+                    out.push(code, bits);
+                }
+                mode = target;
+            }
+            None => {
+                // Binary shift is only available from upper, lower and mixed mode
+                if matches!(mode, Mode::Punct | Mode::Digit) {
+                    for &(code, bits) in mode.latch(Mode::Upper) {
+                        out.push(code, bits);
+                    }
+                    mode = Mode::Upper;
+                }
+                let len = data[i..]
+                    .iter()
+                    .take(MAX_BINARY)
+                    .take_while(|&&b| MODES.iter().all(|m| m.code(b).is_none()))
+                    .count();
+                out.push(BINARY_SHIFT, 5);
+                match len {
+                    0..=31 => out.push(len as u16, 5),
+                    _ => {
+                        out.push(0, 5);
+                        out.push((len - 31) as u16, 11);
+                    }
+                }
+                for &b in &data[i..i + len] {
+                    out.push(b as u16, 8);
+                }
+                i += len;
+            }
+        }
+    }
+    out.bits
+}
+
+/// Galois field GF(2^m) used for the Reed-Solomon codes
+struct Gf {
+    exp: Vec<u16>,
+    log: Vec<u16>,
+}
+
+impl Gf {
+    /// Field for codewords of `word_size` bits
+    fn new(word_size: usize) -> Self {
+        let polynomial: u32 = match word_size {
+            4 => 0x13,
+            6 => 0x43,
+            8 => 0x12d,
+            10 => 0x409,
+            _ => 0x1069,
+        };
+        let size = 1usize << word_size;
+        let mut exp = vec![0u16; size - 1];
+        let mut log = vec![0u16; size];
+        let mut x: u32 = 1;
+        for (i, e) in exp.iter_mut().enumerate() {
+            *e = x as u16;
+            log[x as usize] = i as u16;
+            x <<= 1;
+            if x >= size as u32 {
+                x ^= polynomial;
+            }
+        }
+        Self { exp, log }
+    }
+
+    fn mul(&self, a: u16, b: u16) -> u16 {
+        match a == 0 || b == 0 {
+            true => 0,
+            false => self.exp[(self.log[a as usize] as usize + self.log[b as usize] as usize) % self.exp.len()],
+        }
+    }
+
+    /// Check words with the generator polynomial (x - a^1)...(x - a^n)
+    fn reed_solomon(&self, data: &[u16], ecc_len: usize) -> Vec<u16> {
+        let mut generator = vec![1u16];
+        for i in 1..=ecc_len {
+            let root = self.exp[i % self.exp.len()];
+            let mut next = generator.clone();
+            next.push(0);
+            for (j, &g) in generator.iter().enumerate() {
+                next[j + 1] ^= self.mul(g, root);
+            }
+            generator = next;
+        }
+
+        let mut ecc = vec![0u16; ecc_len];
+        for &d in data {
+            let factor = d ^ ecc.first().copied().unwrap_or(0);
+            ecc.rotate_left(1);
+            if let Some(last) = ecc.last_mut() {
+                *last = 0;
+            }
+            for (e, &g) in ecc.iter_mut().zip(&generator[1..]) {
+                *e ^= self.mul(g, factor);
+            }
+        }
+        ecc
+    }
+}
+
+/// Codeword size in bits for a number of layers
+fn word_size(layers: usize) -> usize {
+    match layers {
+        1..=2 => 6,
+        3..=8 => 8,
+        9..=22 => 10,
+        _ => 12,
+    }
+}
+
+/// Bits available in the data layers
+fn total_bits(layers: usize, compact: bool) -> usize {
+    (if compact { 88 } else { 112 } + 16 * layers) * layers
+}
+
+/// Split into codewords, a word of all zeros or all ones gets a complemented bit inserted
+fn stuff_bits(bits: &[bool], word_size: usize) -> Vec<u16> {
+    let mask = (1u16 << word_size) - 2;
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < bits.len() {
+        let word = (0..word_size).fold(0u16, |word, j| word << 1 | bits.get(i + j).is_none_or(|&b| b) as u16);
+        match word & mask {
+            m if m == mask => {
+                words.push(m);
+                i += word_size - 1;
+            }
+            0 => {
+                words.push(word | 1);
+                i += word_size - 1;
+            }
+            _ => {
+                words.push(word);
+                i += word_size;
+            }
+        }
+    }
+    words
+}
+
+fn words_to_bits(words: &[u16], word_size: usize) -> Vec<bool> {
+    let mut out = BitBuffer::default();
+    for &word in words {
+        out.push(word, word_size);
+    }
+    out.bits
+}
+
+/// Number of layers and data codewords protected by GF(16) check words
+fn mode_message(compact: bool, layers: usize, data_words: usize) -> Vec<bool> {
+    let mut out = BitBuffer::default();
+    let message_len = match compact {
+        true => {
+            out.push((layers - 1) as u16, 2);
+            out.push((data_words - 1) as u16, 6);
+            28
+        }
+        false => {
+            out.push((layers - 1) as u16, 5);
+            out.push((data_words - 1) as u16, 11);
+            40
+        }
+    };
+    let words: Vec<u16> = out.bits.chunks(4).map(|c| c.iter().fold(0, |w, &b| w << 1 | b as u16)).collect();
+    let ecc = Gf::new(4).reed_solomon(&words, message_len / 4 - words.len());
+    words_to_bits(&[words, ecc].concat(), 4)
+}
+
+/// Symbol size and number of layers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AztecLayers {
+    /// Compact symbol with 1-4 layers (15x15 to 27x27 modules)
+    Compact(u8),
+    /// Full range symbol with 1-32 layers (19x19 to 151x151 modules)
+    Full(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AztecOptions {
+    /// Minimum share of the symbol used for error correction in percent, 23 is recommended
+    pub ecc_percent: u32,
+    /// Fixed symbol size, the smallest fitting symbol if `None`
+    pub layers: Option<AztecLayers>,
+}
+
+impl Default for AztecOptions {
+    fn default() -> Self {
+        Self { ecc_percent: 23, layers: None }
+    }
+}
+
+/// An encoded Aztec symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aztec {
+    compact: bool,
+    layers: usize,
+    data_words: usize,
+    size: usize,
+    /// Row major, true is a dark module
+    modules: Vec<bool>,
+}
+
+impl Aztec {
+    /// Encode text, characters beyond ISO 8859-1 can't be encoded
+    pub fn encode(data: &str, options: &AztecOptions) -> Result<Self, BarcodeError> {
+        let bytes = data
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| BarcodeError::InvalidCharacter(c)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_bytes(&bytes, options)
+    }
+
+    /// Encode binary data
+    pub fn from_bytes(data: &[u8], options: &AztecOptions) -> Result<Self, BarcodeError> {
+        if data.is_empty() {
+            return Err(BarcodeError::Empty);
+        }
+        let bits = encode_bits(data);
+        let ecc_bits = bits.len() * options.ecc_percent as usize / 100 + 11;
+
+        let fits = |compact: bool, layers: usize| {
+            let word_size = word_size(layers);
+            let words = stuff_bits(&bits, word_size);
+            let total = total_bits(layers, compact);
+            let usable = total - total % word_size;
+            let ok = words.len() * word_size + ecc_bits <= usable && !(compact && words.len() > 64);
+            ok.then_some(words)
+        };
+
+        let candidates: Vec<(bool, usize)> = match options.layers {
+            Some(AztecLayers::Compact(layers)) => vec![(true, layers.clamp(1, 4) as usize)],
+            Some(AztecLayers::Full(layers)) => vec![(false, layers.clamp(1, 32) as usize)],
+            // Full range symbols with up to 3 layers are bigger than compact ones with the same capacity
+            None => (1..=4).map(|l| (true, l)).chain((4..=32).map(|l| (false, l))).collect(),
+        };
+
+        for &(compact, layers) in &candidates {
+            if let Some(words) = fits(compact, layers) {
+                return Ok(Self::layout(compact, layers, words));
+            }
+        }
+
+        let &(compact, layers) = candidates.last().unwrap_or(&(false, 32));
+        let word_size = word_size(layers);
+        Err(BarcodeError::TooLong {
+            capacity: total_bits(layers, compact) / word_size,
+            found: (stuff_bits(&bits, word_size).len() * word_size + ecc_bits).div_ceil(word_size),
+        })
+    }
+
+    fn layout(compact: bool, layers: usize, mut words: Vec<u16>) -> Self {
+        let word_size = word_size(layers);
+        let total = total_bits(layers, compact);
+        let data_words = words.len();
+        let ecc = Gf::new(word_size).reed_solomon(&words, total / word_size - data_words);
+        words.extend(ecc);
+
+        // The layers start with padding when they don't hold a whole number of codewords
+        let mut message = vec![false; total % word_size];
+        message.extend(words_to_bits(&words, word_size));
+
+        let mut symbol = Self::empty(compact, layers, data_words);
+        symbol.draw_layers(&message);
+        symbol.draw_mode_message();
+        symbol.draw_finder();
+        symbol
+    }
+
+    fn empty(compact: bool, layers: usize, data_words: usize) -> Self {
+        let size = match compact {
+            true => 11 + layers * 4,
+            false => {
+                let base = 14 + layers * 4;
+                base + 1 + 2 * ((base / 2 - 1) / 15)
+            }
+        };
+        Self { compact, layers, data_words, size, modules: vec![false; size * size] }
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        self.modules[y * self.size + x] = true;
+    }
+
+    /// Data layer size without reference grid lines
+    fn base_size(&self) -> usize {
+        (if self.compact { 11 } else { 14 }) + self.layers * 4
+    }
+
+    /// Maps positions in the data layers onto the symbol, skipping the reference grid
+    fn alignment_map(&self) -> Vec<usize> {
+        let base = self.base_size();
+        if self.compact {
+            return (0..base).collect();
+        }
+        let (original, center) = (base / 2, self.size / 2);
+        let mut map = vec![0; base];
+        for i in 0..original {
+            let offset = i + i / 15;
+            map[original - i - 1] = center - offset - 1;
+            map[original + i] = center + offset + 1;
+        }
+        map
+    }
+
+    /// Lay out the message in 2 module wide layers, spiralling in from the outside
+    fn draw_layers(&mut self, message: &[bool]) {
+        let map = self.alignment_map();
+        let base = self.base_size();
+        let mut offset = 0;
+
+        for i in 0..self.layers {
+            let row_size = (self.layers - i) * 4 + if self.compact { 9 } else { 12 };
+            let (low, high) = (i * 2, base - 1 - i * 2);
+            for j in 0..row_size {
+                for k in 0..2 {
+                    let bit = |side: usize| message[offset + side * row_size * 2 + j * 2 + k];
+                    if bit(0) {
+                        self.set(map[low + k], map[low + j]);
+                    }
+                    if bit(1) {
+                        self.set(map[low + j], map[high - k]);
+                    }
+                    if bit(2) {
+                        self.set(map[high - k], map[high - j]);
+                    }
+                    if bit(3) {
+                        self.set(map[high - j], map[low + k]);
+                    }
+                }
+            }
+            offset += row_size * 8;
+        }
+    }
+
+    /// Mode message around the finder
+    fn draw_mode_message(&mut self) {
+        let bits = mode_message(self.compact, self.layers, self.data_words);
+        let center = self.size / 2;
+        let (side, radius) = match self.compact {
+            true => (7, 5),
+            false => (10, 7),
+        };
+        for i in 0..side {
+            let offset = match self.compact {
+                true => center - 3 + i,
+                false => center - 5 + i + i / 5,
+            };
+            let [a, b, c, d] = [bits[i], bits[i + side], bits[3 * side - 1 - i], bits[4 * side - 1 - i]];
+            if a {
+                self.set(offset, center - radius);
+            }
+            if b {
+                self.set(center + radius, offset);
+            }
+            if c {
+                self.set(offset, center + radius);
+            }
+            if d {
+                self.set(center - radius, offset);
+            }
+        }
+    }
+
+    /// Bull's eye, orientation marks and for full range symbols the reference grid
+    fn draw_finder(&mut self) {
+        let center = self.size / 2;
+        let rings = if self.compact { 5 } else { 7 };
+        for i in (0..rings).step_by(2) {
+            for j in center - i..=center + i {
+                self.set(j, center - i);
+                self.set(j, center + i);
+                self.set(center - i, j);
+                self.set(center + i, j);
+            }
+        }
+
+        let (low, high) = (center - rings, center + rings);
+        for (x, y) in [(low, low), (low + 1, low), (low, low + 1), (high, low), (high, low + 1), (high, high - 1)] {
+            self.set(x, y);
+        }
+
+        if !self.compact {
+            let base = self.base_size();
+            for j in (0..base / 2 - 1).step_by(15).map(|i| i / 15 * 16) {
+                for k in (center & 1..self.size).step_by(2) {
+                    self.set(center - j, k);
+                    self.set(center + j, k);
+                    self.set(k, center - j);
+                    self.set(k, center + j);
+                }
+            }
+        }
+    }
+
+    /// Size in modules
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    /// Number of data codewords, the rest of the layers holds error correction
+    pub fn data_words(&self) -> usize {
+        self.data_words
+    }
+
+    /// Module at column `x` and row `y`, light outside the symbol
+    pub fn get_module(&self, x: i32, y: i32) -> bool {
+        let size = self.size as i32;
+        (0..size).contains(&x) && (0..size).contains(&y) && self.modules[(y * size + x) as usize]
+    }
+}
+
+impl MatrixSymbol for Aztec {
+    fn modules(&self) -> Size {
+        Size::new(self.size as u32, self.size as u32)
+    }
+
+    fn get_module(&self, x: i32, y: i32) -> bool {
+        self.get_module(x, y)
+    }
+}
+
+/// An Aztec symbol drawn with square modules
+pub type StyledAztec = StyledMatrix<Aztec>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UPPER: [&str; 32] = [
+        "PS", " ", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T",
+        "U", "V", "W", "X", "Y", "Z", "LL", "ML", "DL", "BS",
+    ];
+    const LOWER: [&str; 32] = [
+        "PS", " ", "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t",
+        "u", "v", "w", "x", "y", "z", "US", "ML", "DL", "BS",
+    ];
+    const MIXED: [&str; 32] = [
+        "PS", " ", "\x01", "\x02", "\x03", "\x04", "\x05", "\x06", "\x07", "\x08", "\t", "\n", "\x0b", "\x0c", "\r",
+        "\x1b", "\x1c", "\x1d", "\x1e", "\x1f", "@", "\\", "^", "_", "`", "|", "~", "\x7f", "LL", "UL", "PL", "BS",
+    ];
+    const PUNCT: [&str; 32] = [
+        "FLG", "\r", "\r\n", ". ", ", ", ": ", "!", "\"", "#", "$", "%", "&", "'", "(", ")", "*", "+", ",", "-", ".",
+        "/", ":", ";", "<", "=", ">", "?", "[", "]", "{", "}", "UL",
+    ];
+    const DIGIT: [&str; 16] = ["PS", " ", "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", ",", ".", "UL", "US"];
+
+    struct Reader<'a> {
+        bits: &'a [bool],
+        index: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn read(&mut self, n: usize) -> Option<u16> {
+            let bits = self.bits.get(self.index..self.index + n)?;
+            self.index += n;
+            Some(bits.iter().fold(0, |w, &b| w << 1 | b as u16))
+        }
+    }
+
+    /// Reads a symbol back from its modules like a scanner would after locating the finder
+    fn decode(symbol: &Aztec) -> Vec<u8> {
+        let get = |x: usize, y: usize| symbol.get_module(x as i32, y as i32);
+        let size = symbol.size();
+        let center = size / 2;
+
+        // Compact symbols have an orientation mark where full range ones have a light ring
+        let compact = get(center - 5, center - 5);
+        let (side, radius) = if compact { (7, 5) } else { (10, 7) };
+        let mut mode = vec![false; 4 * side];
+        for i in 0..side {
+            let offset = if compact { center - 3 + i } else { center - 5 + i + i / 5 };
+            mode[i] = get(offset, center - radius);
+            mode[i + side] = get(center + radius, offset);
+            mode[3 * side - 1 - i] = get(offset, center + radius);
+            mode[4 * side - 1 - i] = get(center - radius, offset);
+        }
+        let words: Vec<u16> = mode.chunks(4).map(|c| c.iter().fold(0, |w, &b| w << 1 | b as u16)).collect();
+        let data_len = if compact { 2 } else { 4 };
+        assert_eq!(Gf::new(4).reed_solomon(&words[..data_len], words.len() - data_len), words[data_len..]);
+        let mut reader = Reader { bits: &mode, index: 0 };
+        let layers = reader.read(if compact { 2 } else { 5 }).unwrap() as usize + 1;
+        let data_words = reader.read(if compact { 6 } else { 11 }).unwrap() as usize + 1;
+        assert_eq!((compact, layers, data_words), (symbol.is_compact(), symbol.layers(), symbol.data_words()));
+
+        // Layers, outside in
+        let map = symbol.alignment_map();
+        let base = map.len();
+        let mut raw = Vec::new();
+        for i in 0..layers {
+            let row_size = (layers - i) * 4 + if compact { 9 } else { 12 };
+            let (low, high) = (i * 2, base - 1 - i * 2);
+            let mut sides = vec![Vec::new(); 4];
+            for j in 0..row_size {
+                for k in 0..2 {
+                    sides[0].push(get(map[low + k], map[low + j]));
+                    sides[1].push(get(map[low + j], map[high - k]));
+                    sides[2].push(get(map[high - k], map[high - j]));
+                    sides[3].push(get(map[high - j], map[low + k]));
+                }
+            }
+            raw.extend(sides.concat());
+        }
+
+        let word_size = word_size(layers);
+        let words: Vec<u16> = raw[raw.len() % word_size..]
+            .chunks(word_size)
+            .map(|c| c.iter().fold(0, |w, &b| w << 1 | b as u16))
+            .collect();
+        let gf = Gf::new(word_size);
+        assert_eq!(gf.reed_solomon(&words[..data_words], words.len() - data_words), words[data_words..]);
+
+        // Remove stuffed bits
+        let all_ones = (1 << word_size) - 1;
+        let mut bits = Vec::new();
+        for &word in &words[..data_words] {
+            assert!(word != 0 && word != all_ones);
+            let len = if word == 1 || word == all_ones - 1 { word_size - 1 } else { word_size };
+            bits.extend((0..word_size).rev().take(len).map(|i| word >> i & 1 == 1));
+        }
+
+        let mut reader = Reader { bits: &bits, index: 0 };
+        let mut out = Vec::new();
+        let (mut latched, mut shifted): (&[&str], Option<&[&str]>) = (&UPPER, None);
+        loop {
+            let table = shifted.take().unwrap_or(latched);
+            let n = if table.len() == 16 { 4 } else { 5 };
+            let Some(code) = reader.read(n) else { break };
+            match table[code as usize] {
+                "BS" => {
+                    let Some(mut len) = reader.read(5) else { break };
+                    if len == 0 {
+                        let Some(long) = reader.read(11) else { break };
+                        len = long + 31;
+                    }
+                    for _ in 0..len {
+                        let Some(b) = reader.read(8) else { break };
+                        out.push(b as u8);
+                    }
+                }
+                "PS" => shifted = Some(&PUNCT),
+                "US" => shifted = Some(&UPPER),
+                "UL" => latched = &UPPER,
+                "LL" => latched = &LOWER,
+                "ML" => latched = &MIXED,
+                "PL" => latched = &PUNCT,
+                "DL" => latched = &DIGIT,
+                "FLG" => panic!("FLG(n) is never encoded"),
+                text => out.extend(text.bytes()),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn high_level_encodation() {
+        let bits = encode_bits(b"Ab1");
+        // A, L/L b, D/L 1
+        let expected = [(2, 5), (28, 5), (3, 5), (30, 5), (3, 4)];
+        let mut buffer = BitBuffer::default();
+        expected.iter().for_each(|&(code, n)| buffer.push(code, n));
+        assert_eq!(bits, buffer.bits);
+    }
+
+    /// Modules written as `X` and `.`, spaces are ignored
+    fn pattern(text: &str) -> Vec<bool> {
+        text.chars().filter(|&c| c != ' ').map(|c| c == 'X').collect()
+    }
+
+    #[test]
+    fn reference_mode_messages() {
+        // Expected output of the ZXing Aztec encoder (EncoderTest.testModeMessage)
+        assert_eq!(mode_message(true, 2, 29), pattern(".X .XXX.. ...X XX.. ..X .XX. .XX.X"));
+        assert_eq!(mode_message(true, 4, 64), pattern("XX XXXXXX .X.. ...X ..XX .X.. XX.."));
+        assert_eq!(mode_message(false, 21, 660), pattern("X.X.. .X.X..X..XX .XXX ..X.. .XXX. .X... ..XXX"));
+        assert_eq!(mode_message(false, 32, 4096), pattern("XXXXX XXXXXXXXXXX X.X. ..... XXX.X ..X.. X.XXX"));
+    }
+
+    #[test]
+    fn reference_check_words() {
+        // Check words computed by the ZXing Reed-Solomon encoder (ReedSolomonTestCase.testAztec)
+        let gf = Gf::new(4);
+        assert_eq!(gf.reed_solomon(&[0x5, 0x6], 5), [0x3, 0x2, 0xB, 0xB, 0x7]);
+        assert_eq!(gf.reed_solomon(&[0x0, 0x0, 0x0, 0x9], 6), [0xA, 0xD, 0x8, 0x6, 0x5, 0x6]);
+        assert_eq!(gf.reed_solomon(&[0x2, 0x8, 0x8, 0x7], 6), [0xE, 0xC, 0xA, 0x9, 0x6, 0x8]);
+        let data = [0x9, 0x32, 0x1, 0x29, 0x2F, 0x2, 0x27, 0x25, 0x1, 0x1B];
+        let ecc = [0x2C, 0x2, 0xD, 0xD, 0xA, 0x16, 0x28, 0x9, 0x22, 0xA, 0x14];
+        assert_eq!(Gf::new(6).reed_solomon(&data, ecc.len()), ecc);
+    }
+
+    #[test]
+    fn bit_stuffing() {
+        let bits: Vec<bool> = "000000111111".chars().map(|c| c == '1').collect();
+        // The last word is padded with ones
+        assert_eq!(stuff_bits(&bits, 6), [0b000001, 0b011111, 0b111110]);
+        assert_eq!(stuff_bits(&bits[..6], 6), [0b000001, 0b011111]);
+    }
+
+    #[test]
+    fn symbol_sizes() {
+        let small = Aztec::encode("A", &AztecOptions::default()).unwrap();
+        assert!(small.is_compact());
+        assert_eq!((small.layers(), small.size()), (1, 15));
+
+        let options = AztecOptions { layers: Some(AztecLayers::Full(1)), ..Default::default() };
+        assert_eq!(Aztec::encode("A", &options).unwrap().size(), 19);
+        let options = AztecOptions { layers: Some(AztecLayers::Full(16)), ..Default::default() };
+        assert_eq!(Aztec::encode("A", &options).unwrap().size(), 14 + 64 + 1 + 2 * 2);
+
+        let options = AztecOptions { layers: Some(AztecLayers::Compact(1)), ..Default::default() };
+        assert!(matches!(Aztec::encode(&"A".repeat(40), &options), Err(BarcodeError::TooLong { .. })));
+        assert_eq!(Aztec::encode("", &options), Err(BarcodeError::Empty));
+    }
+
+    #[test]
+    fn error_correction_level() {
+        let data = "Code 2D!".repeat(8);
+        let low = Aztec::encode(&data, &AztecOptions { ecc_percent: 10, layers: None }).unwrap();
+        let high = Aztec::encode(&data, &AztecOptions { ecc_percent: 80, layers: None }).unwrap();
+        assert!(high.size() > low.size());
+        assert_eq!(decode(&high), data.as_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        let binary: Vec<u8> = (0..=255).collect();
+        let inputs: [&[u8]; 6] = [
+            b"A",
+            b"Hello, World: 1234.5\r\nlower UPPER mixed@~ [punct]?",
+            b"0123456789012345678901234567890123456789",
+            b"Gr\xfc\xdfe aus K\xf6ln",
+            &binary,
+            b"a.b.c.d A1B2C3 x:y ~~~ $$$",
+        ];
+        for input in inputs {
+            let symbol = Aztec::from_bytes(input, &AztecOptions::default()).unwrap();
+            assert_eq!(decode(&symbol), input, "{}", String::from_utf8_lossy(input));
+        }
+
+        // Reference grid of a large full range symbol
+        let long = "The quick brown fox jumps over the lazy dog. ".repeat(30);
+        let symbol = Aztec::encode(&long, &AztecOptions::default()).unwrap();
+        assert!(!symbol.is_compact() && symbol.layers() > 14);
+        assert_eq!(decode(&symbol), long.as_bytes());
+    }
+
+    #[test]
+    fn finder() {
+        let symbol = Aztec::encode("Aztec", &AztecOptions::default()).unwrap();
+        let center = symbol.size() as i32 / 2;
+        for d in 0..=4 {
+            assert_eq!(symbol.get_module(center + d, center), d % 2 == 0);
+        }
+    }
+}
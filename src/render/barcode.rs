@@ -0,0 +1,298 @@
+//! Drawing of linear (1D) barcodes
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Size},
+    primitives::{Primitive, PrimitiveStyle, Rectangle},
+    Drawable,
+};
+use embedded_layout::View;
+
+use crate::render::code128::Code128;
+use crate::render::ops::{FontKind, HAlign, TextOptions, VAlign};
+use crate::render::text::StyledText;
+
+/// Errors when encoding barcode data
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarcodeError {
+    /// No data to encode
+    Empty,
+    /// Character that can't be encoded in this symbology
+    InvalidCharacter(char),
+    /// Malformed GS1 element string
+    InvalidGs1(String),
+    /// Wrong number of digits
+    InvalidLength { expected: usize, found: usize },
+    /// Check digit doesn't match the data
+    InvalidCheckDigit { expected: u8, found: u8 },
+    /// More codewords than the largest symbol holds
+    TooLong { capacity: usize, found: usize },
+    /// ECI assignment number out of range
+    InvalidEci(u32),
+}
+
+impl std::fmt::Display for BarcodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BarcodeError::Empty => write!(f, "No barcode data"),
+            BarcodeError::InvalidCharacter(c) => write!(f, "Character {c:?} can not be encoded"),
+            BarcodeError::InvalidGs1(msg) => write!(f, "Invalid GS1 data: {msg}"),
+            BarcodeError::InvalidLength { expected, found } => write!(f, "Expected {expected} digits, found {found}"),
+            BarcodeError::InvalidCheckDigit { expected, found } => {
+                write!(f, "Invalid check digit {found}, expected {expected}")
+            }
+            BarcodeError::TooLong { capacity, found } => {
+                write!(f, "Data needs {found} codewords, the largest symbol holds {capacity}")
+            }
+            BarcodeError::InvalidEci(assignment) => write!(f, "Invalid ECI assignment number {assignment}"),
+        }
+    }
+}
+
+impl std::error::Error for BarcodeError {}
+
+/// GS1 application identifiers with a predefined length, these need no FNC1 separator
+pub(crate) const GS1_FIXED_LENGTH: [&str; 22] = [
+    "00", "01", "02", "03", "04", "11", "12", "13", "14", "15", "16", "17", "18", "19", "20", "31", "32", "33", "34",
+    "35", "36", "41",
+];
+
+/// Split `(AI)value(AI)value...` into its elements
+pub(crate) fn parse_gs1(data: &str) -> Result<Vec<(&str, &str)>, BarcodeError> {
+    let invalid = |msg: &str| BarcodeError::InvalidGs1(msg.to_string());
+
+    let mut elements = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let after = rest.strip_prefix('(').ok_or_else(|| invalid("expected '(' before an application identifier"))?;
+        let (ai, after) = after.split_once(')').ok_or_else(|| invalid("missing ')'"))?;
+        if !(2..=4).contains(&ai.len()) || !ai.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid(&format!("invalid application identifier ({ai})")));
+        }
+
+        let end = after.find('(').unwrap_or(after.len());
+        let value = &after[..end];
+        if value.is_empty() {
+            return Err(invalid(&format!("empty value for ({ai})")));
+        }
+        if let Some(c) = value.chars().find(|c| !c.is_ascii() || c.is_ascii_control()) {
+            return Err(BarcodeError::InvalidCharacter(c));
+        }
+
+        elements.push((ai, value));
+        rest = &after[end..];
+    }
+
+    match elements.is_empty() {
+        true => Err(BarcodeError::Empty),
+        false => Ok(elements),
+    }
+}
+
+/// Gap between the bars and the human readable text in dots
+const TEXT_GAP: u32 = 4;
+
+/// Module width in whole dots for a nominal X-dimension in mm, e.g. `Model::dpi` for the resolution
+pub fn x_dimension(mm: f32, dpi: u32) -> u32 {
+    ((mm * dpi as f32 / 25.4).round() as u32).max(1)
+}
+
+/// A linear barcode with quiet zones and optional human readable text underneath
+#[derive(Debug, Clone)]
+pub struct StyledBarcode {
+    /// Bars (true) and spaces, one entry per module
+    pub modules: Vec<bool>,
+    /// Human readable interpretation, drawn centred below the bars
+    pub text: Option<String>,
+    pub position: Point,
+    /// Width of a module (narrowest bar) in dots
+    pub module_width: u32,
+    /// Bar height in dots
+    pub height: u32,
+    /// Quiet zone on either side in modules
+    pub quiet_zone: u32,
+    pub font: FontKind,
+    /// Modules drawn `guard_extension` dots longer, e.g. EAN guard bars. Empty for none.
+    pub guards: Vec<bool>,
+    pub guard_extension: u32,
+}
+
+impl StyledBarcode {
+    pub fn new(modules: Vec<bool>) -> Self {
+        Self {
+            modules,
+            text: None,
+            position: Point::zero(),
+            module_width: 3,
+            height: 120,
+            quiet_zone: 10,
+            font: FontKind::Font12x16,
+            guards: Vec::new(),
+            guard_extension: 0,
+        }
+    }
+
+    pub fn with_module_width(mut self, module_width: u32) -> Self {
+        self.module_width = module_width.max(1);
+        self
+    }
+
+    pub fn with_height(mut self, height: u32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_quiet_zone(mut self, quiet_zone: u32) -> Self {
+        self.quiet_zone = quiet_zone;
+        self
+    }
+
+    pub fn with_text(mut self, text: Option<String>) -> Self {
+        self.text = text;
+        self
+    }
+
+    pub fn with_font(mut self, font: FontKind) -> Self {
+        self.font = font;
+        self
+    }
+
+    /// Width including quiet zones in dots
+    pub fn width(&self) -> u32 {
+        (self.modules.len() as u32 + 2 * self.quiet_zone) * self.module_width
+    }
+
+    fn text_options(&self) -> TextOptions {
+        TextOptions { font: self.font.clone(), h_align: HAlign::Centre, v_align: VAlign::Top }
+    }
+
+    fn text_height(&self) -> u32 {
+        match self.text {
+            Some(_) => TEXT_GAP + self.text_options().line_height(),
+            None => 0,
+        }
+    }
+
+    fn is_guard(&self, module: usize) -> bool {
+        self.guards.get(module).copied().unwrap_or(false)
+    }
+
+    /// Bars as (first module, width in modules), split where the guard flag changes
+    fn bars(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut i = 0;
+        std::iter::from_fn(move || {
+            let start = i + self.modules[i..].iter().position(|&m| m)?;
+            let guard = self.is_guard(start);
+            let len = (start..self.modules.len())
+                .take_while(|&j| self.modules[j] && self.is_guard(j) == guard)
+                .count();
+            i = start + len;
+            Some((start, len))
+        })
+    }
+}
+
+impl From<Code128> for StyledBarcode {
+    fn from(code: Code128) -> Self {
+        Self::new(code.modules()).with_text(Some(code.text().to_string()))
+    }
+}
+
+impl Drawable for StyledBarcode {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let left = self.position.x + (self.quiet_zone * self.module_width) as i32;
+        let style = PrimitiveStyle::with_fill(BinaryColor::On);
+
+        for (start, len) in self.bars() {
+            let top_left = Point::new(left + (start as u32 * self.module_width) as i32, self.position.y);
+            let height = match self.is_guard(start) {
+                true => self.height + self.guard_extension,
+                false => self.height,
+            };
+            Rectangle::new(top_left, Size::new(len as u32 * self.module_width, height))
+                .into_styled(style)
+                .draw(target)?;
+        }
+
+        if let Some(text) = &self.text {
+            let top_left = self.position + Point::new(0, (self.height + TEXT_GAP) as i32);
+            let area = Rectangle::new(top_left, Size::new(self.width(), self.text_height() - TEXT_GAP));
+            StyledText::new(text, &self.text_options(), area).draw(target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Dimensions for StyledBarcode {
+    fn bounding_box(&self) -> Rectangle {
+        let guards = match self.guards.contains(&true) {
+            true => self.guard_extension,
+            false => 0,
+        };
+        let height = (self.height + self.text_height()).max(self.height + guards);
+        Rectangle::new(self.position, Size::new(self.width(), height))
+    }
+}
+
+impl View for StyledBarcode {
+    fn translate_impl(&mut self, by: Point) {
+        self.position += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::display::Display;
+
+    #[test]
+    fn bars_and_quiet_zone() {
+        let barcode = StyledBarcode::new(vec![true, false, true, true, false])
+            .with_module_width(2)
+            .with_height(4)
+            .with_quiet_zone(1);
+        assert_eq!(barcode.bars().collect::<Vec<_>>(), vec![(0, 1), (2, 2)]);
+        assert_eq!(barcode.bounding_box().size, Size::new(14, 4));
+
+        let mut display = Display::new(8, 14);
+        barcode.draw(&mut display).unwrap();
+        let row: Vec<bool> = (0..14).map(|x| display.get(x, 3).unwrap()).collect();
+        let expected = [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0].map(|b| b == 1);
+        assert_eq!(row, expected);
+        assert!(!display.get(2, 4).unwrap());
+    }
+
+    #[test]
+    fn guards() {
+        let mut barcode = StyledBarcode::new(vec![true, true, true]).with_height(4).with_quiet_zone(0);
+        barcode.guards = vec![true, false, false];
+        barcode.guard_extension = 2;
+        assert_eq!(barcode.bars().collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+        assert_eq!(barcode.bounding_box().size, Size::new(9, 6));
+    }
+
+    #[test]
+    fn x_dimensions() {
+        assert_eq!(x_dimension(0.33, 300), 4);
+        assert_eq!(x_dimension(0.33, 180), 2);
+        assert_eq!(x_dimension(0.01, 180), 1);
+    }
+
+    #[test]
+    fn text_below() {
+        let code = Code128::encode("AB").unwrap();
+        let barcode = StyledBarcode::from(code).with_height(50).with_font(FontKind::Font6x12);
+        assert_eq!(barcode.text.as_deref(), Some("AB"));
+        assert_eq!(barcode.bounding_box().size.height, 50 + TEXT_GAP + 12);
+    }
+}
@@ -0,0 +1,252 @@
+//! Scaling and dithering of images to printable dots
+use std::path::{Path, PathBuf};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Size},
+    primitives::Rectangle,
+    Drawable, Pixel,
+};
+use embedded_layout::View;
+use image::{imageops, DynamicImage, GrayImage, Luma};
+
+use crate::render::ops::{Dither, ImageOptions};
+
+/// 4x4 Bayer threshold matrix
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// An image file or an already decoded image for `Render::render_image`
+#[derive(Debug, Clone, Copy)]
+pub enum ImageSource<'a> {
+    Path(&'a Path),
+    Image(&'a DynamicImage),
+}
+
+impl<'a> From<&'a Path> for ImageSource<'a> {
+    fn from(path: &'a Path) -> Self {
+        ImageSource::Path(path)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for ImageSource<'a> {
+    fn from(path: &'a PathBuf) -> Self {
+        ImageSource::Path(path)
+    }
+}
+
+impl<'a> From<&'a str> for ImageSource<'a> {
+    fn from(path: &'a str) -> Self {
+        ImageSource::Path(Path::new(path))
+    }
+}
+
+impl<'a> From<&'a DynamicImage> for ImageSource<'a> {
+    fn from(image: &'a DynamicImage) -> Self {
+        ImageSource::Image(image)
+    }
+}
+
+/// A 1-bit image, only the printed dots are drawn so it can overlay other content
+#[derive(Debug, Clone)]
+pub struct Bitmap {
+    pub position: Point,
+    size: Size,
+    dots: Vec<bool>,
+}
+
+impl Bitmap {
+    /// Scale `image` to the largest size with the same aspect ratio that fits `area`, then dither it
+    pub fn fit(image: &DynamicImage, area: Size, options: &ImageOptions) -> Self {
+        let size = fit_size(Size::new(image.width(), image.height()), area);
+        let lightness = lightness(image, options.invert);
+        let scaled = match size == Size::new(image.width(), image.height()) {
+            true => lightness,
+            false => imageops::resize(&lightness, size.width, size.height, imageops::FilterType::Triangle),
+        };
+        Self { position: Point::zero(), size, dots: dither(&scaled, options) }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Dot at `x`, `y` relative to the top left corner, blank outside the bitmap
+    pub fn get(&self, x: i32, y: i32) -> bool {
+        let (width, height) = (self.size.width as i32, self.size.height as i32);
+        (0..width).contains(&x) && (0..height).contains(&y) && self.dots[(y * width + x) as usize]
+    }
+}
+
+/// Largest size with the aspect ratio of `image` that fits `area`
+fn fit_size(image: Size, area: Size) -> Size {
+    if image.width == 0 || image.height == 0 || area.width == 0 || area.height == 0 {
+        return Size::zero();
+    }
+    let scale = (area.width as f64 / image.width as f64).min(area.height as f64 / image.height as f64);
+    Size::new(
+        ((image.width as f64 * scale).round() as u32).clamp(1, area.width),
+        ((image.height as f64 * scale).round() as u32).clamp(1, area.height),
+    )
+}
+
+/// Grey level as printed on white tape, transparent pixels are white
+fn lightness(image: &DynamicImage, invert: bool) -> GrayImage {
+    let image = image.to_luma_alpha8();
+    GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let [luma, alpha] = image.get_pixel(x, y).0;
+        let ink = if invert { luma } else { 255 - luma };
+        Luma([255 - (ink as u16 * alpha as u16 / 255) as u8])
+    })
+}
+
+/// Printed dots, row by row
+fn dither(image: &GrayImage, options: &ImageOptions) -> Vec<bool> {
+    let threshold = options.threshold as f32;
+    match options.dither {
+        Dither::Threshold => image.pixels().map(|p| p.0[0] < options.threshold).collect(),
+        Dither::Ordered => image
+            .enumerate_pixels()
+            .map(|(x, y, p)| {
+                let offset = (BAYER[y as usize % 4][x as usize % 4] as f32 + 0.5) * 16.0 - 128.0;
+                (p.0[0] as f32) < threshold + offset
+            })
+            .collect(),
+        Dither::FloydSteinberg => {
+            let weights = [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+            diffuse(image, threshold, &weights, 16.0)
+        }
+        Dither::Atkinson => {
+            let weights = [(1, 0, 1.0), (2, 0, 1.0), (-1, 1, 1.0), (0, 1, 1.0), (1, 1, 1.0), (0, 2, 1.0)];
+            diffuse(image, threshold, &weights, 8.0)
+        }
+    }
+}
+
+/// Error diffusion, every dot passes the difference between its grey level and the printed result on to
+/// the neighbours at (dx, dy) with weight / divisor
+fn diffuse(image: &GrayImage, threshold: f32, weights: &[(i32, i32, f32)], divisor: f32) -> Vec<bool> {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    let mut values: Vec<f32> = image.pixels().map(|p| p.0[0] as f32).collect();
+    let mut dots = vec![false; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            dots[i] = values[i] < threshold;
+            let error = values[i] - if dots[i] { 0.0 } else { 255.0 };
+            for &(dx, dy, weight) in weights {
+                let (nx, ny) = (x + dx, y + dy);
+                if (0..width).contains(&nx) && ny < height {
+                    values[(ny * width + nx) as usize] += error * weight / divisor;
+                }
+            }
+        }
+    }
+    dots
+}
+
+impl Drawable for Bitmap {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let height = target.bounding_box().size.height as i32;
+        let (w, h) = (self.size.width as i32, self.size.height as i32);
+        let pixels = (0..h)
+            .flat_map(|y| (0..w).map(move |x| Point::new(x, y)))
+            .filter(|p| self.get(p.x, p.y))
+            .map(|p| p + self.position)
+            .filter(|p| p.x >= 0 && p.y >= 0 && p.y < height)
+            .map(|p| Pixel(p, BinaryColor::On));
+        target.draw_iter(pixels)
+    }
+}
+
+impl Dimensions for Bitmap {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(self.position, self.size)
+    }
+}
+
+impl View for Bitmap {
+    fn translate_impl(&mut self, by: Point) {
+        self.position += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{GrayAlphaImage, LumaA};
+
+    fn grey(width: u32, height: u32, level: u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_pixel(width, height, Luma([level])))
+    }
+
+    fn coverage(bitmap: &Bitmap) -> f32 {
+        bitmap.dots.iter().filter(|&&d| d).count() as f32 / bitmap.dots.len() as f32
+    }
+
+    #[test]
+    fn scale_to_fit() {
+        assert_eq!(fit_size(Size::new(100, 50), Size::new(40, 40)), Size::new(40, 20));
+        assert_eq!(fit_size(Size::new(10, 30), Size::new(100, 60)), Size::new(20, 60));
+        assert_eq!(fit_size(Size::new(1000, 1), Size::new(10, 10)), Size::new(10, 1));
+        assert_eq!(fit_size(Size::new(10, 10), Size::new(0, 10)), Size::zero());
+
+        let bitmap = Bitmap::fit(&grey(100, 50, 0), Size::new(40, 40), &ImageOptions::default());
+        assert_eq!(bitmap.size(), Size::new(40, 20));
+        assert!(bitmap.get(39, 19) && !bitmap.get(40, 19));
+    }
+
+    #[test]
+    fn dithering() {
+        let threshold = ImageOptions { dither: Dither::Threshold, ..Default::default() };
+        assert_eq!(coverage(&Bitmap::fit(&grey(16, 16, 127), Size::new(16, 16), &threshold)), 1.0);
+        assert_eq!(coverage(&Bitmap::fit(&grey(16, 16, 128), Size::new(16, 16), &threshold)), 0.0);
+
+        for dither in [Dither::FloydSteinberg, Dither::Atkinson, Dither::Ordered] {
+            let options = ImageOptions { dither, ..Default::default() };
+            let black = Bitmap::fit(&grey(16, 16, 0), Size::new(16, 16), &options);
+            let white = Bitmap::fit(&grey(16, 16, 255), Size::new(16, 16), &options);
+            assert_eq!((coverage(&black), coverage(&white)), (1.0, 0.0), "{dither:?}");
+
+        }
+
+        let quarter = |dither| {
+            let options = ImageOptions { dither, ..Default::default() };
+            coverage(&Bitmap::fit(&grey(32, 32, 192), Size::new(32, 32), &options))
+        };
+        assert!((quarter(Dither::FloydSteinberg) - 0.25).abs() < 0.02);
+        assert_eq!(quarter(Dither::Ordered), 0.25);
+        // Atkinson drops part of the error, light areas get lighter
+        assert!((0.1..0.2).contains(&quarter(Dither::Atkinson)));
+
+        // The Bayer matrix prints exactly half of a mid grey area in a checkerboard
+        let ordered = ImageOptions { dither: Dither::Ordered, ..Default::default() };
+        let half = Bitmap::fit(&grey(4, 4, 128), Size::new(4, 4), &ordered);
+        assert_eq!(coverage(&half), 0.5);
+        assert!(half.get(0, 1) && half.get(1, 0) && !half.get(0, 0) && !half.get(1, 1));
+    }
+
+    #[test]
+    fn alpha_and_invert() {
+        // Opaque black on the left, transparent black on the right
+        let image = DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(2, 1, |x, _| LumaA([0, [255, 0][x as usize]])));
+        let options = ImageOptions { dither: Dither::Threshold, ..Default::default() };
+        let bitmap = Bitmap::fit(&image, Size::new(2, 1), &options);
+        assert!(bitmap.get(0, 0) && !bitmap.get(1, 0));
+
+        let white = DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(2, 1, |x, _| LumaA([255, [255, 0][x as usize]])));
+        let inverted = ImageOptions { invert: true, ..options };
+        let bitmap = Bitmap::fit(&white, Size::new(2, 1), &inverted);
+        assert!(bitmap.get(0, 0) && !bitmap.get(1, 0));
+    }
+}
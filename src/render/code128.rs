@@ -0,0 +1,295 @@
+//! Code 128 encoder with automatic A/B/C subset switching and GS1-128 support
+use crate::render::barcode::{parse_gs1, BarcodeError, GS1_FIXED_LENGTH};
+
+/// Bar and space widths of every symbol value, start codes are 103-105
+const PATTERNS: [&[u8; 6]; 106] = [
+    b"212222", b"222122", b"222221", b"121223", b"121322", b"131222", b"122213", b"122312", b"132212", b"221213",
+    b"221312", b"231212", b"112232", b"122132", b"122231", b"113222", b"123122", b"123221", b"223211", b"221132",
+    b"221231", b"213212", b"223112", b"312131", b"311222", b"321122", b"321221", b"312212", b"322112", b"322211",
+    b"212123", b"212321", b"232121", b"111323", b"131123", b"131321", b"112313", b"132113", b"132311", b"211313",
+    b"231113", b"231311", b"112133", b"112331", b"132131", b"113123", b"113321", b"133121", b"313121", b"211331",
+    b"231131", b"213113", b"213311", b"213131", b"311123", b"311321", b"331121", b"312113", b"312311", b"332111",
+    b"314111", b"221411", b"431111", b"111224", b"111422", b"121124", b"121421", b"141122", b"141221", b"112214",
+    b"112412", b"122114", b"122411", b"142112", b"142211", b"241211", b"221114", b"413111", b"241112", b"134111",
+    b"111242", b"121142", b"121241", b"114212", b"124112", b"124211", b"411212", b"421112", b"421211", b"212141",
+    b"214121", b"412121", b"111143", b"111341", b"131141", b"114113", b"114311", b"411113", b"411311", b"113141",
+    b"114131", b"311141", b"411131", b"211412", b"211214", b"211232",
+];
+const STOP: &[u8; 7] = b"2331112";
+
+const START_A: u8 = 103;
+const START_B: u8 = 104;
+const START_C: u8 = 105;
+const CODE_A: u8 = 101;
+const CODE_B: u8 = 100;
+const CODE_C: u8 = 99;
+const SHIFT: u8 = 98;
+const FNC1: u8 = 102;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Set {
+    A,
+    B,
+    C,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Char(u8),
+    Fnc1,
+}
+
+/// An encoded Code 128 symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code128 {
+    values: Vec<u8>,
+    text: String,
+}
+
+impl Code128 {
+    /// Encode ASCII text, subsets are chosen for the shortest symbol
+    pub fn encode(data: &str) -> Result<Self, BarcodeError> {
+        let tokens = data
+            .chars()
+            .map(|c| match c.is_ascii() {
+                true => Ok(Token::Char(c as u8)),
+                false => Err(BarcodeError::InvalidCharacter(c)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if tokens.is_empty() {
+            return Err(BarcodeError::Empty);
+        }
+
+        Ok(Self { values: encode_tokens(&tokens), text: data.to_string() })
+    }
+
+    /// Encode GS1-128 element strings written as `(01)09501101530003(10)ABC123`.
+    /// FNC1 is inserted after the start code and after variable length fields.
+    pub fn gs1(data: &str) -> Result<Self, BarcodeError> {
+        let elements = parse_gs1(data)?;
+
+        let mut tokens = vec![Token::Fnc1];
+        for (i, (ai, value)) in elements.iter().enumerate() {
+            tokens.extend(ai.bytes().chain(value.bytes()).map(Token::Char));
+            let last = i == elements.len() - 1;
+            if !last && !GS1_FIXED_LENGTH.contains(&&ai[..2]) {
+                tokens.push(Token::Fnc1);
+            }
+        }
+
+        Ok(Self { values: encode_tokens(&tokens), text: data.to_string() })
+    }
+
+    /// Symbol values including start code, check symbol and stop code (106)
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// Human readable text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Bars (true) and spaces, one entry per module, without quiet zones
+    pub fn modules(&self) -> Vec<bool> {
+        let mut modules = Vec::with_capacity(self.values.len() * 11 + 2);
+        for &value in &self.values {
+            let widths: &[u8] = match value {
+                106 => STOP,
+                v => PATTERNS[v as usize],
+            };
+            for (i, w) in widths.iter().enumerate() {
+                modules.extend(std::iter::repeat_n(i % 2 == 0, (w - b'0') as usize));
+            }
+        }
+        modules
+    }
+}
+
+/// Number of digits at the start of `tokens`
+fn digit_run(tokens: &[Token]) -> usize {
+    tokens.iter().take_while(|t| matches!(t, Token::Char(c) if c.is_ascii_digit())).count()
+}
+
+fn in_set(token: Token, set: Set) -> bool {
+    match (token, set) {
+        (Token::Fnc1, _) => true,
+        (Token::Char(c), Set::A) => c < 96,
+        (Token::Char(c), Set::B) => (32..128).contains(&c),
+        (Token::Char(_), Set::C) => false,
+    }
+}
+
+/// A or B, whichever is needed first by the upcoming characters
+fn choose_ab(tokens: &[Token]) -> Set {
+    for &t in tokens {
+        match t {
+            Token::Char(c) if c < 32 => return Set::A,
+            Token::Char(c) if c >= 96 => return Set::B,
+            _ => {}
+        }
+    }
+    Set::B
+}
+
+/// Switch to C for runs of at least 4 digits at the start or end, 6 in the middle
+fn use_c(tokens: &[Token], at_start: bool) -> bool {
+    let run = digit_run(tokens);
+    let at_end = run == tokens.len();
+    match (at_start, at_end) {
+        (true, true) => run >= 2 && run.is_multiple_of(2),
+        (true, false) | (false, true) => run >= 4,
+        (false, false) => run >= 6,
+    }
+}
+
+fn value(token: Token, set: Set) -> u8 {
+    match (token, set) {
+        (Token::Fnc1, _) => FNC1,
+        (Token::Char(c), Set::A) if c < 32 => c + 64,
+        (Token::Char(c), _) => c - 32,
+    }
+}
+
+fn encode_tokens(tokens: &[Token]) -> Vec<u8> {
+    // A leading FNC1 (GS1) doesn't affect the choice of start code
+    let skip = tokens.iter().take_while(|&&t| t == Token::Fnc1).count();
+    let mut set = match use_c(&tokens[skip..], true) {
+        true => Set::C,
+        false => choose_ab(tokens),
+    };
+    let mut values = vec![match set {
+        Set::A => START_A,
+        Set::B => START_B,
+        Set::C => START_C,
+    }];
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let rest = &tokens[i..];
+
+        if set == Set::C {
+            if rest[0] == Token::Fnc1 {
+                values.push(FNC1);
+                i += 1;
+            } else if digit_run(rest) >= 2 {
+                let (Token::Char(a), Token::Char(b)) = (rest[0], rest[1]) else { unreachable!() };
+                values.push((a - b'0') * 10 + (b - b'0'));
+                i += 2;
+            } else {
+                set = choose_ab(rest);
+                values.push(if set == Set::A { CODE_A } else { CODE_B });
+            }
+            continue;
+        }
+
+        if rest[0] != Token::Fnc1 && use_c(rest, false) {
+            // Odd runs: the first digit stays in the current set
+            if digit_run(rest) % 2 == 1 {
+                values.push(value(rest[0], set));
+                i += 1;
+            }
+            set = Set::C;
+            values.push(CODE_C);
+            continue;
+        }
+
+        if in_set(rest[0], set) {
+            values.push(value(rest[0], set));
+            i += 1;
+            continue;
+        }
+
+        // Shift for a single character, switch otherwise
+        let other = if set == Set::A { Set::B } else { Set::A };
+        if rest.get(1).is_some_and(|&next| in_set(next, set)) {
+            values.push(SHIFT);
+            values.push(value(rest[0], other));
+            i += 1;
+        } else {
+            set = other;
+            values.push(if set == Set::A { CODE_A } else { CODE_B });
+        }
+    }
+
+    let checksum = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| i.max(1) as u32 * v as u32)
+        .sum::<u32>()
+        % 103;
+    values.push(checksum as u8);
+    values.push(106);
+    values
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn modules_str(code: &Code128) -> String {
+        code.modules().iter().map(|&m| if m { '1' } else { '0' }).collect()
+    }
+
+    #[test]
+    fn patterns() {
+        for p in PATTERNS {
+            let w: Vec<u8> = p.iter().map(|b| b - b'0').collect();
+            assert_eq!(w.iter().sum::<u8>(), 11);
+            // Bars have an even number of modules
+            assert_eq!((w[0] + w[2] + w[4]) % 2, 0);
+        }
+    }
+
+    #[test]
+    fn subset_b() {
+        let code = Code128::encode("PJJ123C").unwrap();
+        // P J J 1 2 3 C in B, the digit run is too short for C
+        assert_eq!(code.values(), &[START_B, 48, 42, 42, 17, 18, 19, 35, 55, 106]);
+        assert!(modules_str(&code).starts_with("11010010000"));
+        assert!(modules_str(&code).ends_with("1100011101011"));
+    }
+
+    #[test]
+    fn subset_c() {
+        let code = Code128::encode("123456").unwrap();
+        assert_eq!(code.values(), &[START_C, 12, 34, 56, ((105u32 + 12 + 34 * 2 + 56 * 3) % 103) as u8, 106]);
+        assert_eq!(code.modules().len(), 5 * 11 + 13);
+
+        // Odd digit count: first digit in B, then C
+        let code = Code128::encode("A12345").unwrap();
+        assert_eq!(&code.values()[..5], &[START_B, 33, 17, CODE_C, 23]);
+    }
+
+    #[test]
+    fn shift_and_switch() {
+        // Single control character between lowercase letters uses shift
+        let code = Code128::encode("a\tb").unwrap();
+        assert_eq!(&code.values()[..5], &[START_B, 65, SHIFT, 73, 66]);
+
+        // Several control characters switch to A
+        let code = Code128::encode("a\t\tb").unwrap();
+        assert_eq!(&code.values()[..6], &[START_B, 65, CODE_A, 73, 73, CODE_B]);
+    }
+
+    #[test]
+    fn gs1() {
+        let code = Code128::gs1("(01)09501101530003(10)AB1(21)12345").unwrap();
+        let v = code.values();
+        assert_eq!(&v[..2], &[START_C, FNC1]);
+        // (01) is fixed length, no separator before (10)
+        assert_eq!(&v[2..11], &[1, 9, 50, 11, 1, 53, 0, 3, 10]);
+        // (10) is variable length: AB1 in B, then FNC1 before (21)
+        assert_eq!(&v[11..16], &[CODE_B, 33, 34, 17, FNC1]);
+        assert_eq!(code.text(), "(01)09501101530003(10)AB1(21)12345");
+
+        assert!(matches!(Code128::gs1("01)123"), Err(BarcodeError::InvalidGs1(_))));
+        assert!(matches!(Code128::gs1("(01)"), Err(BarcodeError::InvalidGs1(_))));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Code128::encode(""), Err(BarcodeError::Empty));
+        assert_eq!(Code128::encode("Grüße"), Err(BarcodeError::InvalidCharacter('ü')));
+    }
+}
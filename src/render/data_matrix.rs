@@ -0,0 +1,568 @@
+//! Data Matrix (ECC200) encoder with square and rectangular symbols and GS1 support
+use embedded_graphics::prelude::Size;
+
+use crate::render::barcode::{parse_gs1, BarcodeError, GS1_FIXED_LENGTH};
+use crate::render::matrix::{MatrixSymbol, StyledMatrix};
+
+const PAD: u8 = 129;
+const FNC1: u8 = 232;
+const UPPER_SHIFT: u8 = 235;
+
+/// Symbol shapes to choose from when encoding
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SymbolShape {
+    #[default]
+    Square,
+    Rectangle,
+    /// Smallest symbol of either shape
+    Any,
+}
+
+/// An ECC200 symbol size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolSize {
+    pub rows: usize,
+    pub cols: usize,
+    /// Size of a single data region, without its finder and timing pattern
+    region_rows: usize,
+    region_cols: usize,
+    /// Data codewords
+    pub data: usize,
+    /// Error correction codewords
+    pub ecc: usize,
+    /// Interleaved Reed-Solomon blocks
+    blocks: usize,
+}
+
+const fn size(
+    rows: usize,
+    cols: usize,
+    region_rows: usize,
+    region_cols: usize,
+    data: usize,
+    ecc: usize,
+    blocks: usize,
+) -> SymbolSize {
+    SymbolSize { rows, cols, region_rows, region_cols, data, ecc, blocks }
+}
+
+/// All ECC200 symbols, ordered by capacity within each shape
+const SIZES: [SymbolSize; 30] = [
+    size(10, 10, 8, 8, 3, 5, 1),
+    size(12, 12, 10, 10, 5, 7, 1),
+    size(14, 14, 12, 12, 8, 10, 1),
+    size(16, 16, 14, 14, 12, 12, 1),
+    size(18, 18, 16, 16, 18, 14, 1),
+    size(20, 20, 18, 18, 22, 18, 1),
+    size(22, 22, 20, 20, 30, 20, 1),
+    size(24, 24, 22, 22, 36, 24, 1),
+    size(26, 26, 24, 24, 44, 28, 1),
+    size(32, 32, 14, 14, 62, 36, 1),
+    size(36, 36, 16, 16, 86, 42, 1),
+    size(40, 40, 18, 18, 114, 48, 1),
+    size(44, 44, 20, 20, 144, 56, 1),
+    size(48, 48, 22, 22, 174, 68, 1),
+    size(52, 52, 24, 24, 204, 84, 2),
+    size(64, 64, 14, 14, 280, 112, 2),
+    size(72, 72, 16, 16, 368, 144, 4),
+    size(80, 80, 18, 18, 456, 192, 4),
+    size(88, 88, 20, 20, 576, 224, 4),
+    size(96, 96, 22, 22, 696, 272, 4),
+    size(104, 104, 24, 24, 816, 336, 6),
+    size(120, 120, 18, 18, 1050, 408, 6),
+    size(132, 132, 20, 20, 1304, 496, 8),
+    size(144, 144, 22, 22, 1558, 620, 10),
+    size(8, 18, 6, 16, 5, 7, 1),
+    size(8, 32, 6, 14, 10, 11, 1),
+    size(12, 26, 10, 24, 16, 14, 1),
+    size(12, 36, 10, 16, 22, 18, 1),
+    size(16, 36, 14, 16, 32, 24, 1),
+    size(16, 48, 14, 22, 49, 28, 1),
+];
+
+impl SymbolSize {
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    /// Smallest symbol of `shape` holding `codewords` data codewords
+    pub fn fitting(codewords: usize, shape: SymbolShape) -> Result<Self, BarcodeError> {
+        let candidates = SIZES.iter().filter(|s| match shape {
+            SymbolShape::Square => s.is_square(),
+            SymbolShape::Rectangle => !s.is_square(),
+            SymbolShape::Any => true,
+        });
+        let capacity = candidates.clone().map(|s| s.data).max().unwrap_or(0);
+
+        candidates
+            .filter(|s| s.data >= codewords)
+            .min_by_key(|s| s.rows * s.cols)
+            .copied()
+            .ok_or(BarcodeError::TooLong { capacity, found: codewords })
+    }
+
+    /// Rows and columns of the data regions combined (the mapping matrix)
+    fn mapping_size(&self) -> (usize, usize) {
+        let regions_v = self.rows / (self.region_rows + 2);
+        let regions_h = self.cols / (self.region_cols + 2);
+        (regions_v * self.region_rows, regions_h * self.region_cols)
+    }
+}
+
+/// An encoded Data Matrix symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataMatrix {
+    size: SymbolSize,
+    codewords: Vec<u8>,
+    /// Row major, true is a dark module
+    modules: Vec<bool>,
+}
+
+impl DataMatrix {
+    /// Encode text, characters beyond ISO 8859-1 can't be encoded
+    pub fn encode(data: &str, shape: SymbolShape) -> Result<Self, BarcodeError> {
+        let bytes = data
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| BarcodeError::InvalidCharacter(c)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_bytes(&bytes, shape)
+    }
+
+    /// Encode binary data
+    pub fn from_bytes(data: &[u8], shape: SymbolShape) -> Result<Self, BarcodeError> {
+        if data.is_empty() {
+            return Err(BarcodeError::Empty);
+        }
+        let mut codewords = Vec::with_capacity(data.len());
+        encode_ascii(data, &mut codewords);
+        Self::from_codewords(codewords, shape)
+    }
+
+    /// Encode GS1 element strings written as `(01)09501101530003(10)ABC123`.
+    /// FNC1 starts the symbol and follows variable length fields.
+    pub fn gs1(data: &str, shape: SymbolShape) -> Result<Self, BarcodeError> {
+        let elements = parse_gs1(data)?;
+
+        let mut codewords = vec![FNC1];
+        for (i, (ai, value)) in elements.iter().enumerate() {
+            encode_ascii(ai.as_bytes(), &mut codewords);
+            encode_ascii(value.as_bytes(), &mut codewords);
+            let last = i == elements.len() - 1;
+            if !last && !GS1_FIXED_LENGTH.contains(&&ai[..2]) {
+                codewords.push(FNC1);
+            }
+        }
+        Self::from_codewords(codewords, shape)
+    }
+
+    fn from_codewords(mut codewords: Vec<u8>, shape: SymbolShape) -> Result<Self, BarcodeError> {
+        let size = SymbolSize::fitting(codewords.len(), shape)?;
+        pad(&mut codewords, size.data);
+        add_ecc(&mut codewords, &size);
+        let modules = place(&codewords, &size);
+        Ok(Self { size, codewords, modules })
+    }
+
+    pub fn size(&self) -> SymbolSize {
+        self.size
+    }
+
+    /// Data, padding and error correction codewords in symbol order
+    pub fn codewords(&self) -> &[u8] {
+        &self.codewords
+    }
+
+    /// Module at column `x` and row `y`, light outside the symbol
+    pub fn get_module(&self, x: i32, y: i32) -> bool {
+        let (rows, cols) = (self.size.rows as i32, self.size.cols as i32);
+        (0..cols).contains(&x) && (0..rows).contains(&y) && self.modules[(y * cols + x) as usize]
+    }
+}
+
+/// ASCII encodation, digit pairs take a single codeword
+fn encode_ascii(data: &[u8], codewords: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < data.len() {
+        match (data[i], data.get(i + 1)) {
+            (a, Some(b)) if a.is_ascii_digit() && b.is_ascii_digit() => {
+                codewords.push(130 + (a - b'0') * 10 + (b - b'0'));
+                i += 2;
+                continue;
+            }
+            (a, _) if a < 128 => codewords.push(a + 1),
+            (a, _) => codewords.extend([UPPER_SHIFT, a - 127]),
+        }
+        i += 1;
+    }
+}
+
+/// Fill up to `capacity`, all but the first pad codeword are randomised (253-state algorithm)
+fn pad(codewords: &mut Vec<u8>, capacity: usize) {
+    if codewords.len() < capacity {
+        codewords.push(PAD);
+    }
+    while codewords.len() < capacity {
+        let position = codewords.len() + 1;
+        let value = PAD as usize + (149 * position) % 253 + 1;
+        codewords.push(match value > 254 {
+            true => value - 254,
+            false => value,
+        } as u8);
+    }
+}
+
+/// Powers of the generator 2 in GF(256) with the Data Matrix polynomial x^8 + x^5 + x^3 + x^2 + 1,
+/// and their logarithms
+fn gf_tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    for (i, e) in exp.iter_mut().enumerate() {
+        *e = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x12d;
+        }
+    }
+    (exp, log)
+}
+
+/// Reed-Solomon error correction codewords for one block
+fn reed_solomon(data: &[u8], ecc_len: usize) -> Vec<u8> {
+    let (exp, log) = gf_tables();
+    let mul = |a: u8, b: u8| match a == 0 || b == 0 {
+        true => 0,
+        false => exp[(log[a as usize] as usize + log[b as usize] as usize) % 255],
+    };
+
+    // Generator polynomial (x - 2^1)(x - 2^2)...(x - 2^n), highest power first
+    let mut generator = vec![1u8];
+    for i in 1..=ecc_len {
+        let root = exp[i % 255];
+        let mut next = generator.clone();
+        next.push(0);
+        for (j, &g) in generator.iter().enumerate() {
+            next[j + 1] ^= mul(g, root);
+        }
+        generator = next;
+    }
+
+    let mut ecc = vec![0u8; ecc_len];
+    for &d in data {
+        let factor = d ^ ecc[0];
+        ecc.rotate_left(1);
+        ecc[ecc_len - 1] = 0;
+        for (e, &g) in ecc.iter_mut().zip(&generator[1..]) {
+            *e ^= mul(g, factor);
+        }
+    }
+    ecc
+}
+
+/// Append the error correction codewords, larger symbols interleave several blocks
+fn add_ecc(codewords: &mut Vec<u8>, size: &SymbolSize) {
+    let ecc_len = size.ecc / size.blocks;
+    let mut ecc = vec![0u8; size.ecc];
+    for block in 0..size.blocks {
+        let data: Vec<u8> = codewords.iter().skip(block).step_by(size.blocks).copied().collect();
+        for (i, e) in reed_solomon(&data, ecc_len).into_iter().enumerate() {
+            ecc[i * size.blocks + block] = e;
+        }
+    }
+    codewords.extend(ecc);
+}
+
+/// Codeword placement into the mapping matrix (ISO/IEC 16022 Annex F)
+struct Placement<'a> {
+    codewords: &'a [u8],
+    rows: i32,
+    cols: i32,
+    bits: Vec<Option<bool>>,
+}
+
+impl<'a> Placement<'a> {
+    fn is_set(&self, row: i32, col: i32) -> bool {
+        self.bits[(row * self.cols + col) as usize].is_some()
+    }
+
+    /// Place bit `bit` (0 is the most significant) of codeword `index`, wrapping around the edges
+    fn module(&mut self, mut row: i32, mut col: i32, index: usize, bit: u8) {
+        if row < 0 {
+            row += self.rows;
+            col += 4 - (self.rows + 4) % 8;
+        }
+        if col < 0 {
+            col += self.cols;
+            row += 4 - (self.cols + 4) % 8;
+        }
+        let value = self.codewords[index] & (0x80 >> bit) != 0;
+        self.bits[(row * self.cols + col) as usize] = Some(value);
+    }
+
+    fn modules(&mut self, index: usize, positions: [(i32, i32); 8]) {
+        for (bit, (row, col)) in positions.into_iter().enumerate() {
+            self.module(row, col, index, bit as u8);
+        }
+    }
+
+    /// The standard L shaped codeword with its last bit at `row`, `col`
+    fn utah(&mut self, row: i32, col: i32, index: usize) {
+        self.modules(
+            index,
+            [
+                (row - 2, col - 2),
+                (row - 2, col - 1),
+                (row - 1, col - 2),
+                (row - 1, col - 1),
+                (row - 1, col),
+                (row, col - 2),
+                (row, col - 1),
+                (row, col),
+            ],
+        );
+    }
+
+    fn corner(&mut self, kind: u8, index: usize) {
+        let (r, c) = (self.rows, self.cols);
+        let positions = match kind {
+            1 => [(r - 1, 0), (r - 1, 1), (r - 1, 2), (0, c - 2), (0, c - 1), (1, c - 1), (2, c - 1), (3, c - 1)],
+            2 => [(r - 3, 0), (r - 2, 0), (r - 1, 0), (0, c - 4), (0, c - 3), (0, c - 2), (0, c - 1), (1, c - 1)],
+            3 => [(r - 3, 0), (r - 2, 0), (r - 1, 0), (0, c - 2), (0, c - 1), (1, c - 1), (2, c - 1), (3, c - 1)],
+            _ => [(r - 1, 0), (r - 1, c - 1), (0, c - 3), (0, c - 2), (0, c - 1), (1, c - 3), (1, c - 2), (1, c - 1)],
+        };
+        self.modules(index, positions);
+    }
+
+    /// Place all codewords, returns how many were placed
+    fn fill(&mut self) -> usize {
+        let (rows, cols) = (self.rows, self.cols);
+        let mut index = 0;
+        let mut next = || {
+            index += 1;
+            index - 1
+        };
+        let (mut row, mut col) = (4, 0);
+
+        loop {
+            if row == rows && col == 0 {
+                self.corner(1, next());
+            }
+            if row == rows - 2 && col == 0 && cols % 4 != 0 {
+                self.corner(2, next());
+            }
+            if row == rows - 2 && col == 0 && cols % 8 == 4 {
+                self.corner(3, next());
+            }
+            if row == rows + 4 && col == 2 && cols % 8 == 0 {
+                self.corner(4, next());
+            }
+
+            // Sweep up and to the right
+            loop {
+                if row < rows && col >= 0 && !self.is_set(row, col) {
+                    self.utah(row, col, next());
+                }
+                row -= 2;
+                col += 2;
+                if row < 0 || col >= cols {
+                    break;
+                }
+            }
+            row += 1;
+            col += 3;
+
+            // Sweep down and to the left
+            loop {
+                if row >= 0 && col < cols && !self.is_set(row, col) {
+                    self.utah(row, col, next());
+                }
+                row += 2;
+                col -= 2;
+                if row >= rows || col < 0 {
+                    break;
+                }
+            }
+            row += 3;
+            col += 1;
+
+            if row >= rows && col >= cols {
+                break;
+            }
+        }
+        next()
+    }
+
+    fn run(mut self) -> Vec<bool> {
+        let (rows, cols) = (self.rows, self.cols);
+        self.fill();
+
+        // Unused modules in the bottom right corner get a fixed pattern
+        if !self.is_set(rows - 1, cols - 1) {
+            self.bits[((rows - 1) * cols + cols - 1) as usize] = Some(true);
+            self.bits[((rows - 2) * cols + cols - 2) as usize] = Some(true);
+        }
+
+        self.bits.into_iter().map(|b| b.unwrap_or(false)).collect()
+    }
+}
+
+/// Symbol modules, the mapping matrix split into data regions with finder and timing patterns
+fn place(codewords: &[u8], size: &SymbolSize) -> Vec<bool> {
+    let (map_rows, map_cols) = size.mapping_size();
+    let mapping = Placement {
+        codewords,
+        rows: map_rows as i32,
+        cols: map_cols as i32,
+        bits: vec![None; map_rows * map_cols],
+    }
+    .run();
+
+    let (region_h, region_w) = (size.region_rows + 2, size.region_cols + 2);
+    let mut modules = Vec::with_capacity(size.rows * size.cols);
+    for row in 0..size.rows {
+        for col in 0..size.cols {
+            let (r, c) = (row % region_h, col % region_w);
+            modules.push(match (r, c) {
+                // Solid L on the left and bottom, alternating timing on the top and right
+                (_, 0) => true,
+                (r, _) if r == region_h - 1 => true,
+                (0, c) => c % 2 == 0,
+                (r, c) if c == region_w - 1 => r % 2 == 1,
+                (r, c) => {
+                    let map_row = row / region_h * size.region_rows + r - 1;
+                    let map_col = col / region_w * size.region_cols + c - 1;
+                    mapping[map_row * map_cols + map_col]
+                }
+            });
+        }
+    }
+    modules
+}
+
+impl MatrixSymbol for DataMatrix {
+    fn modules(&self) -> Size {
+        Size::new(self.size.cols as u32, self.size.rows as u32)
+    }
+
+    fn get_module(&self, x: i32, y: i32) -> bool {
+        self.get_module(x, y)
+    }
+}
+
+/// A Data Matrix symbol drawn with square modules and a quiet zone
+pub type StyledDataMatrix = StyledMatrix<DataMatrix>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::display::Display;
+    use embedded_graphics::{
+        prelude::{Dimensions, Point},
+        Drawable,
+    };
+
+    #[test]
+    fn ascii_encodation() {
+        let mut codewords = Vec::new();
+        encode_ascii(b"123456", &mut codewords);
+        assert_eq!(codewords, [142, 164, 186]);
+
+        codewords.clear();
+        encode_ascii(b"A1b\xe9", &mut codewords);
+        assert_eq!(codewords, [66, 50, 99, UPPER_SHIFT, 106]);
+    }
+
+    #[test]
+    fn error_correction() {
+        // ISO/IEC 16022 example symbol for "123456"
+        let code = DataMatrix::encode("123456", SymbolShape::Square).unwrap();
+        assert_eq!(code.size().rows, 10);
+        assert_eq!(code.codewords(), [142, 164, 186, 114, 25, 5, 88, 102]);
+    }
+
+    #[test]
+    fn padding() {
+        let code = DataMatrix::encode("A", SymbolShape::Square).unwrap();
+        assert_eq!(&code.codewords()[..3], [66, PAD, 70]);
+    }
+
+    #[test]
+    fn placement_covers_every_module() {
+        for size in SIZES {
+            let (rows, cols) = size.mapping_size();
+            let codewords = vec![0u8; size.data + size.ecc];
+            let mut placement =
+                Placement { codewords: &codewords, rows: rows as i32, cols: cols as i32, bits: vec![None; rows * cols] };
+            assert_eq!(placement.fill(), codewords.len(), "{size:?}");
+            // No module is placed twice
+            assert_eq!(placement.bits.iter().filter(|b| b.is_some()).count(), 8 * codewords.len(), "{size:?}");
+
+            // Only the fixed pattern in the bottom right corner is left
+            for (i, bit) in placement.bits.iter().enumerate() {
+                let (row, col) = (i / cols, i % cols);
+                assert!(bit.is_some() || (row >= rows - 2 && col >= cols - 2), "{size:?} {row} {col}");
+            }
+        }
+    }
+
+    #[test]
+    fn finder_pattern() {
+        let code = DataMatrix::encode(&"0123456789".repeat(10), SymbolShape::Square).unwrap();
+        let size = code.size();
+        assert_eq!((size.rows, size.cols), (32, 32));
+        for i in 0..32 {
+            assert!(code.get_module(0, i) && code.get_module(i, 31));
+            assert_eq!(code.get_module(i, 0), i % 2 == 0);
+            assert_eq!(code.get_module(31, i), i % 2 == 1);
+            // Inner finder patterns of the 2x2 regions
+            assert!(code.get_module(16, i) && code.get_module(i, 15));
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let data = "ABCDEFGHIJKLMNOP";
+        let square = DataMatrix::encode(data, SymbolShape::Square).unwrap().size();
+        assert_eq!((square.rows, square.cols), (18, 18));
+        let rectangle = DataMatrix::encode(data, SymbolShape::Rectangle).unwrap().size();
+        assert_eq!((rectangle.rows, rectangle.cols), (12, 26));
+        let any = DataMatrix::encode(data, SymbolShape::Any).unwrap().size();
+        assert_eq!(any, rectangle);
+
+        let long = "A".repeat(50);
+        assert_eq!(
+            DataMatrix::encode(&long, SymbolShape::Rectangle),
+            Err(BarcodeError::TooLong { capacity: 49, found: 50 })
+        );
+        assert_eq!(DataMatrix::encode("", SymbolShape::Square), Err(BarcodeError::Empty));
+        assert_eq!(DataMatrix::encode("€", SymbolShape::Square), Err(BarcodeError::InvalidCharacter('€')));
+    }
+
+    #[test]
+    fn gs1() {
+        let code = DataMatrix::gs1("(01)09501101530003(10)AB(21)1", SymbolShape::Square).unwrap();
+        let codewords = code.codewords();
+        assert_eq!(codewords[0], FNC1);
+        // (01) is fixed length, (10) needs a separator
+        assert_eq!(&codewords[1..9], [131, 139, 180, 141, 131, 183, 130, 133]);
+        assert_eq!(&codewords[9..15], [140, 66, 67, FNC1, 151, 50]);
+    }
+
+    #[test]
+    fn styled() {
+        let code = DataMatrix::encode("123456", SymbolShape::Square).unwrap();
+        let mut styled = StyledDataMatrix::from(code).with_scale(2).with_quiet_zone(2);
+        styled.position = Point::new(-2, 3);
+        assert_eq!(styled.bounding_box().size, Size::new(28, 28));
+        assert!(!styled.get_element(Point::new(3, 3)));
+        assert!(styled.get_element(Point::new(4, 4)));
+        assert!(!styled.clone().inverted().get_element(Point::new(4, 4)));
+
+        let mut display = Display::new(24, 8);
+        styled.draw(&mut display).unwrap();
+        // Left finder column of the symbol, shifted by the position
+        assert!(display.get(2, 20).unwrap());
+        assert!(!display.get(1, 20).unwrap());
+    }
+}
@@ -0,0 +1,383 @@
+//! EAN-13, UPC-A and EAN-8 encoders
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Size},
+    primitives::Rectangle,
+    Drawable,
+};
+use embedded_layout::View;
+
+use crate::render::barcode::{BarcodeError, StyledBarcode};
+use crate::render::ops::{FontKind, HAlign, TextOptions, VAlign};
+use crate::render::text::StyledText;
+
+/// Left hand odd parity (set A) digit patterns, set C is the complement and set B the reversed complement
+const L_CODES: [u8; 10] = [
+    0b0001101, 0b0011001, 0b0010011, 0b0111101, 0b0100011, 0b0110001, 0b0101111, 0b0111011, 0b0110111, 0b0001011,
+];
+
+/// Even parity (set B) digits of the left half, encoding the first EAN-13 digit
+const PARITY: [u8; 10] = [
+    0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110, 0b011010,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EanKind {
+    Ean13,
+    UpcA,
+    Ean8,
+}
+
+impl EanKind {
+    /// Number of digits including the check digit
+    pub fn digits(&self) -> usize {
+        match self {
+            EanKind::Ean13 => 13,
+            EanKind::UpcA => 12,
+            EanKind::Ean8 => 8,
+        }
+    }
+
+    /// Minimum quiet zones (left, right) in modules
+    pub fn quiet_zones(&self) -> (u32, u32) {
+        match self {
+            EanKind::Ean13 => (11, 7),
+            EanKind::UpcA => (9, 9),
+            EanKind::Ean8 => (7, 7),
+        }
+    }
+}
+
+/// An EAN / UPC symbol with a valid check digit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ean {
+    kind: EanKind,
+    digits: Vec<u8>,
+}
+
+impl Ean {
+    /// Encode 12 digits (check digit is added) or 13 digits (check digit is validated)
+    pub fn ean13(data: &str) -> Result<Self, BarcodeError> {
+        Self::new(EanKind::Ean13, data)
+    }
+
+    /// Encode 11 or 12 digits
+    pub fn upca(data: &str) -> Result<Self, BarcodeError> {
+        Self::new(EanKind::UpcA, data)
+    }
+
+    /// Encode 7 or 8 digits
+    pub fn ean8(data: &str) -> Result<Self, BarcodeError> {
+        Self::new(EanKind::Ean8, data)
+    }
+
+    pub fn new(kind: EanKind, data: &str) -> Result<Self, BarcodeError> {
+        let mut digits = data
+            .chars()
+            .map(|c| c.to_digit(10).map(|d| d as u8).ok_or(BarcodeError::InvalidCharacter(c)))
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let len = kind.digits();
+        if digits.len() == len - 1 {
+            digits.push(check_digit(&digits));
+        } else if digits.len() == len {
+            let expected = check_digit(&digits[..len - 1]);
+            if digits[len - 1] != expected {
+                return Err(BarcodeError::InvalidCheckDigit { expected, found: digits[len - 1] });
+            }
+        } else {
+            return Err(BarcodeError::InvalidLength { expected: len, found: digits.len() });
+        }
+
+        Ok(Self { kind, digits })
+    }
+
+    pub fn kind(&self) -> EanKind {
+        self.kind
+    }
+
+    /// All digits including the check digit
+    pub fn digits(&self) -> &[u8] {
+        &self.digits
+    }
+
+    /// Digits as text
+    pub fn text(&self) -> String {
+        self.digits.iter().map(|d| char::from(b'0' + d)).collect()
+    }
+
+    /// Bars (true) and spaces, one entry per module, without quiet zones
+    pub fn modules(&self) -> Vec<bool> {
+        // UPC-A is EAN-13 with a leading zero
+        let (parity, left, right) = match self.kind {
+            EanKind::Ean13 => (PARITY[self.digits[0] as usize], &self.digits[1..7], &self.digits[7..]),
+            EanKind::UpcA => (0, &self.digits[..6], &self.digits[6..]),
+            EanKind::Ean8 => (0, &self.digits[..4], &self.digits[4..]),
+        };
+
+        let mut modules = vec![true, false, true];
+        for (i, &d) in left.iter().enumerate() {
+            let even = parity & (1 << (left.len() - 1 - i)) != 0;
+            let code = match even {
+                true => (!L_CODES[d as usize] & 0x7f).reverse_bits() >> 1,
+                false => L_CODES[d as usize],
+            };
+            push_code(&mut modules, code);
+        }
+        modules.extend([false, true, false, true, false]);
+        for &d in right {
+            push_code(&mut modules, !L_CODES[d as usize] & 0x7f);
+        }
+        modules.extend([true, false, true]);
+        modules
+    }
+
+    /// Modules drawn extended below the symbol: the guards, and for UPC-A the first and last digit
+    pub fn guards(&self) -> Vec<bool> {
+        let modules = self.modules().len();
+        let half = modules / 2;
+        let upc = self.kind == EanKind::UpcA;
+        (0..modules)
+            .map(|i| match upc {
+                true => i < 10 || i >= modules - 10 || (half - 2..=half + 2).contains(&i),
+                false => i < 3 || i >= modules - 3 || (half - 2..=half + 2).contains(&i),
+            })
+            .collect()
+    }
+}
+
+fn push_code(modules: &mut Vec<bool>, code: u8) {
+    modules.extend((0..7).rev().map(|bit| code & (1 << bit) != 0));
+}
+
+/// Modulo 10 check digit, weights 3 and 1 starting from the rightmost digit
+pub fn check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| d as u32 * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// An EAN / UPC symbol with extended guard bars and the human readable digits underneath
+#[derive(Debug, Clone)]
+pub struct StyledEan {
+    pub ean: Ean,
+    /// Top left corner of the left quiet zone
+    pub position: Point,
+    /// X-dimension (module width) in dots, see `barcode::x_dimension`
+    pub module_width: u32,
+    /// Height of the normal bars in dots
+    pub height: u32,
+    pub font: FontKind,
+    /// Draw the human readable digits
+    pub show_text: bool,
+}
+
+impl StyledEan {
+    pub fn with_module_width(mut self, module_width: u32) -> Self {
+        self.module_width = module_width.max(1);
+        self
+    }
+
+    pub fn with_height(mut self, height: u32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_font(mut self, font: FontKind) -> Self {
+        self.font = font;
+        self
+    }
+
+    pub fn with_text(mut self, show_text: bool) -> Self {
+        self.show_text = show_text;
+        self
+    }
+
+    fn text_options(&self) -> TextOptions {
+        TextOptions { font: self.font.clone(), h_align: HAlign::Centre, v_align: VAlign::Top }
+    }
+
+    /// Guard bars extend 5 modules below the normal bars
+    fn guard_extension(&self) -> u32 {
+        5 * self.module_width
+    }
+
+    /// Left edge of the bars
+    fn bars_left(&self) -> i32 {
+        self.position.x + (self.ean.kind.quiet_zones().0 * self.module_width) as i32
+    }
+
+    fn text_top(&self) -> i32 {
+        self.position.y + (self.height + self.module_width) as i32
+    }
+
+    /// Digit groups as (text, first module, width in modules), positions are relative to the bars
+    fn text_groups(&self) -> Vec<(String, i32, u32)> {
+        let text = self.ean.text();
+        let (quiet_left, quiet_right) = self.ean.kind.quiet_zones();
+        let modules = self.ean.modules().len() as i32;
+        let outside_left = (-(quiet_left as i32), quiet_left);
+        let outside_right = (modules, quiet_right);
+
+        let group = |range: std::ops::Range<usize>, (start, width): (i32, u32)| (text[range].to_string(), start, width);
+        match self.ean.kind {
+            EanKind::Ean13 => vec![
+                group(0..1, outside_left),
+                group(1..7, (3, 42)),
+                group(7..13, (50, 42)),
+            ],
+            EanKind::UpcA => vec![
+                group(0..1, outside_left),
+                group(1..6, (10, 35)),
+                group(6..11, (50, 35)),
+                group(11..12, outside_right),
+            ],
+            EanKind::Ean8 => vec![group(0..4, (3, 28)), group(4..8, (36, 28))],
+        }
+    }
+
+    fn bars(&self) -> StyledBarcode {
+        let mut barcode = StyledBarcode::new(self.ean.modules())
+            .with_module_width(self.module_width)
+            .with_height(self.height)
+            .with_quiet_zone(0);
+        barcode.guards = self.ean.guards();
+        barcode.guard_extension = self.guard_extension();
+        barcode.position = Point::new(self.bars_left(), self.position.y);
+        barcode
+    }
+}
+
+impl From<Ean> for StyledEan {
+    fn from(ean: Ean) -> Self {
+        Self {
+            ean,
+            position: Point::zero(),
+            module_width: 4,
+            height: 240,
+            font: FontKind::Font24x32,
+            show_text: true,
+        }
+    }
+}
+
+impl Drawable for StyledEan {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        self.bars().draw(target)?;
+
+        if self.show_text {
+            let options = self.text_options();
+            let height = options.line_height();
+            for (text, start, width) in self.text_groups() {
+                let left = self.bars_left() + start * self.module_width as i32;
+                let area = Rectangle::new(Point::new(left, self.text_top()), Size::new(width * self.module_width, height));
+                StyledText::new(&text, &options, area).draw(target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Dimensions for StyledEan {
+    fn bounding_box(&self) -> Rectangle {
+        let (left, right) = self.ean.kind.quiet_zones();
+        let width = (left + self.ean.modules().len() as u32 + right) * self.module_width;
+        let bottom = match self.show_text {
+            true => (self.text_top() - self.position.y) as u32 + self.text_options().line_height(),
+            false => self.height,
+        };
+        Rectangle::new(self.position, Size::new(width, bottom.max(self.height + self.guard_extension())))
+    }
+}
+
+impl View for StyledEan {
+    fn translate_impl(&mut self, by: Point) {
+        self.position += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn modules_str(ean: &Ean) -> String {
+        ean.modules().iter().map(|&m| if m { '1' } else { '0' }).collect()
+    }
+
+    #[test]
+    fn check_digits() {
+        assert_eq!(Ean::ean13("400638133393").unwrap().text(), "4006381333931");
+        assert_eq!(Ean::upca("03600029145").unwrap().text(), "036000291452");
+        assert_eq!(Ean::ean8("9638507").unwrap().text(), "96385074");
+
+        assert!(Ean::ean13("4006381333931").is_ok());
+        assert_eq!(
+            Ean::ean13("4006381333932"),
+            Err(BarcodeError::InvalidCheckDigit { expected: 1, found: 2 })
+        );
+        assert_eq!(Ean::ean8("123"), Err(BarcodeError::InvalidLength { expected: 8, found: 3 }));
+        assert_eq!(Ean::ean8("123456a"), Err(BarcodeError::InvalidCharacter('a')));
+    }
+
+    #[test]
+    fn ean13_modules() {
+        let ean = Ean::ean13("4006381333931").unwrap();
+        let m = modules_str(&ean);
+        assert_eq!(m.len(), 95);
+        // First digit 4 selects parity LGLLGG, so the second digit 0 is in set A and the third in set B
+        assert_eq!(&m[..3], "101");
+        assert_eq!(&m[3..10], "0001101");
+        assert_eq!(&m[10..17], "0100111");
+        assert_eq!(&m[45..50], "01010");
+        // Right half uses set C: 3
+        assert_eq!(&m[50..57], "1000010");
+        assert_eq!(&m[92..], "101");
+    }
+
+    #[test]
+    fn upca_is_ean13_with_leading_zero() {
+        let upc = Ean::upca("036000291452").unwrap();
+        let ean = Ean::ean13("0036000291452").unwrap();
+        assert_eq!(upc.modules(), ean.modules());
+
+        // UPC-A also extends the first and last digit
+        let guards = upc.guards();
+        assert!(guards[9] && !guards[10] && guards[85]);
+        let guards = ean.guards();
+        assert!(guards[2] && !guards[3] && guards[47] && !guards[50]);
+    }
+
+    #[test]
+    fn ean8_modules() {
+        let ean = Ean::ean8("96385074").unwrap();
+        assert_eq!(ean.modules().len(), 67);
+    }
+
+    #[test]
+    fn layout() {
+        let styled = StyledEan::from(Ean::ean13("4006381333931").unwrap())
+            .with_module_width(3)
+            .with_height(100)
+            .with_font(FontKind::Font6x12);
+        // 11 + 95 + 7 modules, text below the bars
+        assert_eq!(styled.bounding_box().size, Size::new(113 * 3, 100 + 3 + 12));
+        let groups = styled.text_groups();
+        assert_eq!(groups[0], ("4".to_string(), -11, 11));
+        assert_eq!(groups[2].0, "333931");
+    }
+}
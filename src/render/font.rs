@@ -0,0 +1,241 @@
+use std::{fs, path::Path};
+
+use ab_glyph::{point, Font as _, FontArc, Glyph, PxScale, ScaleFont};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Size},
+    primitives::Rectangle,
+    Drawable, Pixel,
+};
+use embedded_layout::View;
+
+use crate::PTouchError;
+
+/// TrueType / OpenType font, cheap to clone
+#[derive(Clone)]
+pub struct Font {
+    inner: FontArc,
+}
+
+impl Font {
+    /// Load a `.ttf` or `.otf` file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PTouchError> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Load a font from memory, e.g. `include_bytes!`
+    pub fn from_bytes(data: impl Into<Vec<u8>>) -> Result<Self, PTouchError> {
+        let inner = FontArc::try_from_vec(data.into()).map_err(|_| PTouchError::InvalidFont)?;
+        Ok(Self { inner })
+    }
+
+    /// Scale for a size in points (1/72 inch) at the given resolution
+    fn px_scale(&self, size: f32, dpi: u32) -> PxScale {
+        let px_per_em = pt_to_dots(size, dpi);
+        let units_per_em = self.inner.units_per_em().unwrap_or(1000.0);
+        PxScale::from(px_per_em * self.inner.height_unscaled() / units_per_em)
+    }
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font").field("glyphs", &self.inner.glyph_count()).finish()
+    }
+}
+
+/// Convert a size in points to printer dots
+pub fn pt_to_dots(size: f32, dpi: u32) -> f32 {
+    size * dpi as f32 / 72.0
+}
+
+/// Style for `TtfText`
+#[derive(Debug, Clone)]
+pub struct TtfTextStyle {
+    pub font: Font,
+    /// Font size in points
+    pub size: f32,
+    /// Printer resolution used to convert points to dots
+    pub dpi: u32,
+    /// Minimum glyph coverage (0-255) for a dot to be printed
+    pub threshold: u8,
+    /// Apply the font's kerning table
+    pub kerning: bool,
+}
+
+impl TtfTextStyle {
+    pub fn new(font: Font, size: f32) -> Self {
+        Self { font, size, dpi: 300, threshold: 128, kerning: true }
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.kerning = kerning;
+        self
+    }
+
+    /// Line height (ascent - descent) in dots
+    pub fn line_height(&self) -> u32 {
+        let font = self.font.inner.as_scaled(self.font.px_scale(self.size, self.dpi));
+        font.height().ceil() as u32
+    }
+
+    /// Glyphs positioned relative to the top left corner of the line
+    fn layout(&self, text: &str) -> (Vec<Glyph>, f32) {
+        let font = self.font.inner.as_scaled(self.font.px_scale(self.size, self.dpi));
+        let mut glyphs = Vec::with_capacity(text.len());
+        let mut caret = 0.0;
+        let mut previous = None;
+
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous.filter(|_| self.kerning) {
+                caret += font.kern(previous, id);
+            }
+            glyphs.push(id.with_scale_and_position(font.scale(), point(caret, font.ascent())));
+            caret += font.h_advance(id);
+            previous = Some(id);
+        }
+
+        (glyphs, caret)
+    }
+
+    /// Size of the line box (advance width x line height) in dots
+    pub fn measure(&self, text: &str) -> Size {
+        let (_, width) = self.layout(text);
+        Size::new(width.ceil() as u32, self.line_height())
+    }
+}
+
+/// A single line of text in a TrueType / OpenType font.
+/// `position` is the top left corner of the line box, only the glyphs are drawn.
+pub struct TtfText<'a> {
+    pub text: &'a str,
+    pub style: &'a TtfTextStyle,
+    pub position: Point,
+}
+
+impl<'a> TtfText<'a> {
+    pub fn new(text: &'a str, style: &'a TtfTextStyle, position: Point) -> Self {
+        Self { text, style, position }
+    }
+}
+
+impl<'a> Drawable for TtfText<'a> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let clip = target.bounding_box();
+        let threshold = self.style.threshold.max(1) as f32 / 255.0;
+        let (glyphs, _) = self.style.layout(self.text);
+
+        let mut pixels = Vec::new();
+        for glyph in glyphs {
+            let Some(outlined) = self.style.font.inner.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            let origin = self.position + Point::new(bounds.min.x as i32, bounds.min.y as i32);
+
+            outlined.draw(|x, y, coverage| {
+                let p = origin + Point::new(x as i32, y as i32);
+                // The display grows along x, so only clip negative coordinates there
+                if coverage >= threshold && p.x >= 0 && p.y >= 0 && p.y < clip.size.height as i32 {
+                    pixels.push(Pixel(p, BinaryColor::On));
+                }
+            });
+        }
+
+        target.draw_iter(pixels)
+    }
+}
+
+impl<'a> Dimensions for TtfText<'a> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(self.position, self.style.measure(self.text))
+    }
+}
+
+impl<'a> View for TtfText<'a> {
+    fn translate_impl(&mut self, by: Point) {
+        self.position += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::render::display::Display;
+
+    /// ASCII subset of DejaVu Sans, see tests/fonts/LICENSE
+    pub(crate) fn test_font() -> Font {
+        Font::from_bytes(include_bytes!("../../tests/fonts/QLTestSans.ttf").as_slice()).unwrap()
+    }
+
+    #[test]
+    fn points_to_dots() {
+        assert_eq!(pt_to_dots(72.0, 300), 300.0);
+        assert_eq!(pt_to_dots(12.0, 300), 50.0);
+        assert_eq!(pt_to_dots(12.0, 600), 100.0);
+    }
+
+    #[test]
+    fn invalid_font() {
+        assert!(matches!(Font::from_bytes(vec![0u8; 16]), Err(PTouchError::InvalidFont)));
+    }
+
+    #[test]
+    fn size_scales_with_dpi() {
+        let style = TtfTextStyle::new(test_font(), 12.0);
+        let low = style.measure("Label");
+        let high = style.clone().with_dpi(600).measure("Label");
+
+        // DejaVu Sans has a line height of ~1.16 em, 12pt at 300dpi is 50 dots per em
+        assert!((57..=60).contains(&low.height), "{low:?}");
+        assert!(high.width.abs_diff(low.width * 2) <= 2, "{low:?} {high:?}");
+    }
+
+    #[test]
+    fn kerning() {
+        let style = TtfTextStyle::new(test_font(), 40.0);
+        let kerned = style.measure("AV").width;
+        let plain = style.clone().with_kerning(false).measure("AV").width;
+        assert!(kerned < plain, "{kerned} >= {plain}");
+    }
+
+    #[test]
+    fn draw_clips_to_display() {
+        let style = TtfTextStyle::new(test_font(), 20.0);
+        let mut display = Display::new(40, 8);
+
+        TtfText::new("Hg", &style, Point::new(-5, 10)).draw(&mut display).unwrap();
+
+        let on = (0..display.size().width as usize)
+            .flat_map(|x| (0..40).map(move |y| (x, y)))
+            .filter(|&(x, y)| display.get(x, y).unwrap())
+            .count();
+        assert!(on > 50);
+    }
+}
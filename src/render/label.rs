@@ -0,0 +1,912 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::{Circle, Rectangle};
+use serde::Deserialize;
+
+use crate::commands::PrintOptions;
+use crate::render::aztec::{Aztec, AztecOptions, StyledAztec};
+use crate::render::barcode::{self, StyledBarcode};
+use crate::render::code128::Code128;
+use crate::render::data_matrix::{DataMatrix, StyledDataMatrix, SymbolShape};
+use crate::render::ean::{Ean, EanKind, StyledEan};
+use crate::render::font::{Font, TtfTextStyle};
+use crate::render::layout::{Direction, Node, Padding, Stack};
+use crate::render::ops::{
+    Dither, FontKind, HAlign, ImageOptions, Length, QrOptions, StrokeOptions, TableOptions, TextOptions, VAlign,
+};
+use crate::render::shapes::Shape;
+use crate::render::template::{self, Record, TemplateError};
+#[cfg(feature = "svg")]
+use crate::render::svg::Svg;
+use crate::render::{Orientation, QrCodeEcc, Render, RenderConfig};
+use crate::PTouchError;
+
+/// Label description, elements drawn onto the label at fixed positions or arranged by stacks.
+/// Lengths are dots, or millimetres as strings like `"2.5mm"`. The CLI and server read JSON, TOML or YAML, see `Format`.
+/// Text and barcode content can hold `{{field}}` placeholders filled by `merge`, see `template::fill`.
+///
+/// ```json
+/// { "media": "62x29", "elements": [
+///     { "type": "qrcode", "data": "https://lib.rs", "x": 1, "y": 0, "height": "25mm" },
+///     { "type": "stack", "direction": "vertical", "x": "27mm", "y": "2mm", "children": [
+///         { "type": "text", "text": "Hello Rust!", "font": "24x32" },
+///         { "type": "code128", "data": "PN-0001", "height": 80 }
+///     ] }
+/// ] }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelDescription {
+    /// Media name for `PrintOptions::with_media`, e.g. `"62"` or `"62x29"`
+    #[serde(default)]
+    pub media: Option<String>,
+    #[serde(default)]
+    pub orientation: Option<Orientation>,
+    /// Shortest label length, continuous tape is cut there even if the elements end earlier
+    #[serde(default)]
+    pub length: Option<Length>,
+    /// Serial numbers available as fields, by name
+    #[serde(default)]
+    pub counters: BTreeMap<String, Counter>,
+    pub elements: Vec<Element>,
+}
+
+/// A field counting up (or down) from `start` by `step` per page
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Counter {
+    #[serde(default = "one")]
+    pub start: i64,
+    #[serde(default = "one")]
+    pub step: i64,
+}
+
+/// An element and its box. Inside a stack `x` and `y` are ignored and `weight` shares out the space left over.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Element {
+    #[serde(flatten)]
+    pub kind: ElementKind,
+    #[serde(default)]
+    pub x: Length,
+    #[serde(default)]
+    pub y: Length,
+    /// Size of the box, each element kind says what it uses when they are missing
+    #[serde(default)]
+    pub width: Option<Length>,
+    #[serde(default)]
+    pub height: Option<Length>,
+    #[serde(default)]
+    pub weight: u32,
+    /// Only draw the element if this holds for the record, see `template::condition`
+    #[serde(default, rename = "if")]
+    pub condition: Option<String>,
+}
+
+/// Unknown fields are rejected here, the box fields of `Element` are taken out before the kind sees them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ElementKind {
+    /// A line of text aligned in the box, which is the size of the text by default
+    Text {
+        text: String,
+        #[serde(default)]
+        font: FontSpec,
+        #[serde(default = "left")]
+        h_align: HAlign,
+        #[serde(default = "top")]
+        v_align: VAlign,
+    },
+    /// QR code with the largest module size that fits the box, or `module_size` without one
+    QrCode {
+        data: String,
+        #[serde(default)]
+        ecc: Ecc,
+        #[serde(default)]
+        module_size: Option<Length>,
+        /// Quiet zone in modules
+        #[serde(default)]
+        quiet_zone: Option<u32>,
+        #[serde(default)]
+        micro: bool,
+    },
+    /// Code 128, GS1-128 if `data` starts with an application identifier like `(01)`. The height is the bar height.
+    Code128 {
+        data: String,
+        #[serde(default)]
+        module_width: Option<Length>,
+    },
+    /// EAN-13 with 0.33mm modules by default, the height is the bar height
+    Ean13 {
+        data: String,
+        #[serde(default)]
+        module_width: Option<Length>,
+    },
+    UpcA {
+        data: String,
+        #[serde(default)]
+        module_width: Option<Length>,
+    },
+    Ean8 {
+        data: String,
+        #[serde(default)]
+        module_width: Option<Length>,
+    },
+    /// Data Matrix with 0.33mm modules by default, GS1 if `data` starts with an application identifier
+    DataMatrix {
+        data: String,
+        #[serde(default)]
+        module_size: Option<Length>,
+    },
+    Aztec {
+        data: String,
+        #[serde(default)]
+        module_size: Option<Length>,
+    },
+    /// Image file scaled into the box, one dot per pixel by default. The aspect ratio is kept when only one
+    /// side is given.
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        dither: Dither,
+        #[serde(default)]
+        invert: bool,
+        #[serde(default = "threshold")]
+        threshold: u8,
+    },
+    /// SVG artwork scaled into the box like an image, its own physical size by default
+    #[cfg(feature = "svg")]
+    Svg { path: PathBuf },
+    /// Straight line from the top left to the bottom right corner of the box
+    Line {
+        #[serde(default)]
+        stroke: StrokeOptions,
+    },
+    /// Rectangle on the edges of the box
+    Rect {
+        #[serde(default)]
+        radius: Length,
+        #[serde(default)]
+        stroke: StrokeOptions,
+    },
+    /// Circle with the width (or height) of the box as diameter
+    Circle {
+        #[serde(default)]
+        stroke: StrokeOptions,
+    },
+    /// Separator across the whole tape width at `x`
+    Rule {
+        #[serde(default)]
+        stroke: StrokeOptions,
+    },
+    /// Text cells in rows and columns, see `TableOptions`
+    Table {
+        rows: Vec<Vec<String>>,
+        #[serde(default)]
+        font: FontSpec,
+        #[serde(default)]
+        padding: Option<Length>,
+        /// Grid lines, `null` for none
+        #[serde(default = "grid")]
+        grid: Option<StrokeOptions>,
+        #[serde(default)]
+        column_widths: Vec<Length>,
+        #[serde(default)]
+        row_height: Option<Length>,
+    },
+    /// Elements next to or below each other, see `layout::Stack`. The box fits the children by default.
+    Stack {
+        #[serde(default = "horizontal")]
+        direction: Direction,
+        #[serde(default)]
+        spacing: Length,
+        #[serde(default)]
+        padding: Length,
+        #[serde(default = "left")]
+        h_align: HAlign,
+        #[serde(default = "centre")]
+        v_align: VAlign,
+        children: Vec<Element>,
+    },
+}
+
+fn left() -> HAlign {
+    HAlign::Left
+}
+
+fn top() -> VAlign {
+    VAlign::Top
+}
+
+fn centre() -> VAlign {
+    VAlign::Centre
+}
+
+fn horizontal() -> Direction {
+    Direction::Horizontal
+}
+
+fn one() -> i64 {
+    1
+}
+
+fn threshold() -> u8 {
+    128
+}
+
+fn grid() -> Option<StrokeOptions> {
+    Some(StrokeOptions::default())
+}
+
+/// A built-in font by size (`"6x6"`, `"6x8"`, `"6x12"`, `"8x16"`, `"12x16"`, `"24x32"`) or a font file and its
+/// size in points
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum FontSpec {
+    Bitmap(String),
+    TrueType { file: PathBuf, size: f32 },
+}
+
+impl Default for FontSpec {
+    fn default() -> Self {
+        FontSpec::Bitmap("12x16".into())
+    }
+}
+
+impl FontSpec {
+    fn bitmap(name: &str) -> Option<FontKind> {
+        match name {
+            "6x6" => Some(FontKind::Font6x6),
+            "6x8" => Some(FontKind::Font6x8),
+            "6x12" => Some(FontKind::Font6x12),
+            "8x16" => Some(FontKind::Font8x16),
+            "12x16" => Some(FontKind::Font12x16),
+            "24x32" => Some(FontKind::Font24x32),
+            _ => None,
+        }
+    }
+
+    fn load(&self) -> Result<FontKind, PTouchError> {
+        match self {
+            FontSpec::Bitmap(name) => FontSpec::bitmap(name).ok_or(PTouchError::InvalidFont),
+            FontSpec::TrueType { file, size } => Ok(FontKind::TrueType(TtfTextStyle::new(Font::from_file(file)?, *size))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecc {
+    Low,
+    #[default]
+    Medium,
+    Quartile,
+    High,
+}
+
+impl From<Ecc> for QrCodeEcc {
+    fn from(ecc: Ecc) -> Self {
+        match ecc {
+            Ecc::Low => QrCodeEcc::Low,
+            Ecc::Medium => QrCodeEcc::Medium,
+            Ecc::Quartile => QrCodeEcc::Quartile,
+            Ecc::High => QrCodeEcc::High,
+        }
+    }
+}
+
+/// Text formats of label descriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// By file extension, `.toml`, `.yaml` or `.yml`, JSON otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("toml") => Format::Toml,
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+
+    /// By media type, e.g. `application/toml` or `application/yaml; charset=utf-8`, JSON otherwise
+    pub fn from_content_type(content_type: &str) -> Self {
+        let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/toml" | "text/toml" => Format::Toml,
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// A problem with a label description, `path` names the element, e.g. `elements[2].children[0] (qrcode)`
+#[derive(Debug)]
+pub enum LabelError {
+    /// The text isn't a description in its format, e.g. a misspelt field
+    Syntax(String),
+    /// The description is incomplete or inconsistent
+    Invalid { path: String, reason: String },
+    /// Drawing the element failed
+    Render { path: String, error: Box<PTouchError> },
+}
+
+impl std::fmt::Display for LabelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LabelError::Syntax(reason) => write!(f, "Invalid label description: {reason}"),
+            LabelError::Invalid { path, reason } => write!(f, "{path}: {reason}"),
+            LabelError::Render { path, error } => write!(f, "{path}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LabelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LabelError::Syntax(_) | LabelError::Invalid { .. } => None,
+            LabelError::Render { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
+
+impl LabelError {
+    /// The error with `segment` in front of its path
+    fn within(self, segment: &str) -> Self {
+        match self {
+            LabelError::Syntax(reason) => LabelError::Syntax(reason),
+            LabelError::Invalid { path, reason } => LabelError::Invalid { path: format!("{segment}.{path}"), reason },
+            LabelError::Render { path, error } => LabelError::Render { path: format!("{segment}.{path}"), error },
+        }
+    }
+}
+
+/// Attach the element at `segment` to an error from drawing it or one of its children
+fn at(error: PTouchError, segment: String, kind: &str) -> PTouchError {
+    PTouchError::LabelError(match error {
+        PTouchError::LabelError(error) => error.within(&segment),
+        error => LabelError::Render { path: format!("{segment} ({kind})"), error: Box::new(error) },
+    })
+}
+
+impl LabelDescription {
+    /// Read and validate a description
+    pub fn parse(text: &str, format: Format) -> Result<Self, LabelError> {
+        let label: LabelDescription = match format {
+            Format::Json => serde_json::from_str(text).map_err(|e| LabelError::Syntax(e.to_string()))?,
+            Format::Toml => toml::from_str(text).map_err(|e| LabelError::Syntax(e.to_string()))?,
+            Format::Yaml => serde_yaml::from_str(text).map_err(|e| LabelError::Syntax(e.to_string()))?,
+        };
+        label.validate()?;
+        Ok(label)
+    }
+
+    /// Check the description without drawing it
+    pub fn validate(&self) -> Result<(), LabelError> {
+        if let Some(media) = &self.media {
+            if PrintOptions::default().with_media(media).is_none() {
+                return Err(LabelError::Invalid { path: "media".into(), reason: format!("Unknown media {media:?}") });
+            }
+        }
+        for (i, element) in self.elements.iter().enumerate() {
+            element.validate(&format!("elements[{i}]"))?;
+        }
+        Ok(())
+    }
+
+    /// `options` with the label's media, if it names one
+    pub fn print_options(&self, options: PrintOptions) -> Result<PrintOptions, LabelError> {
+        match &self.media {
+            Some(media) => options.with_media(media).ok_or_else(|| LabelError::Invalid {
+                path: "media".into(),
+                reason: format!("Unknown media {media:?}"),
+            }),
+            None => Ok(options),
+        }
+    }
+
+    /// `cfg` with the label's media width, orientation and length
+    pub fn render_config(&self, cfg: RenderConfig) -> Result<RenderConfig, LabelError> {
+        let mut cfg = cfg;
+        if self.media.is_some() {
+            let options = self.print_options(PrintOptions::default())?;
+            cfg.y = options.printable_dots().unwrap_or(cfg.y);
+        }
+        if let Some(orientation) = self.orientation {
+            cfg.orientation = orientation;
+        }
+        if let Some(length) = self.length {
+            cfg.min_x = cfg.min_x.max(length.dots(cfg.dpi) as usize);
+        }
+        Ok(cfg)
+    }
+
+    /// Draw all elements onto a new label, errors name the offending element
+    pub fn render(&self, cfg: RenderConfig) -> Result<Render, PTouchError> {
+        self.validate()?;
+        let mut render = Render::new(cfg);
+        for (i, element) in self.elements.iter().enumerate() {
+            let top_left = Point::new(element.x.dots(cfg.dpi) as i32, element.y.dots(cfg.dpi) as i32);
+            element.draw(&mut render, top_left).map_err(|e| at(e, format!("elements[{i}]"), element.kind.name()))?;
+        }
+        Ok(render)
+    }
+
+    /// The label for `record` as page `index` of a job: placeholders filled in, counters at their value for the
+    /// page and elements whose condition doesn't hold left out. Fields of the record take precedence over counters.
+    pub fn merge(&self, record: &Record, index: usize) -> Result<LabelDescription, LabelError> {
+        let mut record = record.clone();
+        for (name, counter) in &self.counters {
+            let value = counter.start + index as i64 * counter.step;
+            record.entry(name.clone()).or_insert_with(|| value.to_string());
+        }
+        let elements = Element::merge_all(&self.elements, &record, "elements")?;
+        Ok(LabelDescription { counters: BTreeMap::new(), elements, ..self.clone() })
+    }
+
+    /// One page per record, errors name the record and element, e.g. `records[3].elements[0] (text)`
+    pub fn render_pages(&self, records: &[Record], cfg: RenderConfig) -> Result<Vec<Render>, PTouchError> {
+        self.validate()?;
+        let within = |i: usize| {
+            move |error| match error {
+                PTouchError::LabelError(error) => PTouchError::LabelError(error.within(&format!("records[{i}]"))),
+                error => error,
+            }
+        };
+        let pages = records.iter().enumerate().map(|(i, record)| {
+            let label = self.merge(record, i).map_err(|e| e.within(&format!("records[{i}]")))?;
+            label.render(cfg).map_err(within(i))
+        });
+        pages.collect()
+    }
+}
+
+impl ElementKind {
+    /// Name of the kind as written in the `type` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            ElementKind::Text { .. } => "text",
+            ElementKind::QrCode { .. } => "qrcode",
+            ElementKind::Code128 { .. } => "code128",
+            ElementKind::Ean13 { .. } => "ean13",
+            ElementKind::UpcA { .. } => "upca",
+            ElementKind::Ean8 { .. } => "ean8",
+            ElementKind::DataMatrix { .. } => "datamatrix",
+            ElementKind::Aztec { .. } => "aztec",
+            ElementKind::Image { .. } => "image",
+            #[cfg(feature = "svg")]
+            ElementKind::Svg { .. } => "svg",
+            ElementKind::Line { .. } => "line",
+            ElementKind::Rect { .. } => "rect",
+            ElementKind::Circle { .. } => "circle",
+            ElementKind::Rule { .. } => "rule",
+            ElementKind::Table { .. } => "table",
+            ElementKind::Stack { .. } => "stack",
+        }
+    }
+}
+
+impl Element {
+    /// Filled in copies of the elements that are drawn for `record`
+    fn merge_all(elements: &[Element], record: &Record, path: &str) -> Result<Vec<Element>, LabelError> {
+        let merged = elements.iter().enumerate().map(|(i, element)| element.merge(record, &format!("{path}[{i}]")));
+        merged.filter_map(Result::transpose).collect()
+    }
+
+    fn merge(&self, record: &Record, path: &str) -> Result<Option<Element>, LabelError> {
+        let name = self.kind.name();
+        let invalid = |error: TemplateError| LabelError::Invalid { path: format!("{path} ({name})"), reason: error.to_string() };
+        if let Some(condition) = &self.condition {
+            if !template::condition(condition, record).map_err(invalid)? {
+                return Ok(None);
+            }
+        }
+        let fill = |text: &mut String| template::fill(text, record).map(|filled| *text = filled).map_err(invalid);
+        let mut element = Element { condition: None, ..self.clone() };
+        match &mut element.kind {
+            ElementKind::Text { text: data, .. }
+            | ElementKind::QrCode { data, .. }
+            | ElementKind::Code128 { data, .. }
+            | ElementKind::Ean13 { data, .. }
+            | ElementKind::UpcA { data, .. }
+            | ElementKind::Ean8 { data, .. }
+            | ElementKind::DataMatrix { data, .. }
+            | ElementKind::Aztec { data, .. } => fill(data)?,
+            ElementKind::Table { rows, .. } => rows.iter_mut().flatten().try_for_each(fill)?,
+            ElementKind::Stack { children, .. } => *children = Element::merge_all(children, record, &format!("{path}.children"))?,
+            _ => {}
+        }
+        Ok(Some(element))
+    }
+
+    fn validate(&self, path: &str) -> Result<(), LabelError> {
+        let invalid = |reason: &str| LabelError::Invalid { path: format!("{path} ({})", self.kind.name()), reason: reason.into() };
+        let templates: Vec<&String> = match &self.kind {
+            ElementKind::Text { text: data, .. }
+            | ElementKind::QrCode { data, .. }
+            | ElementKind::Code128 { data, .. }
+            | ElementKind::Ean13 { data, .. }
+            | ElementKind::UpcA { data, .. }
+            | ElementKind::Ean8 { data, .. }
+            | ElementKind::DataMatrix { data, .. }
+            | ElementKind::Aztec { data, .. } => vec![data],
+            ElementKind::Table { rows, .. } => rows.iter().flatten().collect(),
+            _ => Vec::new(),
+        };
+        for text in templates {
+            template::check(text).map_err(|e| invalid(&e.to_string()))?;
+        }
+        if let Some(condition) = &self.condition {
+            template::check_condition(condition).map_err(|e| invalid(&e.to_string()))?;
+        }
+        let font = |font: &FontSpec| match font {
+            FontSpec::Bitmap(name) if FontSpec::bitmap(name).is_none() => Err(invalid(&format!("Unknown font {name:?}"))),
+            _ => Ok(()),
+        };
+        match &self.kind {
+            ElementKind::Text { font: spec, .. } | ElementKind::Table { font: spec, .. } => font(spec),
+            ElementKind::Rect { .. } if self.width.is_none() || self.height.is_none() => {
+                Err(invalid("A rectangle needs a width and a height"))
+            }
+            ElementKind::Circle { .. } if self.width.is_none() && self.height.is_none() => {
+                Err(invalid("A circle needs a width or height as diameter"))
+            }
+            ElementKind::Stack { children, .. } => {
+                for (i, child) in children.iter().enumerate() {
+                    child.validate(&format!("{path}.children[{i}]"))?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Width and height in dots, `natural` where they are missing
+    fn size(&self, natural: Size, dpi: u32) -> Size {
+        Size::new(
+            self.width.map_or(natural.width, |width| width.dots(dpi)),
+            self.height.map_or(natural.height, |height| height.dots(dpi)),
+        )
+    }
+
+    /// Width and height in dots with the aspect ratio of `natural` when only one of them is given
+    fn scaled_size(&self, natural: Size, dpi: u32) -> Size {
+        let scale = |length: u32, from: u32, to: u32| (length as u64 * to as u64 / from.max(1) as u64) as u32;
+        match (self.width.map(|w| w.dots(dpi)), self.height.map(|h| h.dots(dpi))) {
+            (Some(width), None) => Size::new(width, scale(width, natural.width, natural.height)),
+            (None, Some(height)) => Size::new(scale(height, natural.height, natural.width), height),
+            _ => self.size(natural, dpi),
+        }
+    }
+
+    /// Draw the element with its box at `top_left`, returns the box
+    fn draw(&self, render: &mut Render, top_left: Point) -> Result<Rectangle, PTouchError> {
+        let dpi = render.cfg.dpi;
+        let dots = |length: Length| length.dots(dpi);
+        let x_dimension = barcode::x_dimension(0.33, dpi);
+        match &self.kind {
+            ElementKind::Text { text, font, h_align, v_align } => {
+                let options = TextOptions { font: font.load()?, h_align: *h_align, v_align: *v_align };
+                let natural = render.text_options(&options).measure(text);
+                let area = Rectangle::new(top_left, self.size(natural, dpi));
+                render.render_text(text, &options, area)?;
+                Ok(area)
+            }
+            ElementKind::QrCode { data, ecc, module_size, quiet_zone, micro } => {
+                let defaults = QrOptions::default();
+                let fit = match (self.width, self.height) {
+                    (None, None) => None,
+                    (width, height) => Some(self.size(Size::new(0, 0), dpi)).map(|size| match (width, height) {
+                        (Some(_), None) => Size::new(size.width, size.width),
+                        (None, Some(_)) => Size::new(size.height, size.height),
+                        _ => size,
+                    }),
+                };
+                let options = QrOptions {
+                    ecc: (*ecc).into(),
+                    module_size: module_size.map_or(defaults.module_size, dots),
+                    quiet_zone: quiet_zone.unwrap_or(defaults.quiet_zone),
+                    micro: *micro,
+                    fit,
+                    ..defaults
+                };
+                render.render_qrcode(data, &options, top_left)
+            }
+            ElementKind::Code128 { data, module_width } => {
+                let code = match data.starts_with('(') {
+                    true => Code128::gs1(data)?,
+                    false => Code128::encode(data)?,
+                };
+                let mut barcode = StyledBarcode::from(code);
+                if let Some(module_width) = module_width {
+                    barcode = barcode.with_module_width(dots(*module_width));
+                }
+                if let Some(height) = self.height {
+                    barcode = barcode.with_height(dots(height));
+                }
+                barcode.position = top_left;
+                render.render_barcode(&barcode)
+            }
+            ElementKind::Ean13 { data, module_width }
+            | ElementKind::UpcA { data, module_width }
+            | ElementKind::Ean8 { data, module_width } => {
+                let kind = match self.kind {
+                    ElementKind::Ean13 { .. } => EanKind::Ean13,
+                    ElementKind::UpcA { .. } => EanKind::UpcA,
+                    _ => EanKind::Ean8,
+                };
+                let module_width = module_width.map_or(x_dimension, dots);
+                let mut ean = StyledEan::from(Ean::new(kind, data)?)
+                    .with_module_width(module_width)
+                    .with_height(self.height.map_or(module_width * 60, dots));
+                ean.position = top_left;
+                render.render_barcode(&ean)
+            }
+            ElementKind::DataMatrix { data, module_size } => {
+                let code = match data.starts_with('(') {
+                    true => DataMatrix::gs1(data, SymbolShape::Square)?,
+                    false => DataMatrix::encode(data, SymbolShape::Square)?,
+                };
+                let mut symbol = StyledDataMatrix::from(code).with_scale(module_size.map_or(x_dimension, dots));
+                symbol.position = top_left;
+                render.render_barcode(&symbol)
+            }
+            ElementKind::Aztec { data, module_size } => {
+                let code = Aztec::encode(data, &AztecOptions::default())?;
+                let mut symbol = StyledAztec::from(code).with_scale(module_size.map_or(x_dimension, dots));
+                symbol.position = top_left;
+                render.render_barcode(&symbol)
+            }
+            ElementKind::Image { path, dither, invert, threshold } => {
+                let image = image::open(path)?;
+                let area = Rectangle::new(top_left, self.scaled_size(Size::new(image.width(), image.height()), dpi));
+                let options = ImageOptions { dither: *dither, invert: *invert, threshold: *threshold, ..Default::default() };
+                render.render_image(&image, area, &options)?;
+                Ok(area)
+            }
+            #[cfg(feature = "svg")]
+            ElementKind::Svg { path } => {
+                let svg = Svg::from_file(path)?;
+                let area = Rectangle::new(top_left, self.scaled_size(svg.size_at(dpi), dpi));
+                render.render_svg(&svg, area, &ImageOptions { dither: Dither::Threshold, ..Default::default() })?;
+                Ok(area)
+            }
+            ElementKind::Line { stroke } => {
+                let size = self.size(Size::zero(), dpi);
+                render.render_line(top_left, top_left + size, stroke)
+            }
+            ElementKind::Rect { radius, stroke } => {
+                render.render_rect(Rectangle::new(top_left, self.size(Size::zero(), dpi)), *radius, stroke)
+            }
+            ElementKind::Circle { stroke } => {
+                let diameter = self.width.or(self.height).map_or(0, dots);
+                render.render_shape(Shape::Circle(Circle::new(top_left, diameter)), stroke)
+            }
+            ElementKind::Rule { stroke } => render.render_rule(top_left.x, stroke),
+            ElementKind::Table { rows, font, padding, grid, column_widths, row_height } => {
+                let defaults = TableOptions::default();
+                let options = TableOptions {
+                    text: TextOptions { font: font.load()?, ..defaults.text },
+                    padding: padding.unwrap_or(defaults.padding),
+                    grid: grid.clone(),
+                    column_widths: column_widths.clone(),
+                    row_height: *row_height,
+                };
+                render.render_table(rows, &options, top_left)
+            }
+            ElementKind::Stack { direction, spacing, padding, h_align, v_align, children } => {
+                let mut stack = Stack::new(*direction)
+                    .with_spacing(dots(*spacing))
+                    .with_padding(Padding::all(dots(*padding)))
+                    .with_alignment(*h_align, *v_align);
+                for (i, child) in children.iter().enumerate() {
+                    let node = Node::draw(move |render, area| {
+                        child.draw(render, area.top_left).map_err(|e| at(e, format!("children[{i}]"), child.kind.name()))
+                    });
+                    stack = stack.push_weighted(node, child.weight);
+                }
+                let available = Size::new(render.cfg.max_x as u32, render.cfg.y as u32);
+                let area = Rectangle::new(top_left, self.size(render.measure_layout(&stack, available)?, dpi));
+                render.render_layout(&stack, area)?;
+                Ok(area)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(json: &str) -> LabelDescription {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn cfg() -> RenderConfig {
+        RenderConfig { y: 128, ..Default::default() }
+    }
+
+    #[test]
+    fn positioned_elements() {
+        // The original format still works
+        let label = parse(r#"{ "elements": [
+            { "type": "text", "text": "Hello", "x": 1, "y": 61 },
+            { "type": "qrcode", "data": "https://lib.rs", "x": 200, "y": 0 }
+        ] }"#);
+        let render = label.render(cfg()).unwrap();
+        assert!(render.display().get(200 + 16, 16).unwrap());
+
+        let label = parse(r#"{ "media": "29", "orientation": "horizontal", "length": "30mm", "elements": [
+            { "type": "rect", "x": "1mm", "y": 2, "width": 40, "height": "5mm", "radius": 4,
+              "stroke": { "width": 3, "dash": [6, "0.5mm"] } },
+            { "type": "qrcode", "data": "A", "x": 100, "height": 100, "quiet_zone": 0, "ecc": "high" },
+            { "type": "line", "x": 0, "y": 120, "width": 50, "stroke": { "width": 1 } }
+        ] }"#);
+        let rect = &label.elements[0];
+        assert_eq!((rect.x, rect.width, rect.height), (Length::Mm(1.0), Some(Length::Dots(40)), Some(Length::Mm(5.0))));
+        let cfg = label.render_config(cfg()).unwrap();
+        assert_eq!((cfg.y, cfg.min_x), (306, 354));
+        assert!(matches!(cfg.orientation, Orientation::Horizontal));
+        let render = label.render(cfg).unwrap();
+        // Version 1 with 4 dot modules fits 100 dots
+        assert!(render.display().get(100, 0).unwrap() && render.display().get(183, 0).unwrap());
+        assert!(!render.display().get(184, 0).unwrap());
+        assert!(render.display().get(20, 2).unwrap() && render.display().get(25, 120).unwrap());
+    }
+
+    #[test]
+    fn stacks() {
+        let label = parse(r#"{ "elements": [
+            { "type": "stack", "direction": "vertical", "x": 10, "y": 4, "spacing": 2, "children": [
+                { "type": "text", "text": "Title", "font": "6x12" },
+                { "type": "stack", "spacing": 4, "children": [
+                    { "type": "circle", "width": 10, "stroke": { "fill": true } },
+                    { "type": "text", "text": "ok", "font": "6x8" }
+                ] }
+            ] }
+        ] }"#);
+        let render = label.render(cfg()).unwrap();
+        // The title is 12 dots high and the row below starts 2 dots further down
+        assert!(render.display().get(10, 4 + 3).unwrap());
+        assert!(!render.display().get(15, 4 + 12 + 1).unwrap() && render.display().get(15, 4 + 12 + 2).unwrap());
+        // The text is centred on the circle, 4 dots to its right
+        assert!(!render.display().get(24, 22).unwrap() && render.display().get(24, 23).unwrap());
+    }
+
+    #[test]
+    fn merge_records() {
+        let label = parse(r#"{ "counters": { "serial": { "start": 100 } }, "elements": [
+            { "type": "text", "text": "{{name|upper}} #{{serial|pad:5}}" },
+            { "type": "stack", "children": [
+                { "type": "text", "text": "FRAGILE", "if": "fragile" },
+                { "type": "code128", "data": "{{sku}}" }
+            ] }
+        ] }"#);
+        let records = template::read_csv("name,sku,fragile\nBolt,B-1,\nVase,V-2,yes\n").unwrap();
+        let text = |label: &LabelDescription| match &label.elements[0].kind {
+            ElementKind::Text { text, .. } => text.clone(),
+            _ => unreachable!(),
+        };
+        let children = |label: &LabelDescription| match &label.elements[1].kind {
+            ElementKind::Stack { children, .. } => children.iter().map(|child| child.kind.name()).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+
+        let bolt = label.merge(&records[0], 0).unwrap();
+        assert_eq!((text(&bolt), children(&bolt)), ("BOLT #00100".to_string(), vec!["code128"]));
+        let vase = label.merge(&records[1], 1).unwrap();
+        assert_eq!((text(&vase), children(&vase)), ("VASE #00101".to_string(), vec!["text", "code128"]));
+        assert!(matches!(&vase.elements[1].kind, ElementKind::Stack { children, .. }
+            if matches!(&children[1].kind, ElementKind::Code128 { data, .. } if data == "V-2")));
+        assert_eq!(label.render_pages(&records, cfg()).unwrap().len(), 2);
+
+        let missing = Record::from([("name".to_string(), "Nut".to_string())]);
+        let error = label.render_pages(&records[..1].iter().cloned().chain([missing]).collect::<Vec<_>>(), cfg());
+        let error = error.err().unwrap().to_string();
+        assert_eq!(error, r#"Label error: records[1].elements[1].children[1] (code128): Unknown field "sku""#);
+
+        let label = parse(r#"{ "elements": [ { "type": "text", "text": "{{name", "if": "a b" } ] }"#);
+        assert!(label.validate().unwrap_err().to_string().starts_with("elements[0] (text): Invalid template"));
+    }
+
+    #[test]
+    fn errors_name_the_element() {
+        let label = parse(r#"{ "elements": [
+            { "type": "text", "text": "ok" },
+            { "type": "stack", "children": [ { "type": "rect", "width": 4 } ] }
+        ] }"#);
+        let error = label.validate().unwrap_err().to_string();
+        assert_eq!(error, "elements[1].children[0] (rect): A rectangle needs a width and a height");
+
+        let label = parse(r#"{ "elements": [
+            { "type": "stack", "children": [ { "type": "ean13", "data": "123" } ] }
+        ] }"#);
+        let error = label.render(cfg()).err().unwrap();
+        assert!(error.to_string().starts_with("Label error: elements[0].children[0] (ean13): Barcode error"), "{error}");
+
+        let label = parse(r#"{ "media": "63", "elements": [ { "type": "text", "text": "x", "font": "7x7" } ] }"#);
+        assert_eq!(label.validate().unwrap_err().to_string(), r#"media: Unknown media "63""#);
+        let label = LabelDescription { media: None, ..label };
+        assert_eq!(label.validate().unwrap_err().to_string(), r#"elements[0] (text): Unknown font "7x7""#);
+
+        let error = serde_json::from_str::<LabelDescription>(r#"{ "elements": [ { "type": "rect", "x": "5cm" } ] }"#);
+        assert!(error.unwrap_err().to_string().contains("Invalid length \"5cm\""));
+    }
+
+    #[test]
+    fn formats() {
+        let json = r#"{ "media": "62x29", "counters": { "n": { "start": 5 } }, "elements": [
+            { "type": "text", "text": "No. {{n}}", "font": "6x12", "x": "2mm", "y": 4 },
+            { "type": "rect", "x": 0, "y": 0, "width": 40, "height": 20, "stroke": { "width": 2 } }
+        ] }"#;
+        let toml = r#"
+            media = "62x29"
+
+            [counters.n]
+            start = 5
+
+            [[elements]]
+            type = "text"
+            text = "No. {{n}}"
+            font = "6x12"
+            x = "2mm"
+            y = 4
+
+            [[elements]]
+            type = "rect"
+            x = 0
+            y = 0
+            width = 40
+            height = 20
+            stroke = { width = 2 }
+        "#;
+        let yaml = "
+media: \"62x29\"
+counters:
+  n:
+    start: 5
+elements:
+  - type: text
+    text: \"No. {{n}}\"
+    font: 6x12
+    x: 2mm
+    y: 4
+  - type: rect
+    x: 0
+    y: 0
+    width: 40
+    height: 20
+    stroke:
+      width: 2
+";
+        let expected = format!("{:?}", LabelDescription::parse(json, Format::Json).unwrap());
+        assert_eq!(format!("{:?}", LabelDescription::parse(toml, Format::Toml).unwrap()), expected);
+        assert_eq!(format!("{:?}", LabelDescription::parse(yaml, Format::Yaml).unwrap()), expected);
+
+        assert_eq!(Format::from_path(Path::new("shelf.YML")), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("shelf.toml")), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("shelf")), Format::Json);
+        assert_eq!(Format::from_content_type("application/toml; charset=utf-8"), Format::Toml);
+        assert_eq!(Format::from_content_type("text/yaml"), Format::Yaml);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let error = |json| LabelDescription::parse(json, Format::Json).unwrap_err().to_string();
+        assert!(error(r#"{ "meda": "62", "elements": [] }"#).contains("unknown field `meda`"));
+        assert!(error(r#"{ "elements": [ { "type": "text", "text": "x", "colour": "red" } ] }"#)
+            .contains("unknown field `colour`"));
+        assert!(error(r#"{ "elements": [ { "type": "line", "width": 9, "stroke": { "widht": 2 } } ] }"#)
+            .contains("unknown field `widht`"));
+        assert!(error(r#"{ "counters": { "n": { "begin": 1 } }, "elements": [] }"#).contains("unknown field `begin`"));
+
+        // The box fields are shared by all kinds
+        let label = r#"{ "elements": [ { "type": "text", "text": "x", "x": 1, "y": 2, "weight": 1, "if": "x" } ] }"#;
+        assert!(LabelDescription::parse(label, Format::Json).is_ok());
+    }
+}
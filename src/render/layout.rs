@@ -0,0 +1,393 @@
+//! Automatic placement of label content in horizontal and vertical stacks
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::{Point, Size},
+    primitives::Rectangle,
+    Drawable,
+};
+use embedded_layout::View;
+
+use crate::render::bitmap::ImageSource;
+use crate::render::ops::{HAlign, ImageOptions, QrOptions, TextOptions, VAlign};
+use crate::render::Render;
+use crate::PTouchError;
+
+/// Content drawn by a `Render` method into the box it is given, returns the box it covers
+type DrawFn<'a> = Box<dyn Fn(&mut Render, Rectangle) -> Result<Rectangle, PTouchError> + 'a>;
+
+/// Content with a fixed size that can be moved, e.g. a `Bitmap`, `StyledShape`, `Table` or styled barcode
+pub trait Element {
+    fn size(&self) -> Size;
+    fn draw_at(&self, render: &mut Render, top_left: Point) -> Result<Rectangle, PTouchError>;
+}
+
+impl<T> Element for T
+where
+    T: View + Drawable<Color = BinaryColor, Output = ()> + Clone,
+{
+    fn size(&self) -> Size {
+        self.bounds().size
+    }
+
+    fn draw_at(&self, render: &mut Render, top_left: Point) -> Result<Rectangle, PTouchError> {
+        let mut view = self.clone();
+        view.translate_mut(top_left - self.bounds().top_left);
+        view.draw(&mut render.display)?;
+        Ok(view.bounds())
+    }
+}
+
+/// An entry of a `Stack`
+pub enum Node<'a> {
+    Element(Box<dyn Element + 'a>),
+    /// Drawn into the top left of the box it gets, its size is found by drawing it once off the label
+    Draw(DrawFn<'a>),
+    /// Empty space, give it a weight to push its neighbours apart
+    Space(Size),
+    Stack(Stack<'a>),
+}
+
+impl<'a> Node<'a> {
+    pub fn element(element: impl Element + 'a) -> Self {
+        Node::Element(Box::new(element))
+    }
+
+    pub fn draw(draw: impl Fn(&mut Render, Rectangle) -> Result<Rectangle, PTouchError> + 'a) -> Self {
+        Node::Draw(Box::new(draw))
+    }
+
+    /// A line of text, aligned by the stack instead of the options
+    pub fn text(text: impl Into<String>, options: &TextOptions) -> Self {
+        let text = text.into();
+        let options = TextOptions { h_align: HAlign::Left, v_align: VAlign::Top, ..options.clone() };
+        Node::draw(move |render, area| render.render_text(&text, &options, area))
+    }
+
+    pub fn qrcode(data: impl Into<String>, options: &QrOptions) -> Self {
+        let (data, options) = (data.into(), options.clone());
+        Node::draw(move |render, area| render.render_qrcode(&data, &options, area.top_left))
+    }
+
+    pub fn code128(data: impl Into<String>) -> Self {
+        let data = data.into();
+        Node::draw(move |render, area| render.render_code128(&data, area.top_left))
+    }
+
+    /// An image scaled to fit the space the stack has for it, aligned by the stack
+    pub fn image(source: impl Into<ImageSource<'a>>, options: &ImageOptions) -> Self {
+        let source = source.into();
+        let options = ImageOptions { h_align: HAlign::Left, v_align: VAlign::Top, ..options.clone() };
+        Node::draw(move |render, area| render.render_image(source, area, &options))
+    }
+
+    /// Size with `available` dots to spare
+    fn measure(&self, render: &Render, available: Size) -> Result<Size, PTouchError> {
+        match self {
+            Node::Element(element) => Ok(element.size()),
+            Node::Draw(draw) => {
+                let mut scratch = Render::new(render.cfg);
+                Ok(draw(&mut scratch, Rectangle::new(Point::zero(), available))?.size)
+            }
+            Node::Space(size) => Ok(*size),
+            Node::Stack(stack) => stack.measure(render, available),
+        }
+    }
+
+    /// Draw into `cell`, stacks fill it and everything else is aligned in it
+    fn arrange(
+        &self,
+        render: &mut Render,
+        cell: Rectangle,
+        h_align: HAlign,
+        v_align: VAlign,
+    ) -> Result<Option<Rectangle>, PTouchError> {
+        let top_left = |size: Size| {
+            let free = cell.size.saturating_sub(size);
+            let x = match h_align {
+                HAlign::Left => 0,
+                HAlign::Centre => free.width / 2,
+                HAlign::Right => free.width,
+            };
+            let y = match v_align {
+                VAlign::Top => 0,
+                VAlign::Centre => free.height / 2,
+                VAlign::Bottom => free.height,
+            };
+            cell.top_left + Point::new(x as i32, y as i32)
+        };
+        match self {
+            Node::Element(element) => element.draw_at(render, top_left(element.size())).map(Some),
+            Node::Draw(draw) => {
+                let size = self.measure(render, cell.size)?;
+                draw(render, Rectangle::new(top_left(size), size)).map(Some)
+            }
+            Node::Space(_) => Ok(None),
+            Node::Stack(stack) => stack.arrange(render, cell),
+        }
+    }
+}
+
+impl<'a> From<Stack<'a>> for Node<'a> {
+    fn from(stack: Stack<'a>) -> Self {
+        Node::Stack(stack)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+/// Space inside the edges of a `Stack` in dots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Padding {
+    pub fn all(dots: u32) -> Self {
+        Self { top: dots, right: dots, bottom: dots, left: dots }
+    }
+
+    pub fn symmetric(horizontal: u32, vertical: u32) -> Self {
+        Self { top: vertical, right: horizontal, bottom: vertical, left: horizontal }
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.left + self.right, self.top + self.bottom)
+    }
+}
+
+/// Entries placed next to or below each other with `spacing` dots between them.
+///
+/// Entries get their own size along the stack and the full size across it, where they are aligned. Space left
+/// over along the stack goes to weighted entries in proportion to their weight, or aligns the whole row.
+pub struct Stack<'a> {
+    direction: Direction,
+    children: Vec<(Node<'a>, u32)>,
+    spacing: u32,
+    padding: Padding,
+    h_align: HAlign,
+    v_align: VAlign,
+}
+
+impl<'a> Stack<'a> {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            children: Vec::new(),
+            spacing: 0,
+            padding: Padding::default(),
+            h_align: HAlign::Left,
+            v_align: VAlign::Centre,
+        }
+    }
+
+    pub fn horizontal() -> Self {
+        Self::new(Direction::Horizontal)
+    }
+
+    pub fn vertical() -> Self {
+        Self::new(Direction::Vertical)
+    }
+
+    pub fn with_spacing(mut self, dots: u32) -> Self {
+        self.spacing = dots;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_alignment(mut self, h_align: HAlign, v_align: VAlign) -> Self {
+        self.h_align = h_align;
+        self.v_align = v_align;
+        self
+    }
+
+    pub fn push(self, node: impl Into<Node<'a>>) -> Self {
+        self.push_weighted(node, 0)
+    }
+
+    /// Add an entry that grows by `weight` shares of the space left over
+    pub fn push_weighted(mut self, node: impl Into<Node<'a>>, weight: u32) -> Self {
+        self.children.push((node.into(), weight));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Length along the stack and across it
+    fn split(&self, size: Size) -> (u32, u32) {
+        match self.direction {
+            Direction::Horizontal => (size.width, size.height),
+            Direction::Vertical => (size.height, size.width),
+        }
+    }
+
+    fn join(&self, along: u32, across: u32) -> Size {
+        match self.direction {
+            Direction::Horizontal => Size::new(along, across),
+            Direction::Vertical => Size::new(across, along),
+        }
+    }
+
+    fn sizes(&self, render: &Render, inner: Size) -> Result<Vec<Size>, PTouchError> {
+        self.children.iter().map(|(node, _)| node.measure(render, inner)).collect()
+    }
+
+    fn gaps(&self) -> u32 {
+        self.children.len().saturating_sub(1) as u32 * self.spacing
+    }
+
+    /// Smallest size that holds all entries with `available` dots to spare
+    pub(crate) fn measure(&self, render: &Render, available: Size) -> Result<Size, PTouchError> {
+        let sizes = self.sizes(render, available.saturating_sub(self.padding.size()))?;
+        let along = sizes.iter().map(|&size| self.split(size).0).sum::<u32>() + self.gaps();
+        let across = sizes.iter().map(|&size| self.split(size).1).max().unwrap_or(0);
+        Ok(self.join(along, across) + self.padding.size())
+    }
+
+    /// Draw all entries inside `area`, returns the box around what was drawn
+    pub(crate) fn arrange(&self, render: &mut Render, area: Rectangle) -> Result<Option<Rectangle>, PTouchError> {
+        let inner = Rectangle::new(
+            area.top_left + Point::new(self.padding.left as i32, self.padding.top as i32),
+            area.size.saturating_sub(self.padding.size()),
+        );
+        let sizes = self.sizes(render, inner.size)?;
+        let (length, across) = self.split(inner.size);
+        let used = sizes.iter().map(|&size| self.split(size).0).sum::<u32>() + self.gaps();
+        let free = length.saturating_sub(used);
+        let weights = self.children.iter().map(|(_, weight)| weight).sum::<u32>();
+
+        let mut offset = match (weights, self.direction, self.h_align, self.v_align) {
+            (0, Direction::Horizontal, HAlign::Centre, _) | (0, Direction::Vertical, _, VAlign::Centre) => free / 2,
+            (0, Direction::Horizontal, HAlign::Right, _) | (0, Direction::Vertical, _, VAlign::Bottom) => free,
+            _ => 0,
+        };
+        let mut extras: Vec<u32> = match weights {
+            0 => vec![0; self.children.len()],
+            _ => {
+                let share = |weight: u32| (free as u64 * weight as u64 / weights as u64) as u32;
+                self.children.iter().map(|(_, weight)| share(*weight)).collect()
+            },
+        };
+        // The last weighted entry takes what rounding left over
+        if let Some(last) = self.children.iter().rposition(|(_, weight)| *weight > 0) {
+            extras[last] += free - extras.iter().sum::<u32>();
+        }
+
+        let mut drawn = None::<Rectangle>;
+        for (((node, _), size), extra) in self.children.iter().zip(sizes).zip(extras) {
+            let cell_length = self.split(size).0 + extra;
+            let start = match self.direction {
+                Direction::Horizontal => Point::new(offset as i32, 0),
+                Direction::Vertical => Point::new(0, offset as i32),
+            };
+            let cell = Rectangle::new(inner.top_left + start, self.join(cell_length, across));
+            if let Some(rect) = node.arrange(render, cell, self.h_align, self.v_align)? {
+                drawn = Some(match drawn {
+                    Some(drawn) => union(drawn, rect),
+                    None => rect,
+                });
+            }
+            offset += cell_length + self.spacing;
+        }
+        Ok(drawn)
+    }
+}
+
+/// Smallest rectangle covering `a` and `b`
+fn union(a: Rectangle, b: Rectangle) -> Rectangle {
+    let far = |r: Rectangle| r.top_left + r.size;
+    let top_left = a.top_left.component_min(b.top_left);
+    let bottom_right = far(a).component_max(far(b));
+    Rectangle::with_corners(top_left, bottom_right - Point::new(1, 1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::ops::{FontKind, Length, StrokeOptions};
+    use crate::render::shapes::{Shape, StyledShape};
+    use crate::render::RenderConfig;
+
+    fn block(width: u32, height: u32) -> Node<'static> {
+        let rect = Rectangle::new(Point::zero(), Size::new(width, height));
+        let fill = StrokeOptions { width: Length::Dots(0), fill: true, ..Default::default() };
+        Node::element(StyledShape::new(Shape::Rectangle(rect, 0), &fill, 300))
+    }
+
+    fn render() -> Render {
+        Render::new(RenderConfig { y: 64, ..Default::default() })
+    }
+
+    #[test]
+    fn stacks_and_alignment() {
+        let mut render = render();
+        let column = Stack::vertical().with_spacing(1).with_alignment(HAlign::Right, VAlign::Top);
+        let stack = Stack::horizontal()
+            .with_spacing(4)
+            .with_padding(Padding::all(2))
+            .push(block(10, 10))
+            .push(column.push(block(6, 3)).push(block(3, 3)))
+            .push(block(5, 20));
+        let area = Rectangle::new(Point::zero(), Size::new(100, 40));
+        assert_eq!(render.measure_layout(&stack, area.size).unwrap(), Size::new(2 + 10 + 4 + 6 + 4 + 5 + 2, 2 + 20 + 2));
+
+        let drawn = render.render_layout(&stack, area).unwrap();
+        // Blocks are centred across the stack, the inner stack fills its cell and aligns right and top
+        assert_eq!(drawn, Rectangle::new(Point::new(2, 2), Size::new(10 + 4 + 6 + 4 + 5, 28)));
+        let display = render.display();
+        assert!(display.get(2, 15).unwrap() && !display.get(2, 14).unwrap() && display.get(11, 24).unwrap());
+        assert!(display.get(16, 2).unwrap() && display.get(19, 6).unwrap() && !display.get(18, 6).unwrap());
+        assert!(display.get(26, 10).unwrap() && !display.get(26, 9).unwrap());
+
+        let mut render = self::render();
+        let centred = Stack::horizontal().with_alignment(HAlign::Centre, VAlign::Bottom).push(block(10, 10));
+        assert_eq!(render.render_layout(&centred, area).unwrap(), Rectangle::new(Point::new(45, 30), Size::new(10, 10)));
+    }
+
+    #[test]
+    fn weights() {
+        let mut render = render();
+        let stack = Stack::horizontal()
+            .push(block(10, 10))
+            .push_weighted(Node::Space(Size::zero()), 1)
+            .push(block(10, 10))
+            .push_weighted(Node::Space(Size::zero()), 2)
+            .push(block(10, 10));
+        let drawn = render.render_layout(&stack, Rectangle::new(Point::zero(), Size::new(100, 10))).unwrap();
+        assert_eq!(drawn.size, Size::new(100, 10));
+        // 70 dots left over, split one to two
+        assert!(render.display().get(33, 0).unwrap() && !render.display().get(32, 0).unwrap());
+        assert!(render.display().get(90, 0).unwrap() && !render.display().get(89, 0).unwrap());
+    }
+
+    #[test]
+    fn render_content() {
+        let mut render = render();
+        let text = TextOptions { font: FontKind::Font6x12, ..Default::default() };
+        let stack = Stack::horizontal()
+            .with_spacing(8)
+            .push(Node::qrcode("layout", &QrOptions { module_size: 2, quiet_zone: 1, ..Default::default() }))
+            .push(Stack::vertical().push(Node::text("Line one", &text)).push(Node::text("Two", &text)));
+        let size = render.measure_layout(&stack, Size::new(1000, 64)).unwrap();
+        assert_eq!(size, Size::new(46 + 8 + 8 * 6, 46));
+        let drawn = render.render_layout(&stack, Rectangle::new(Point::zero(), size)).unwrap();
+        assert_eq!(drawn, Rectangle::new(Point::zero(), size));
+        assert!(matches!(
+            render.measure_layout(&Stack::vertical().push(Node::code128("\u{1F600}")), size),
+            Err(PTouchError::BarcodeError(_))
+        ));
+    }
+}
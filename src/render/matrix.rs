@@ -0,0 +1,102 @@
+//! Drawing of 2D matrix symbols (Data Matrix, Aztec)
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, PointsIter, Size},
+    primitives::Rectangle,
+    Drawable, Pixel,
+};
+use embedded_layout::View;
+
+/// A 2D symbol made of a grid of dark and light modules
+pub trait MatrixSymbol {
+    /// Size in modules (columns x rows)
+    fn modules(&self) -> Size;
+
+    /// Module at column `x` and row `y`, light outside the symbol
+    fn get_module(&self, x: i32, y: i32) -> bool;
+
+    /// Minimum quiet zone in modules
+    fn quiet_zone(&self) -> u32 {
+        1
+    }
+}
+
+/// A matrix symbol drawn with square modules and a quiet zone
+#[derive(Debug, Clone)]
+pub struct StyledMatrix<S> {
+    pub symbol: S,
+    pub position: Point,
+    /// Module size in dots
+    pub scale: u32,
+    /// Quiet zone on all sides in modules
+    pub quiet_zone: u32,
+    pub is_inverted: bool,
+}
+
+impl<S: MatrixSymbol> StyledMatrix<S> {
+    pub fn inverted(mut self) -> Self {
+        self.is_inverted = !self.is_inverted;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    pub fn with_quiet_zone(mut self, quiet_zone: u32) -> Self {
+        self.quiet_zone = quiet_zone;
+        self
+    }
+
+    /// Get element using local coordinates, the quiet zone included
+    pub fn get_element(&self, point: Point) -> bool {
+        let module = point / self.scale as i32 - Point::new(self.quiet_zone as i32, self.quiet_zone as i32);
+        self.symbol.get_module(module.x, module.y) ^ self.is_inverted
+    }
+}
+
+impl<S: MatrixSymbol> From<S> for StyledMatrix<S> {
+    fn from(symbol: S) -> Self {
+        let quiet_zone = symbol.quiet_zone();
+        Self { symbol, position: Point::zero(), scale: 1, quiet_zone, is_inverted: false }
+    }
+}
+
+impl<S: MatrixSymbol> Drawable for StyledMatrix<S> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let height = target.bounding_box().size.height as i32;
+        let pixels = self
+            .bounding_box()
+            .points()
+            .filter(|p| p.x >= 0 && p.y >= 0 && p.y < height)
+            .map(|p| Pixel(p, self.get_element(p - self.position).into()));
+        target.draw_iter(pixels)
+    }
+}
+
+impl<S: MatrixSymbol> Dimensions for StyledMatrix<S> {
+    fn bounding_box(&self) -> Rectangle {
+        let border = 2 * self.quiet_zone;
+        let modules = self.symbol.modules();
+        let size = Size::new(modules.width + border, modules.height + border) * self.scale;
+        Rectangle::new(self.position, size)
+    }
+}
+
+impl<S: MatrixSymbol> View for StyledMatrix<S> {
+    fn translate_impl(&mut self, by: Point) {
+        self.position += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
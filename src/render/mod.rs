@@ -1,25 +1,63 @@
-use embedded_graphics::mono_font::ascii::FONT_6X10;
-use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
 use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::prelude::*;
-use embedded_graphics::primitives::{Rectangle, StyledDrawable};
-use embedded_graphics::text::{Alignment, Text, TextStyleBuilder};
-use embedded_graphics::text::renderer::{CharacterStyle, TextRenderer};
+use embedded_graphics::primitives::{Circle, Line, Rectangle};
 use embedded_graphics_simulator::{
     BinaryColorTheme, OutputSettingsBuilder, SimulatorDisplay, Window,
 };
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use image::{DynamicImage, GrayImage, ImageFormat, Luma, RgbImage};
 use crate::prelude::display::{Display, DrawPixel};
 use crate::PTouchError;
-use crate::render::qr_code::{Mask, QrCode, QrCodeEcc, Version};
-use crate::render::qr_code_embedded_graphic::StyledQrCode;
+use crate::render::qr_code::{DataTooLong, QrCode};
+use crate::render::qr_code_embedded_graphic::{StyledMicroQrCode, StyledQrCode};
+use crate::render::aztec::{Aztec, AztecOptions, StyledAztec};
+use crate::render::barcode::StyledBarcode;
+use crate::render::bitmap::{Bitmap, ImageSource};
+use crate::render::code128::Code128;
+use crate::render::data_matrix::{DataMatrix, StyledDataMatrix, SymbolShape};
+use crate::render::ean::{Ean, EanKind, StyledEan};
+use crate::render::font::{TtfText, TtfTextStyle};
+use crate::render::layout::Stack;
+use crate::render::qr_payload::QrPayload;
+use crate::render::qr_segments::Segment;
+use crate::render::shapes::{Shape, StyledShape};
+use crate::render::table::Table;
+use crate::render::ops::{
+    FitOptions, FontKind, HAlign, ImageOptions, Length, ParagraphOptions, QrOptions, StrokeOptions, TableOptions, TextOptions,
+    VAlign,
+};
+use crate::render::text::{FittedText, Paragraph, StyledText};
 
+pub mod aztec;
+pub mod barcode;
+pub mod bitmap;
+pub mod code128;
+pub mod data_matrix;
 pub mod display;
+pub mod ean;
+pub mod font;
+pub mod matrix;
+#[cfg(feature = "serde")]
+pub mod label;
+pub mod layout;
 pub mod ops;
-mod text;
+pub mod qr_payload;
+pub mod qr_segments;
+pub mod shapes;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod table;
+pub mod template;
+pub mod text;
 mod qr_code;
 mod qr_code_embedded_graphic;
 
+pub use qr_code::{Mask, MicroQrCode, MicroVersion, QrCodeEcc, Version};
+
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
 pub enum Orientation {
     Horizontal,
     Vertical,
@@ -34,6 +72,8 @@ pub struct RenderConfig {
     pub max_x: usize,
     /// Image Y size
     pub y: usize,
+    /// Printer resolution, used to convert font sizes in points to dots
+    pub dpi: u32,
 }
 
 impl Default for RenderConfig {
@@ -42,6 +82,7 @@ impl Default for RenderConfig {
             min_x: 32,
             max_x: 10 * 1024,
             y: 696,
+            dpi: 300,
             orientation: Orientation::Vertical,
         }
     }
@@ -62,56 +103,429 @@ impl Render {
         Self { cfg, display }
     }
 
-    pub fn render_text(&mut self, text: &str , point: Point) -> Result<(), PTouchError> {
+    /// Draw a line of text aligned inside `area`, returns the box the text was drawn in
+    pub fn render_text(&mut self, text: &str, options: &TextOptions, area: Rectangle) -> Result<Rectangle, PTouchError> {
+        let options = self.text_options(options);
+        let text = StyledText::new(text, &options, area);
+        text.draw(&mut self.display)?;
+        Ok(text.bounding_box())
+    }
+
+    /// Draw text at the largest size that fits `area`, optionally wrapped and shortened with an ellipsis.
+    /// The result holds the chosen font and lines, see `FittedText::bounding_box` for the drawn box.
+    pub fn render_text_fit(
+        &mut self,
+        text: &str,
+        options: &TextOptions,
+        fit: &FitOptions,
+        area: Rectangle,
+    ) -> Result<FittedText, PTouchError> {
+        let fitted = text::fit(text, &self.text_options(options), fit, area);
+        fitted.draw(&mut self.display)?;
+        Ok(fitted)
+    }
 
-        // TODO implement if text fits
-        let character_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
-        let mut x = Text::new(text, point, character_style);
-        println!("{:?}",x.bounding_box().points());
-        let y = x.character_style.font.image;
-        y.draw(&mut self.display)?;
-        println!("{:#?}",y.bounding_box());
+    /// Draw text wrapped at `width` below `top_left`, returns the box around the drawn lines
+    pub fn render_paragraph(
+        &mut self,
+        text: &str,
+        options: &ParagraphOptions,
+        top_left: Point,
+        width: u32,
+    ) -> Result<Rectangle, PTouchError> {
+        let options = ParagraphOptions { text: self.text_options(&options.text), ..options.clone() };
+        let paragraph = Paragraph::new(text, &options, top_left, width);
+        paragraph.draw(&mut self.display)?;
+        Ok(paragraph.bounding_box())
+    }
 
-        Ok(())
+    /// Use the configured resolution for TrueType fonts
+    fn text_options(&self, options: &TextOptions) -> TextOptions {
+        match &options.font {
+            FontKind::TrueType(style) => TextOptions {
+                font: FontKind::TrueType(style.clone().with_dpi(self.cfg.dpi)),
+                ..options.clone()
+            },
+            _ => options.clone(),
+        }
     }
 
-    pub fn render_qrcode(&mut self, link: &str, point: Point) -> Result<(), PTouchError> {
-        let ecc = QrCodeEcc::Medium; // Choose an error correction level
-        let version = Version::new(1); // Choose a version
-        let max_version = Version::new(40); // Define the max version
-        let mask: Option<Mask> = None; // Automatically choose the mask
-        let mut tempbuffer = vec![0u8; max_version.buffer_len()]; // Temporary buffer
-        let mut outbuffer = vec![0u8; max_version.buffer_len()]; // Output buffer
+    /// Draw a line of TrueType / OpenType text with its top left corner at `point`,
+    /// the size is converted to dots using `RenderConfig::dpi`. Returns the line box.
+    pub fn render_ttf_text(&mut self, text: &str, style: &TtfTextStyle, point: Point) -> Result<Rectangle, PTouchError> {
+        let style = style.clone().with_dpi(self.cfg.dpi);
+        let text = TtfText::new(text, &style, point);
+        text.draw(&mut self.display)?;
+        Ok(text.bounding_box())
+    }
+
+    /// Draw a QR code with the top left corner of its quiet zone at `point`, returns the area it covers
+    pub fn render_qrcode(&mut self, data: &str, options: &QrOptions, point: Point) -> Result<Rectangle, PTouchError> {
+        if options.micro {
+            return self.render_micro_qrcode(options, point, || {
+                MicroQrCode::encode_text(data, options.ecc, options.mask, options.boost_ecc)
+            });
+        }
+        if options.min_version > options.max_version {
+            return Err(PTouchError::RenderError);
+        }
+        let mut tempbuffer = vec![0u8; options.max_version.buffer_len()];
+        let mut outbuffer = vec![0u8; options.max_version.buffer_len()];
 
-        // Encode the text into a QR code
         let qrcode = QrCode::encode_text(
-            link,
+            data,
             &mut tempbuffer,
             &mut outbuffer,
-            ecc,
-            version,
-            max_version,
-            mask,
-            true,
-        ).map_err(|_| PTouchError::RenderError)?;
-
-        // // Draw the QR code on the display
-        // let size = qrcode.size();
-        // for y in 0..size {
-        //     for x in 0..size {
-        //         let color = if qrcode.get_module(x, y) {
-        //             BinaryColor::On
-        //         } else {
-        //             BinaryColor::Off
-        //         };
-        //         self.display.set((point.x + x as i32) as usize, (point.y + y as i32) as usize, color.is_on())?;
-        //     }
-        // }
-        let x = StyledQrCode::from(qrcode);
-        let mut x = x.with_scale(4).with_border(100);
-        x.position = point;
-        x.draw(&mut self.display)?;
-        Ok(())
+            options.ecc,
+            options.min_version,
+            options.max_version,
+            options.mask,
+            options.boost_ecc,
+        )
+        .map_err(|_| PTouchError::RenderError)?;
+        self.render_styled_qrcode(qrcode, options, point)
+    }
+
+    /// Draw a QR code built from explicit segments, e.g. an alphanumeric prefix followed by a numeric serial
+    pub fn render_qr_segments(
+        &mut self,
+        segments: &[Segment],
+        options: &QrOptions,
+        point: Point,
+    ) -> Result<Rectangle, PTouchError> {
+        segments.iter().try_for_each(Segment::validate)?;
+        if options.micro {
+            return self.render_micro_qrcode(options, point, || {
+                qr_segments::with_qr_segments(segments, |segs| {
+                    MicroQrCode::encode_segments(segs, options.ecc, options.mask, options.boost_ecc)
+                })
+            });
+        }
+        self.render_qr_codewords(options, point, |outbuffer, options| {
+            qr_segments::encode_codewords(
+                segments,
+                outbuffer,
+                options.ecc,
+                options.min_version,
+                options.max_version,
+                options.boost_ecc,
+            )
+        })
+    }
+
+    /// Draw a QR code of `data` split into the segments giving the smallest version.
+    /// Micro QR codes can't carry an ECI, their byte segments are left untagged.
+    pub fn render_qrcode_optimized(
+        &mut self,
+        data: &str,
+        options: &QrOptions,
+        point: Point,
+    ) -> Result<Rectangle, PTouchError> {
+        if options.micro {
+            let mut segments = qr_segments::optimal_segments(data, Version::MIN);
+            segments.retain(|segment| !matches!(segment, Segment::Eci(_)));
+            return self.render_qr_segments(&segments, options, point);
+        }
+        self.render_qr_codewords(options, point, |outbuffer, options| {
+            qr_segments::encode_optimal(
+                data,
+                outbuffer,
+                options.ecc,
+                options.min_version,
+                options.max_version,
+                options.boost_ecc,
+            )
+        })
+    }
+
+    /// Draw a QR code of a Wi-Fi, contact, location, e-mail or GS1 Digital Link payload
+    pub fn render_qr_payload(
+        &mut self,
+        payload: &impl QrPayload,
+        options: &QrOptions,
+        point: Point,
+    ) -> Result<Rectangle, PTouchError> {
+        self.render_qrcode_optimized(&payload.payload()?, options, point)
+    }
+
+    fn render_qr_codewords<F>(&mut self, options: &QrOptions, point: Point, encode: F) -> Result<Rectangle, PTouchError>
+    where
+        F: FnOnce(&mut [u8], &QrOptions) -> Result<(usize, QrCodeEcc, Version), DataTooLong>,
+    {
+        if options.min_version > options.max_version {
+            return Err(PTouchError::RenderError);
+        }
+        let mut tempbuffer = vec![0u8; options.max_version.buffer_len()];
+        let mut outbuffer = vec![0u8; options.max_version.buffer_len()];
+
+        let (len, ecc, version) = encode(&mut outbuffer, options).map_err(|_| PTouchError::RenderError)?;
+        let qrcode = QrCode::encode_codewords(&mut outbuffer, len, &mut tempbuffer, ecc, version, options.mask);
+        self.render_styled_qrcode(qrcode, options, point)
+    }
+
+    fn render_micro_qrcode<F>(&mut self, options: &QrOptions, point: Point, encode: F) -> Result<Rectangle, PTouchError>
+    where
+        F: FnOnce() -> Result<MicroQrCode, DataTooLong>,
+    {
+        if options.mask.is_some_and(|mask| mask.value() > 3) {
+            return Err(PTouchError::RenderError);
+        }
+        let code = encode().map_err(|_| PTouchError::RenderError)?;
+        let module_size = options.module_size_for(code.size() as u32).ok_or(PTouchError::RenderError)?;
+        let mut code = StyledMicroQrCode::from(code)
+            .with_scale(module_size)
+            .with_quiet_zone(options.symbol_quiet_zone());
+        if options.invert {
+            code = code.inverted();
+        }
+        code.position = point;
+        self.render_barcode(&code)
+    }
+
+    fn render_styled_qrcode(&mut self, qrcode: QrCode, options: &QrOptions, point: Point) -> Result<Rectangle, PTouchError> {
+        let module_size = options.module_size_for(qrcode.size() as u32).ok_or(PTouchError::RenderError)?;
+        let mut qrcode = StyledQrCode::from(qrcode)
+            .with_scale(module_size)
+            .with_border(options.quiet_zone * module_size);
+        if options.invert {
+            qrcode = qrcode.inverted();
+        }
+        qrcode.position = point;
+        self.render_barcode(&qrcode)
+    }
+
+    /// Draw a Code 128 barcode with human readable text, `data` starting with `(` is encoded as GS1-128
+    pub fn render_code128(&mut self, data: &str, point: Point) -> Result<Rectangle, PTouchError> {
+        let code = match data.starts_with('(') {
+            true => Code128::gs1(data)?,
+            false => Code128::encode(data)?,
+        };
+        let mut barcode = StyledBarcode::from(code);
+        barcode.position = point;
+        self.render_barcode(&barcode)
+    }
+
+    /// Draw an EAN-13, UPC-A or EAN-8 symbol with the standard X-dimension (0.33mm) snapped to whole dots
+    pub fn render_ean(&mut self, kind: EanKind, data: &str, point: Point) -> Result<Rectangle, PTouchError> {
+        let module_width = barcode::x_dimension(0.33, self.cfg.dpi);
+        let mut ean = StyledEan::from(Ean::new(kind, data)?)
+            .with_module_width(module_width)
+            .with_height(module_width * 60);
+        ean.position = point;
+        self.render_barcode(&ean)
+    }
+
+    /// Draw a square Data Matrix with 0.33mm modules, `data` starting with `(` is encoded as GS1 DataMatrix
+    pub fn render_datamatrix(&mut self, data: &str, point: Point) -> Result<Rectangle, PTouchError> {
+        let code = match data.starts_with('(') {
+            true => DataMatrix::gs1(data, SymbolShape::Square)?,
+            false => DataMatrix::encode(data, SymbolShape::Square)?,
+        };
+        let mut symbol = StyledDataMatrix::from(code).with_scale(barcode::x_dimension(0.33, self.cfg.dpi));
+        symbol.position = point;
+        self.render_barcode(&symbol)
+    }
+
+    /// Draw an Aztec code with 0.33mm modules and the recommended error correction
+    pub fn render_aztec(&mut self, data: &str, point: Point) -> Result<Rectangle, PTouchError> {
+        let code = Aztec::encode(data, &AztecOptions::default())?;
+        let mut symbol = StyledAztec::from(code).with_scale(barcode::x_dimension(0.33, self.cfg.dpi));
+        symbol.position = point;
+        self.render_barcode(&symbol)
+    }
+
+    /// Draw a styled barcode (e.g. `StyledBarcode`, `StyledEan`, `StyledMatrix`), returns the area it covers
+    pub fn render_barcode<B>(&mut self, barcode: &B) -> Result<Rectangle, PTouchError>
+    where
+        B: Drawable<Color = BinaryColor, Output = ()> + Dimensions,
+    {
+        barcode.draw(&mut self.display)?;
+        Ok(barcode.bounding_box())
+    }
+
+    /// Draw a greyscale image with its top left corner at `point`, dark pixels (< 128) are printed
+    pub fn render_bitmap(&mut self, image: &GrayImage, point: Point) -> Result<(), PTouchError> {
+        let pixels = image.enumerate_pixels().map(|(x, y, p)| {
+            Pixel(point + Point::new(x as i32, y as i32), BinaryColor::from(p.0[0] < 128))
+        });
+        self.display.draw_iter(pixels)
+    }
+
+    /// Draw an image file or decoded image scaled to fit `area` with its aspect ratio kept and dithered to dots.
+    /// Returns the area the scaled image covers.
+    pub fn render_image<'a>(
+        &mut self,
+        source: impl Into<ImageSource<'a>>,
+        area: Rectangle,
+        options: &ImageOptions,
+    ) -> Result<Rectangle, PTouchError> {
+        let opened;
+        let image = match source.into() {
+            ImageSource::Path(path) => {
+                opened = image::open(path)?;
+                &opened
+            }
+            ImageSource::Image(image) => image,
+        };
+
+        self.place_bitmap(Bitmap::fit(image, area.size, options), area, options)
+    }
+
+    /// Render SVG artwork scaled to fit `area`, aligned as with `render_image`
+    ///
+    /// Use `Svg::size_at` with the printer resolution for an area at the artwork's own size.
+    #[cfg(feature = "svg")]
+    pub fn render_svg(&mut self, svg: &svg::Svg, area: Rectangle, options: &ImageOptions) -> Result<Rectangle, PTouchError> {
+        self.place_bitmap(svg.to_bitmap(area.size, options), area, options)
+    }
+
+    fn place_bitmap(&mut self, mut bitmap: Bitmap, area: Rectangle, options: &ImageOptions) -> Result<Rectangle, PTouchError> {
+        let free = area.size - bitmap.size();
+        let x = match options.h_align {
+            HAlign::Left => 0,
+            HAlign::Centre => free.width / 2,
+            HAlign::Right => free.width,
+        };
+        let y = match options.v_align {
+            VAlign::Top => 0,
+            VAlign::Centre => free.height / 2,
+            VAlign::Bottom => free.height,
+        };
+        bitmap.position = area.top_left + Point::new(x as i32, y as i32);
+        bitmap.draw(&mut self.display)?;
+        Ok(bitmap.bounding_box())
+    }
+
+    /// Draw a straight line from `start` to `end`, returns the box around it
+    pub fn render_line(&mut self, start: Point, end: Point, stroke: &StrokeOptions) -> Result<Rectangle, PTouchError> {
+        self.render_shape(Shape::Line(Line::new(start, end)), stroke)
+    }
+
+    /// Draw a rectangle, optionally with rounded corners, the outline is drawn inside `area`
+    pub fn render_rect(
+        &mut self,
+        area: Rectangle,
+        corner_radius: Length,
+        stroke: &StrokeOptions,
+    ) -> Result<Rectangle, PTouchError> {
+        self.render_shape(Shape::Rectangle(area, corner_radius.dots(self.cfg.dpi)), stroke)
+    }
+
+    /// Draw a circle around `centre`, the outline is drawn inside the diameter
+    pub fn render_circle(
+        &mut self,
+        centre: Point,
+        diameter: Length,
+        stroke: &StrokeOptions,
+    ) -> Result<Rectangle, PTouchError> {
+        self.render_shape(Shape::Circle(Circle::with_center(centre, diameter.dots(self.cfg.dpi))), stroke)
+    }
+
+    /// Draw a separator across the whole tape width with its left edge at `x`
+    pub fn render_rule(&mut self, x: i32, stroke: &StrokeOptions) -> Result<Rectangle, PTouchError> {
+        let centre = x + (stroke.width.dots(self.cfg.dpi).saturating_sub(1) / 2) as i32;
+        let line = Line::new(Point::new(centre, 0), Point::new(centre, self.cfg.y as i32 - 1));
+        self.render_shape(Shape::Line(line), stroke)
+    }
+
+    fn render_shape(&mut self, shape: Shape, stroke: &StrokeOptions) -> Result<Rectangle, PTouchError> {
+        let shape = StyledShape::new(shape, stroke, self.cfg.dpi);
+        shape.draw(&mut self.display)?;
+        Ok(shape.bounding_box())
+    }
+
+    /// Draw a table with its top left corner at `top_left`, one text per cell. Columns are as wide as their
+    /// widest cell unless `TableOptions::column_widths` fixes them. Returns the box around the grid.
+    pub fn render_table<R, S>(
+        &mut self,
+        rows: &[R],
+        options: &TableOptions,
+        top_left: Point,
+    ) -> Result<Rectangle, PTouchError>
+    where
+        R: AsRef<[S]>,
+        S: AsRef<str>,
+    {
+        let options = TableOptions { text: self.text_options(&options.text), ..options.clone() };
+        let table = Table::new(rows, &options, self.cfg.dpi, top_left);
+        table.draw(&mut self.display)?;
+        Ok(table.bounding_box())
+    }
+
+    /// Arrange `layout` inside `area` and draw it, returns the box around the drawn content
+    pub fn render_layout(&mut self, layout: &Stack, area: Rectangle) -> Result<Rectangle, PTouchError> {
+        let drawn = layout.arrange(self, area)?;
+        Ok(drawn.unwrap_or(Rectangle::new(area.top_left, Size::zero())))
+    }
+
+    /// Smallest size `layout` needs with `available` dots to spare, e.g. the label length for continuous tape
+    pub fn measure_layout(&self, layout: &Stack, available: Size) -> Result<Size, PTouchError> {
+        layout.measure(self, available)
+    }
+
+    /// Rendered display, e.g. for previews
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Raster lines for `Commands::print_data`
+    pub fn raster(&self) -> Result<Vec<Vec<u8>>, PTouchError> {
+        self.display.render()
+    }
+
+    /// Preview image, black on white and rotated according to the orientation like `show`
+    pub fn to_image(&self) -> GrayImage {
+        let s = self.display.size();
+        let pixel = |x: u32, y: u32| match self.display.get(x as usize, y as usize) {
+            Ok(true) => Luma([0u8]),
+            _ => Luma([255u8]),
+        };
+
+        match self.cfg.orientation {
+            Orientation::Vertical => GrayImage::from_fn(s.width, s.height, pixel),
+            // Inverse of the transpose in `show`
+            Orientation::Horizontal => GrayImage::from_fn(s.height, s.width, |x, y| pixel(s.width - 1 - y, x)),
+        }
+    }
+
+    /// Preview image as RGB, e.g. for composing with coloured artwork
+    pub fn to_rgb_image(&self) -> RgbImage {
+        DynamicImage::ImageLuma8(self.to_image()).to_rgb8()
+    }
+
+    /// Encode the preview as PNG
+    pub fn to_png(&self) -> Result<Vec<u8>, PTouchError> {
+        let mut png = Vec::new();
+        self.to_image().write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        Ok(png)
+    }
+
+    /// Encode the preview as binary PBM (P4), 1 bit per pixel with 1 being black
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let image = self.to_image();
+        let (w, h) = image.dimensions();
+
+        let mut pbm = format!("P4\n{w} {h}\n").into_bytes();
+        for row in image.rows() {
+            let mut bits = vec![0u8; (w as usize).div_ceil(8)];
+            for (x, p) in row.enumerate() {
+                if p.0[0] == 0 {
+                    bits[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            pbm.extend(bits);
+        }
+        pbm
+    }
+
+    /// Write the preview to a PNG file
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), PTouchError> {
+        Ok(fs::write(path, self.to_png()?)?)
+    }
+
+    /// Write the preview to a PBM file
+    pub fn save_pbm<P: AsRef<Path>>(&self, path: P) -> Result<(), PTouchError> {
+        Ok(fs::write(path, self.to_pbm())?)
     }
 
     pub fn show(&self) -> Result<(), PTouchError> {
@@ -163,5 +577,178 @@ impl Render {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::barcode::BarcodeError;
+    use crate::render::qr_payload::{PayloadError, Wifi, WifiSecurity};
+
+    fn render(orientation: Orientation) -> Render {
+        let mut render = Render::new(RenderConfig { orientation, min_x: 4, y: 3, ..Default::default() });
+        render.display.set(0, 0, true).unwrap();
+        render.display.set(3, 1, true).unwrap();
+        render
+    }
+
+    #[test]
+    fn image_vertical() {
+        let image = render(Orientation::Vertical).to_image();
+        assert_eq!(image.dimensions(), (4, 3));
+        assert_eq!(image.get_pixel(0, 0).0, [0]);
+        assert_eq!(image.get_pixel(3, 1).0, [0]);
+        assert_eq!(image.get_pixel(1, 0).0, [255]);
+    }
+
+    #[test]
+    fn image_horizontal() {
+        let image = render(Orientation::Horizontal).to_image();
+        assert_eq!(image.dimensions(), (3, 4));
+        assert_eq!(image.get_pixel(0, 3).0, [0]);
+        assert_eq!(image.get_pixel(1, 0).0, [0]);
+        assert_eq!(image.get_pixel(0, 0).0, [255]);
+    }
+
+    #[test]
+    fn pbm() {
+        let pbm = render(Orientation::Vertical).to_pbm();
+        assert_eq!(pbm, b"P4\n4 3\n\x80\x10\x00");
+    }
+
+    #[test]
+    fn qrcode_options() {
+        let mut render = Render::new(RenderConfig { y: 100, ..Default::default() });
+        let options = QrOptions { quiet_zone: 2, fit: Some(Size::new(300, 100)), ..Default::default() };
+        // Version 1 is 21 modules, 25 with the quiet zone
+        let area = render.render_qrcode("A", &options, Point::new(10, 0)).unwrap();
+        assert_eq!(area, Rectangle::new(Point::new(10, 0), Size::new(100, 100)));
+        assert!(render.display.get(10 + 8, 8).unwrap());
+        assert!(!render.display.get(10 + 7, 8).unwrap());
+
+        let inverted = QrOptions { invert: true, ..options.clone() };
+        render.render_qrcode("A", &inverted, Point::new(200, 0)).unwrap();
+        assert!(render.display.get(200, 0).unwrap());
 
+        let small = QrOptions { fit: Some(Size::new(20, 20)), ..options };
+        assert!(render.render_qrcode("A", &small, Point::zero()).is_err());
+        let fixed = QrOptions { ecc: QrCodeEcc::High, module_size: 2, quiet_zone: 4, ..Default::default() };
+        let area = render.render_qrcode("A", &fixed, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(58, 58));
+    }
+
+    #[test]
+    fn qr_segments() {
+        let mut render = Render::new(RenderConfig { y: 100, ..Default::default() });
+        let options = QrOptions { ecc: QrCodeEcc::Low, module_size: 1, quiet_zone: 0, ..Default::default() };
+        let data = format!("serial:{}", "0123456789".repeat(8));
+        // Byte mode needs version 5, a byte prefix and a numeric serial fit in version 3
+        let area = render.render_qrcode(&data, &options, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(37, 37));
+        let area = render.render_qrcode_optimized(&data, &options, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(29, 29));
+
+        let segments = [Segment::alphanumeric("PN-").unwrap(), Segment::numeric("0123456789").unwrap()];
+        let area = render.render_qr_segments(&segments, &options, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(21, 21));
+        let invalid = [Segment::Numeric("12a".into())];
+        assert!(matches!(
+            render.render_qr_segments(&invalid, &options, Point::zero()),
+            Err(PTouchError::BarcodeError(BarcodeError::InvalidCharacter('a')))
+        ));
+    }
 
+    #[test]
+    fn qr_payload() {
+        let mut render = Render::new(RenderConfig { y: 100, ..Default::default() });
+        let options = QrOptions { module_size: 1, quiet_zone: 0, ..Default::default() };
+        let wifi = Wifi::new("lab", WifiSecurity::Wpa, "correct horse");
+        // 34 bytes need version 3 at medium error correction
+        let area = render.render_qr_payload(&wifi, &options, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(29, 29));
+        let wifi = Wifi::new("lab", WifiSecurity::Wpa, "");
+        assert!(matches!(
+            render.render_qr_payload(&wifi, &options, Point::zero()),
+            Err(PTouchError::PayloadError(PayloadError::Missing("password")))
+        ));
+    }
+
+    #[test]
+    fn image_in_box() {
+        let mut render = Render::new(RenderConfig { y: 40, ..Default::default() });
+        let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(20, 10, Luma([0])));
+        let area = Rectangle::new(Point::new(5, 0), Size::new(40, 40));
+        let drawn = render.render_image(&image, area, &ImageOptions::default()).unwrap();
+        assert_eq!(drawn, Rectangle::new(Point::new(5, 10), Size::new(40, 20)));
+        assert!(render.display.get(5, 10).unwrap() && !render.display.get(5, 9).unwrap());
+
+        let options = ImageOptions { h_align: HAlign::Right, v_align: VAlign::Bottom, ..Default::default() };
+        let area = Rectangle::new(Point::zero(), Size::new(30, 10));
+        let drawn = render.render_image(&image, area, &options).unwrap();
+        assert_eq!(drawn, Rectangle::new(Point::new(10, 0), Size::new(20, 10)));
+
+        let path = std::env::temp_dir().join("ql-raster-render-image.png");
+        image.save(&path).unwrap();
+        let drawn = render.render_image(&path, area, &options).unwrap();
+        assert_eq!(drawn.size, Size::new(20, 10));
+        assert!(matches!(
+            render.render_image("does-not-exist.png", area, &options),
+            Err(PTouchError::ImageError(_))
+        ));
+    }
+
+    #[test]
+    fn micro_qrcode() {
+        let mut render = Render::new(RenderConfig { y: 100, ..Default::default() });
+        let options = QrOptions { micro: true, module_size: 2, ..Default::default() };
+        // M2 is 13 modules, the quiet zone is capped at 2 modules
+        let area = render.render_qrcode("12345678", &options, Point::new(4, 0)).unwrap();
+        assert_eq!(area, Rectangle::new(Point::new(4, 0), Size::new(34, 34)));
+        assert!(render.display.get(4 + 4, 4).unwrap());
+        assert!(!render.display.get(4 + 3, 3).unwrap());
+
+        let segments = [Segment::alphanumeric("PN").unwrap(), Segment::numeric("12345").unwrap()];
+        let area = render.render_qr_segments(&segments, &options, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(2 * (15 + 4), 2 * (15 + 4)));
+        // 5 bytes of UTF-8 without ECI
+        let area = render.render_qrcode_optimized("Gr\u{fc}n", &options, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(2 * (15 + 4), 2 * (15 + 4)));
+
+        // M1 has no medium error correction, M2 with its quiet zone is 17 modules
+        let fit = QrOptions { fit: Some(Size::new(100, 100)), ..options.clone() };
+        let area = render.render_qrcode("1", &fit, Point::zero()).unwrap();
+        assert_eq!(area.size, Size::new(85, 85));
+        let masked = QrOptions { mask: Some(Mask::new(4)), ..options.clone() };
+        assert!(render.render_qrcode("1", &masked, Point::zero()).is_err());
+        assert!(render.render_qrcode(&"1".repeat(36), &options, Point::zero()).is_err());
+    }
+
+    #[test]
+    fn primitives() {
+        let mut render = Render::new(RenderConfig { y: 64, ..Default::default() });
+        let stroke = StrokeOptions { width: Length::Dots(2), ..Default::default() };
+        let rule = render.render_rule(10, &stroke).unwrap();
+        assert_eq!(rule, Rectangle::new(Point::new(10, 0), Size::new(2, 64)));
+        let row: Vec<bool> = (9..13).map(|x| render.display.get(x, 63).unwrap()).collect();
+        assert_eq!(row, [false, true, true, false]);
+
+        // 1 mm at 300 dpi is 12 dots
+        let thick = StrokeOptions { width: Length::Mm(1.0), ..Default::default() };
+        let area = Rectangle::new(Point::new(20, 10), Size::new(40, 40));
+        assert_eq!(render.render_rect(area, Length::Mm(2.0), &thick).unwrap(), area);
+        assert!(render.display.get(20, 30).unwrap() && render.display.get(31, 30).unwrap());
+        assert!(!render.display.get(32, 30).unwrap() && !render.display.get(20, 10).unwrap());
+
+        let drawn = render.render_circle(Point::new(100, 32), Length::Dots(21), &thick).unwrap();
+        assert_eq!(drawn, Rectangle::new(Point::new(90, 22), Size::new(21, 21)));
+
+        let rows = [["A", "1"], ["B", "2"]];
+        let text = TextOptions { font: FontKind::Font6x8, ..Default::default() };
+        let options = TableOptions { text, ..Default::default() };
+        let table = render.render_table(&rows, &options, Point::new(120, 0)).unwrap();
+        assert_eq!(table.size, Size::new(2 * (5 + 8) + 3 * 2, 2 * (8 + 8) + 3 * 2));
+        // Even grid line widths stay inside the table too
+        let column: Vec<bool> = (0..3).map(|y| render.display.get(130, y).unwrap()).collect();
+        assert_eq!(column, [true, true, false]);
+        let row: Vec<bool> = (118..123).map(|x| render.display.get(x, 20).unwrap()).collect();
+        assert_eq!(row, [false, false, true, true, false]);
+    }
+}
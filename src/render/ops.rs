@@ -1,40 +1,57 @@
 use embedded_graphics::{
-    mono_font::{ascii::FONT_6X10, MonoTextStyle},
-    pixelcolor::Rgb565,
-    prelude::*,
-    text::Text,
+    mono_font::{iso_8859_1, MonoFont},
+    prelude::Size,
 };
 
+use crate::render::font::TtfTextStyle;
+use crate::render::{Mask, QrCodeEcc, Version};
 
+#[derive(Debug, Clone)]
 pub struct TextOptions {
     pub font: FontKind,
     pub v_align: VAlign,
     pub h_align: HAlign,
 }
 
+/// Built-in bitmap fonts (nearest ISO 8859-1 font, scaled up where needed) or a TrueType font.
+/// The names are nominal, the glyphs drawn are the sizes given for each font.
+#[derive(Debug, Clone)]
 pub enum FontKind {
     // #[cfg_attr(feature = "strum", strum(serialize = "6x6"))]
+    /// 4x6 dots
     Font6x6,
     // #[cfg_attr(feature = "strum", strum(serialize = "6x8"))]
+    /// 5x8 dots
     Font6x8,
     // #[cfg_attr(feature = "strum", strum(serialize = "6x12"))]
+    /// 6x12 dots
     Font6x12,
     // #[cfg_attr(feature = "strum", strum(serialize = "8x16"))]
+    /// 9x15 dots
     Font8x16,
     // #[cfg_attr(feature = "strum", strum(serialize = "12x16"))]
+    /// 10x16 dots, 5x8 at twice the size
     Font12x16,
     // #[cfg_attr(feature = "strum", strum(serialize = "24x32"))]
+    /// 20x32 dots, 5x8 at four times the size
     Font24x32,
+    TrueType(TtfTextStyle),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
 pub enum VAlign {
     Top,
+    #[cfg_attr(feature = "serde", serde(alias = "center"))]
     Centre,
     Bottom,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
 pub enum HAlign {
     Left,
+    #[cfg_attr(feature = "serde", serde(alias = "center"))]
     Centre,
     Right,
 }
@@ -48,3 +65,296 @@ impl Default for TextOptions {
         }
     }
 }
+
+/// Options for `Render::render_paragraph`, alignment applies to every line
+#[derive(Debug, Clone)]
+pub struct ParagraphOptions {
+    pub text: TextOptions,
+    /// Line pitch as a multiple of the font's line height
+    pub line_spacing: f32,
+    /// Stretch all but the last line of each paragraph to the full width
+    pub justify: bool,
+    /// Break words wider than a line between characters (e.g. part numbers)
+    pub break_words: bool,
+}
+
+impl Default for ParagraphOptions {
+    fn default() -> Self {
+        Self {
+            text: TextOptions { h_align: HAlign::Left, v_align: VAlign::Top, ..Default::default() },
+            line_spacing: 1.0,
+            justify: false,
+            break_words: true,
+        }
+    }
+}
+
+/// How `Render::render_text_fit` searches for a font size
+#[derive(Debug, Clone)]
+pub struct FitOptions {
+    /// Smallest TrueType size in points
+    pub min_size: f32,
+    /// Largest TrueType size in points
+    pub max_size: f32,
+    /// TrueType size step in points
+    pub step: f32,
+    /// Wrap onto multiple lines at whitespace
+    pub wrap: bool,
+    /// Shorten text that doesn't fit at the smallest size, ending it with an ellipsis
+    pub ellipsis: bool,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        Self { min_size: 6.0, max_size: 72.0, step: 0.5, wrap: false, ellipsis: true }
+    }
+}
+
+/// Options for `Render::render_qrcode`
+#[derive(Debug, Clone)]
+pub struct QrOptions {
+    pub ecc: QrCodeEcc,
+    /// Smallest and largest symbol version (1-40) to choose from
+    pub min_version: Version,
+    pub max_version: Version,
+    /// Fixed mask pattern, the best one is chosen if `None`
+    pub mask: Option<Mask>,
+    /// Raise the error correction level while the symbol doesn't grow
+    pub boost_ecc: bool,
+    /// Module size in dots
+    pub module_size: u32,
+    /// Quiet zone on all sides in modules, the standard asks for 4 (Micro QR uses at most 2)
+    pub quiet_zone: u32,
+    /// Light modules on a dark background
+    pub invert: bool,
+    /// Use the largest module size for which the symbol and quiet zone fit this box, instead of `module_size`
+    pub fit: Option<Size>,
+    /// Encode a Micro QR code (M1-M4) instead, the versions are ignored and the mask must be 0-3
+    pub micro: bool,
+}
+
+impl Default for QrOptions {
+    fn default() -> Self {
+        Self {
+            ecc: QrCodeEcc::Medium,
+            min_version: Version::MIN,
+            max_version: Version::MAX,
+            mask: None,
+            boost_ecc: true,
+            module_size: 4,
+            quiet_zone: 4,
+            invert: false,
+            fit: None,
+            micro: false,
+        }
+    }
+}
+
+impl QrOptions {
+    /// Quiet zone in modules for the selected symbol
+    pub fn symbol_quiet_zone(&self) -> u32 {
+        match self.micro {
+            true => self.quiet_zone.min(2),
+            false => self.quiet_zone,
+        }
+    }
+
+    /// Module size in dots for a symbol `modules` wide, `None` if it doesn't fit the `fit` box
+    pub fn module_size_for(&self, modules: u32) -> Option<u32> {
+        match self.fit {
+            Some(area) => {
+                let size = (area.width.min(area.height)) / (modules + 2 * self.symbol_quiet_zone());
+                (size > 0).then_some(size)
+            }
+            None => Some(self.module_size.max(1)),
+        }
+    }
+}
+
+impl FontKind {
+    /// Bitmap fonts from largest to smallest
+    pub const BITMAP: [FontKind; 6] = [
+        FontKind::Font24x32,
+        FontKind::Font12x16,
+        FontKind::Font8x16,
+        FontKind::Font6x12,
+        FontKind::Font6x8,
+        FontKind::Font6x6,
+    ];
+
+    /// Bitmap font and integer scale factor, `None` for TrueType fonts
+    pub fn mono(&self) -> Option<(&'static MonoFont<'static>, u32)> {
+        match self {
+            FontKind::Font6x6 => Some((&iso_8859_1::FONT_4X6, 1)),
+            FontKind::Font6x8 => Some((&iso_8859_1::FONT_5X8, 1)),
+            FontKind::Font6x12 => Some((&iso_8859_1::FONT_6X12, 1)),
+            FontKind::Font8x16 => Some((&iso_8859_1::FONT_9X15, 1)),
+            FontKind::Font12x16 => Some((&iso_8859_1::FONT_5X8, 2)),
+            FontKind::Font24x32 => Some((&iso_8859_1::FONT_5X8, 4)),
+            FontKind::TrueType(_) => None,
+        }
+    }
+}
+
+impl TextOptions {
+    /// Size of a single line of text in dots
+    pub fn measure(&self, text: &str) -> Size {
+        match &self.font {
+            FontKind::TrueType(style) => style.measure(text),
+            kind => {
+                let (font, scale) = kind.mono().expect("bitmap font");
+                let n = text.chars().count() as u32;
+                let width = n * font.character_size.width + n.saturating_sub(1) * font.character_spacing;
+                Size::new(width, font.character_size.height) * scale
+            }
+        }
+    }
+
+    /// Height of a line in dots
+    pub fn line_height(&self) -> u32 {
+        self.measure("").height
+    }
+}
+
+/// Conversion of greyscale to printed and blank dots
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum Dither {
+    /// Print dots darker than the threshold, best for logos and line art
+    Threshold,
+    /// Error diffusion to four neighbours, smooth gradients for photos
+    #[default]
+    FloydSteinberg,
+    /// Error diffusion that drops a quarter of the error, more contrast than Floyd-Steinberg
+    Atkinson,
+    /// 4x4 Bayer matrix, a regular pattern that stays stable in flat areas
+    Ordered,
+}
+
+/// Options for `Render::render_image`
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    pub dither: Dither,
+    /// Lightness (0-255) below which dots are printed, shifts all dithering modes
+    pub threshold: u8,
+    /// Print the light parts of the image, transparent areas stay blank
+    pub invert: bool,
+    /// Position of the scaled image in the box
+    pub h_align: HAlign,
+    pub v_align: VAlign,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            dither: Dither::default(),
+            threshold: 128,
+            invert: false,
+            h_align: HAlign::Centre,
+            v_align: VAlign::Centre,
+        }
+    }
+}
+
+/// A distance in printer dots, or in millimetres converted with `RenderConfig::dpi`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Dots(u32),
+    Mm(f32),
+}
+
+impl Length {
+    pub fn dots(self, dpi: u32) -> u32 {
+        match self {
+            Length::Dots(dots) => dots,
+            Length::Mm(mm) => (mm.max(0.0) * dpi as f32 / 25.4).round() as u32,
+        }
+    }
+}
+
+impl Default for Length {
+    fn default() -> Self {
+        Length::Dots(0)
+    }
+}
+
+impl From<u32> for Length {
+    fn from(dots: u32) -> Self {
+        Length::Dots(dots)
+    }
+}
+
+impl std::str::FromStr for Length {
+    type Err = String;
+
+    /// Dots as a plain number, millimetres with an `mm` suffix, e.g. `"24"` or `"2.5mm"`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("Invalid length {s:?}, expected dots or millimetres like \"2.5mm\"");
+        match s.strip_suffix("mm") {
+            Some(mm) => mm.trim().parse().ok().filter(|mm: &f32| *mm >= 0.0).map(Length::Mm).ok_or_else(invalid),
+            None => s.strip_suffix("dots").unwrap_or(s).trim().parse().map(Length::Dots).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// A number of dots or a string parsed with `Length::from_str`
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Length {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Dots(u32),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Dots(dots) => Ok(Length::Dots(dots)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Outline of lines, rectangles and circles drawn by `Render`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct StrokeOptions {
+    pub width: Length,
+    /// Dash and gap length along the outline, solid if `None`
+    pub dash: Option<(Length, Length)>,
+    /// Fill closed shapes, the outline is drawn on top
+    pub fill: bool,
+}
+
+impl Default for StrokeOptions {
+    fn default() -> Self {
+        Self { width: Length::Dots(2), dash: None, fill: false }
+    }
+}
+
+/// Options for `Render::render_table`
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    /// Font and alignment of the text inside each cell
+    pub text: TextOptions,
+    /// Space between the grid lines and the cell text
+    pub padding: Length,
+    /// Grid lines around and between the cells, `None` for no lines
+    pub grid: Option<StrokeOptions>,
+    /// Width of each column without padding, sized to the widest cell if missing
+    pub column_widths: Vec<Length>,
+    /// Height of each row without padding, the font's line height if `None`
+    pub row_height: Option<Length>,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            text: TextOptions { h_align: HAlign::Left, ..Default::default() },
+            padding: Length::Dots(4),
+            grid: Some(StrokeOptions::default()),
+            column_widths: Vec::new(),
+            row_height: None,
+        }
+    }
+}
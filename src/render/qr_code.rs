@@ -838,6 +838,287 @@ impl PartialEq for QrCode<'_> {
 
 impl Eq for QrCode<'_> {}
 
+/*---- MicroQrCode functionality ----*/
+
+/// A Micro QR Code symbol, versions M1 to M4 (11 to 17 modules wide).
+///
+/// Micro QR Codes have a single finder pattern and need a quiet zone of only 2 modules,
+/// so they fit where a version 1 QR Code doesn't. They hold up to 35 digits, 21 alphanumeric
+/// characters or 15 bytes. M1 only encodes digits and detects but doesn't correct errors,
+/// M2 supports the low and medium error correction levels and M3 and M4 add byte and kanji mode,
+/// with quartile error correction in M4. ECI segments aren't supported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MicroQrCode {
+    version: MicroVersion,
+
+    errorcorrectionlevel: QrCodeEcc,
+
+    mask: Mask,
+
+    // The modules of this Micro QR Code (false = light, true = dark), row by row.
+    modules: Vec<bool>,
+
+    // Indicates function modules that are not subjected to masking.
+    isfunction: Vec<bool>,
+}
+
+impl MicroQrCode {
+    /*---- Static factory functions ----*/
+
+    /// Encodes the given text string to the smallest Micro QR Code it fits, in numeric,
+    /// alphanumeric or byte mode. See `MicroQrCode::encode_segments()` for the other arguments.
+    pub fn encode_text(text: &str, ecl: QrCodeEcc, mask: Option<Mask>, boostecl: bool) -> Result<Self, DataTooLong> {
+        // Numeric and alphanumeric mode need less than 6 bits per character
+        let mut buffer = vec![0u8; text.len()];
+        let seg: QrSegment = if QrSegment::is_numeric(text) {
+            QrSegment::make_numeric(text, &mut buffer)
+        } else if QrSegment::is_alphanumeric(text) {
+            QrSegment::make_alphanumeric(text, &mut buffer)
+        } else {
+            QrSegment::make_bytes(text.as_bytes())
+        };
+        Self::encode_segments(&[seg], ecl, mask, boostecl)
+    }
+
+    /// Encodes the given segments to the smallest Micro QR Code version they fit at the given
+    /// error correction level. Iff boostecl is `true`, then the error correction level of the result
+    /// may be higher if it can be done without increasing the version. The mask number is either
+    /// between 0 to 3 (inclusive) to force that mask, or `None` to choose the best mask.
+    ///
+    /// Panics if the mask is greater than 3.
+    pub fn encode_segments(
+        segs: &[QrSegment],
+        mut ecl: QrCodeEcc,
+        mask: Option<Mask>,
+        boostecl: bool,
+    ) -> Result<Self, DataTooLong> {
+        assert!(mask.is_none_or(|m| m.value() <= 3), "Mask value out of range");
+
+        // Find the minimal version number to use
+        let mut toolong = DataTooLong::SegmentTooLong;
+        let mut version = MicroVersion::MIN;
+        let datausedbits: usize = loop {
+            let dataused: Option<usize> = MicroQrCode::get_total_bits(segs, version);
+            if let (Some(n), Some(capacity)) = (dataused, version.num_data_bits(ecl)) {
+                if n <= capacity {
+                    break n;
+                }
+                toolong = DataTooLong::DataOverCapacity(n, capacity);
+            }
+            if version >= MicroVersion::MAX {
+                return Err(toolong);
+            }
+            version = MicroVersion::new(version.value() + 1);
+        };
+
+        // Increase the error correction level while the data still fits in the current version number
+        for &newecl in &[QrCodeEcc::Medium, QrCodeEcc::Quartile] {
+            if boostecl && version.num_data_bits(newecl).is_some_and(|capacity| datausedbits <= capacity) {
+                ecl = newecl;
+            }
+        }
+        let capacity: usize = version.num_data_bits(ecl).unwrap();
+
+        // Concatenate all segments to create the data bit string
+        let mut bb = Vec::with_capacity(capacity);
+        let mut append_bits = |val: u32, len: u8| bb.extend((0..len).rev().map(|i| get_bit(val, i)));
+        for seg in segs {
+            append_bits(seg.mode.micro_mode_bits(), version.value() - 1);
+            append_bits(u32::try_from(seg.numchars).unwrap(), seg.mode.num_micro_char_count_bits(version));
+            for i in 0..seg.bitlength {
+                append_bits(u32::from(seg.data[i >> 3] >> (7 - (i & 7)) & 1), 1);
+            }
+        }
+        debug_assert_eq!(bb.len(), datausedbits);
+
+        // Add terminator, pad up to a byte boundary, then add alternating pad bytes.
+        // In M1 and M3 the last data codeword is 4 bits long and padded with zeros.
+        let terminator: usize = (usize::from(version.value()) * 2 + 1).min(capacity - bb.len());
+        bb.resize(bb.len() + terminator, false);
+        bb.resize(bb.len().next_multiple_of(8).min(capacity), false);
+        for &padbyte in [0xEC, 0x11].iter().cycle() {
+            if bb.len() + 8 > capacity {
+                break;
+            }
+            bb.extend((0..8).rev().map(|i| get_bit(padbyte, i)));
+        }
+        bb.resize(capacity, false);
+
+        // Pack bits into bytes, the half codeword in its high nibble, and append the error correction
+        let mut codewords = vec![0u8; capacity.div_ceil(8)];
+        for (i, &bit) in bb.iter().enumerate() {
+            codewords[i >> 3] |= u8::from(bit) << (7 - (i & 7));
+        }
+        let mut ecc = vec![0u8; version.num_ecc_codewords(ecl)];
+        ReedSolomonGenerator::new(ecc.len()).compute_remainder(&codewords, &mut ecc);
+        bb.extend(ecc.iter().flat_map(|&b| (0..8).rev().map(move |i| get_bit(b.into(), i))));
+
+        // Draw function patterns and data, then choose the best mask
+        let size = usize::from(version.size());
+        let mut result = Self {
+            version,
+            errorcorrectionlevel: ecl,
+            mask: Mask::new(0),
+            modules: vec![false; size * size],
+            isfunction: vec![false; size * size],
+        };
+        result.draw_function_patterns();
+        result.draw_codewords(&bb);
+        let mask = mask.unwrap_or_else(|| {
+            (0..4u8)
+                .map(Mask::new)
+                .max_by_key(|&m| {
+                    result.apply_mask(m);
+                    result.draw_format_bits(m);
+                    let score = result.get_score();
+                    result.apply_mask(m); // Undoes the mask due to XOR
+                    (score, std::cmp::Reverse(m))
+                })
+                .unwrap()
+        });
+        result.mask = mask;
+        result.apply_mask(mask);
+        result.draw_format_bits(mask);
+        Ok(result)
+    }
+
+    /*---- Public methods ----*/
+
+    /// Returns this Micro QR Code's version, M1 to M4.
+    pub fn version(&self) -> MicroVersion {
+        self.version
+    }
+
+    /// Returns this Micro QR Code's size, in the range [11, 17].
+    pub fn size(&self) -> i32 {
+        i32::from(self.version.size())
+    }
+
+    /// Returns this Micro QR Code's error correction level, `None` for M1 which only detects errors.
+    pub fn error_correction_level(&self) -> Option<QrCodeEcc> {
+        (self.version > MicroVersion::MIN).then_some(self.errorcorrectionlevel)
+    }
+
+    /// Returns this Micro QR Code's mask, in the range [0, 3].
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+
+    /// Returns the color of the module at the given coordinates, `false` (light) if out of bounds.
+    pub fn get_module(&self, x: i32, y: i32) -> bool {
+        let range = 0..self.size();
+        range.contains(&x) && range.contains(&y) && self.modules[(y * self.size() + x) as usize]
+    }
+
+    /*---- Private helper methods ----*/
+
+    // Sets the color of a module and marks it as a function module.
+    fn set_function_module(&mut self, x: i32, y: i32, isdark: bool) {
+        let index = (y * self.size() + x) as usize;
+        self.modules[index] = isdark;
+        self.isfunction[index] = true;
+    }
+
+    // The finder pattern with its separator in the top left corner, timing patterns along
+    // the top and left edges and a placeholder for the format bits.
+    fn draw_function_patterns(&mut self) {
+        for y in 0..8 {
+            for x in 0..8 {
+                let dist: i32 = (x - 3_i32).abs().max((y - 3_i32).abs());
+                self.set_function_module(x, y, dist != 2 && dist != 4);
+            }
+        }
+        for i in 8..self.size() {
+            self.set_function_module(i, 0, i % 2 == 0);
+            self.set_function_module(0, i, i % 2 == 0);
+        }
+        self.draw_format_bits(Mask::new(0));
+    }
+
+    // Draws the 15 format bits (symbol number and mask, BCH code, XOR mask) next to the finder.
+    fn draw_format_bits(&mut self, mask: Mask) {
+        let data: u32 = self.version.symbol_number(self.errorcorrectionlevel) << 2 | u32::from(mask.value());
+        let mut rem: u32 = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits: u32 = (data << 10 | rem) ^ 0x4445;
+        debug_assert_eq!(bits >> 15, 0);
+
+        for i in 0..8 {
+            self.set_function_module(8, i + 1, get_bit(bits, i as u8));
+        }
+        for i in 8..15 {
+            self.set_function_module(15 - i, 8, get_bit(bits, i as u8));
+        }
+    }
+
+    // Places the data and error correction bits in two module wide columns, zigzagging from the
+    // bottom right corner. The left edge holds the vertical timing pattern.
+    fn draw_codewords(&mut self, bits: &[bool]) {
+        let size = self.size();
+        let mut bits = bits.iter();
+        let mut upward = true;
+        for right in (1..size).rev().step_by(2) {
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    let index = (y * size + x) as usize;
+                    if !self.isfunction[index] {
+                        self.modules[index] = bits.next().copied().unwrap_or(false);
+                    }
+                }
+            }
+            upward = !upward;
+        }
+        debug_assert!(bits.next().is_none());
+    }
+
+    // XORs the codeword modules with the given mask pattern.
+    fn apply_mask(&mut self, mask: Mask) {
+        let size = self.size();
+        for y in 0..size {
+            for x in 0..size {
+                let invert: bool = match mask.value() {
+                    0 => y % 2 == 0,
+                    1 => (y / 2 + x / 3) % 2 == 0,
+                    2 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    3 => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                    _ => unreachable!(),
+                };
+                let index = (y * size + x) as usize;
+                self.modules[index] ^= invert & !self.isfunction[index];
+            }
+        }
+    }
+
+    // Mask evaluation for Micro QR Codes, higher is better: counts the dark modules along the
+    // right and bottom edges, weighing the edge with fewer of them 16 times.
+    fn get_score(&self) -> u32 {
+        let size = self.size();
+        let right = (1..size).filter(|&y| self.get_module(size - 1, y)).count() as u32;
+        let bottom = (1..size).filter(|&x| self.get_module(x, size - 1)).count() as u32;
+        right.min(bottom) * 16 + right.max(bottom)
+    }
+
+    // Calculates the number of bits needed to encode the given segments at the given version,
+    // None if a mode isn't available or a segment has too many characters for its length field.
+    fn get_total_bits(segs: &[QrSegment], version: MicroVersion) -> Option<usize> {
+        let mut result: usize = 0;
+        for seg in segs {
+            if seg.mode == QrSegmentMode::Eci || seg.mode.micro_mode_bits() >> (version.value() - 1) != 0 {
+                return None;
+            }
+            let ccbits: u8 = seg.mode.num_micro_char_count_bits(version);
+            if seg.numchars >= 1 << ccbits {
+                return None;
+            }
+            result += usize::from(version.value() - 1 + ccbits) + seg.bitlength;
+        }
+        Some(result)
+    }
+}
+
 /*---- Helper struct for add_ecc_and_interleave() ----*/
 
 struct ReedSolomonGenerator {
@@ -1280,9 +1561,32 @@ impl QrSegmentMode {
         }
     }
 
+    // Returns the mode indicator value in a Micro QR Code, which is version - 1 bits long.
+    fn micro_mode_bits(self) -> u32 {
+        use QrSegmentMode::*;
+        match self {
+            Numeric => 0,
+            Alphanumeric => 1,
+            Byte => 2,
+            Kanji => 3,
+            Eci => panic!("ECI isn't available in Micro QR Codes"),
+        }
+    }
+
+    // Returns the bit width of the character count field in a Micro QR Code of the given version.
+    fn num_micro_char_count_bits(self, ver: MicroVersion) -> u8 {
+        use QrSegmentMode::*;
+        ver.value()
+            + match self {
+                Numeric => 2,
+                Alphanumeric | Byte => 1,
+                Kanji | Eci => 0,
+            }
+    }
+
     // Returns the bit width of the character count field for a segment in this mode
     // in a QR Code at the given version number. The result is in the range [0, 16].
-    fn num_char_count_bits(self, ver: Version) -> u8 {
+    pub(crate) fn num_char_count_bits(self, ver: Version) -> u8 {
         use QrSegmentMode::*;
         (match self {
             Numeric => [10, 12, 14],
@@ -1399,6 +1703,68 @@ impl Version {
     }
 }
 
+/// A Micro QR Code version number between 1 and 4 (inclusive), M1 to M4.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct MicroVersion(u8);
+
+impl MicroVersion {
+    /// The smallest Micro QR Code, M1.
+    pub const MIN: MicroVersion = MicroVersion(1);
+
+    /// The largest Micro QR Code, M4.
+    pub const MAX: MicroVersion = MicroVersion(4);
+
+    /// Creates a version object from the given number.
+    ///
+    /// Panics if the number is outside the range [1, 4].
+    pub const fn new(ver: u8) -> Self {
+        assert!(1 <= ver && ver <= 4, "Version number out of range");
+        Self(ver)
+    }
+
+    /// Returns the value, which is in the range [1, 4].
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    // Returns the width and height in modules.
+    fn size(self) -> u8 {
+        self.0 * 2 + 9
+    }
+
+    // Returns the number of data bits at the given error correction level, None if the version doesn't
+    // support that level. M1 only detects errors, it's used for the low level.
+    fn num_data_bits(self, ecl: QrCodeEcc) -> Option<usize> {
+        use QrCodeEcc::*;
+        match (self.0, ecl) {
+            (1, Low) => Some(20),
+            (2, Low) => Some(40),
+            (2, Medium) => Some(32),
+            (3, Low) => Some(84),
+            (3, Medium) => Some(68),
+            (4, Low) => Some(128),
+            (4, Medium) => Some(112),
+            (4, Quartile) => Some(80),
+            _ => None,
+        }
+    }
+
+    // Returns the number of error correction codewords, the symbols have a single block.
+    fn num_ecc_codewords(self, ecl: QrCodeEcc) -> usize {
+        let total: usize = [5, 10, 17, 24][usize::from(self.0 - 1)];
+        total - self.num_data_bits(ecl).unwrap().div_ceil(8)
+    }
+
+    // Returns the 3-bit symbol number in the format information.
+    fn symbol_number(self, ecl: QrCodeEcc) -> u32 {
+        let first = [0, 1, 3, 5][usize::from(self.0 - 1)];
+        match self.0 {
+            1 => first,
+            _ => first + ecl.ordinal() as u32,
+        }
+    }
+}
+
 /// A number between 0 and 7 (inclusive).
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Mask(u8);
@@ -1422,3 +1788,115 @@ impl Mask {
 fn get_bit(x: u32, i: u8) -> bool {
     (x >> i) & 1 != 0
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Unmasks the symbol and reads the codeword bits back in placement order
+    fn read_bits(code: &MicroQrCode) -> Vec<bool> {
+        let mut unmasked = code.clone();
+        unmasked.apply_mask(code.mask);
+        let size = code.size();
+        let mut bits = Vec::new();
+        let mut upward = true;
+        for right in (1..size).rev().step_by(2) {
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if !code.isfunction[(y * size + x) as usize] {
+                        bits.push(unmasked.get_module(x, y));
+                    }
+                }
+            }
+            upward = !upward;
+        }
+        bits
+    }
+
+    fn bytes(bits: &[bool]) -> Vec<u8> {
+        bits.chunks(8).map(|byte| byte.iter().fold(0, |acc, &b| acc << 1 | u8::from(b))).collect()
+    }
+
+    #[test]
+    fn micro_codewords() {
+        // Example from ISO/IEC 18004 Annex I, M2-L
+        let code = MicroQrCode::encode_text("01234567", QrCodeEcc::Low, None, false).unwrap();
+        assert_eq!((code.version(), code.size()), (MicroVersion::new(2), 13));
+        assert_eq!(code.error_correction_level(), Some(QrCodeEcc::Low));
+        let bits = read_bits(&code);
+        assert_eq!(bits.len(), 80);
+        assert_eq!(bytes(&bits), [0x40, 0x18, 0xAC, 0xC3, 0x00, 0x86, 0x0D, 0x22, 0xAE, 0x30]);
+
+        // M1 ends in a 4 bit data codeword
+        let code = MicroQrCode::encode_text("123", QrCodeEcc::Low, None, false).unwrap();
+        assert_eq!(code.version(), MicroVersion::MIN);
+        assert_eq!(code.error_correction_level(), None);
+        let bits = read_bits(&code);
+        assert_eq!(bits.len(), 36);
+        let mut data = bytes(&bits[..16]);
+        data.push(bytes(&bits[16..20])[0] << 4);
+        // 0 bits mode, 011 count, 0001111011 digits, 000 terminator and zero padding
+        assert_eq!(data, [0b0110_0011, 0b1101_1000, 0]);
+        let mut ecc = [0u8; 2];
+        ReedSolomonGenerator::new(2).compute_remainder(&data, &mut ecc);
+        assert_eq!(bytes(&bits[20..]), ecc);
+    }
+
+    #[test]
+    fn micro_format() {
+        // Symbol number and mask for M1, M2-M and M4-Q
+        let table = [(0, 0x4445), (2, 0x6793), (7, 0x34e3)];
+        let cases = [("1", QrCodeEcc::Low), ("A", QrCodeEcc::Medium), ("ABCDEFGHIJKL", QrCodeEcc::Quartile)];
+        for ((text, ecl), (number, format)) in cases.into_iter().zip(table) {
+            let code = MicroQrCode::encode_text(text, ecl, Some(Mask::new(0)), false).unwrap();
+            assert_eq!(code.version.symbol_number(code.errorcorrectionlevel), number);
+            let mut bits: u32 = 0;
+            for i in 0..8 {
+                bits |= u32::from(code.get_module(8, i + 1)) << i;
+            }
+            for i in 8..15 {
+                bits |= u32::from(code.get_module(15 - i, 8)) << i;
+            }
+            assert_eq!(bits, format, "{text}");
+        }
+    }
+
+    #[test]
+    fn micro_versions() {
+        let version = |text: &str, ecl| MicroQrCode::encode_text(text, ecl, None, false).map(|c| c.version().value());
+        assert_eq!(version("12345", QrCodeEcc::Low).unwrap(), 1);
+        assert_eq!(version("123456", QrCodeEcc::Low).unwrap(), 2);
+        assert_eq!(version("123456", QrCodeEcc::Medium).unwrap(), 2);
+        assert_eq!(version("AB-1", QrCodeEcc::Low).unwrap(), 2);
+        assert_eq!(version("ab", QrCodeEcc::Low).unwrap(), 3);
+        assert_eq!(version(&"1".repeat(35), QrCodeEcc::Low).unwrap(), 4);
+        assert!(version(&"1".repeat(36), QrCodeEcc::Low).is_err());
+        assert!(version("1", QrCodeEcc::High).is_err());
+
+        let mut buffer = [0u8; 3];
+        let eci = QrSegment::make_eci(26, &mut buffer);
+        assert!(MicroQrCode::encode_segments(&[eci], QrCodeEcc::Low, None, false).is_err());
+
+        let boosted = MicroQrCode::encode_text("A", QrCodeEcc::Low, None, true).unwrap();
+        assert_eq!(boosted.error_correction_level(), Some(QrCodeEcc::Medium));
+    }
+
+    #[test]
+    fn micro_mask_and_finder() {
+        let code = MicroQrCode::encode_text("HELLO", QrCodeEcc::Low, None, false).unwrap();
+        let best = (0..4)
+            .map(|m| MicroQrCode::encode_text("HELLO", QrCodeEcc::Low, Some(Mask::new(m)), false).unwrap())
+            .map(|c| c.get_score())
+            .max()
+            .unwrap();
+        assert_eq!(code.get_score(), best);
+
+        // Finder pattern, separator and timing pattern along the top edge of M2
+        assert_eq!(code.size(), 13);
+        let row: Vec<bool> = (0..code.size()).map(|x| code.get_module(x, 0)).collect();
+        let expected = [true, true, true, true, true, true, true, false, true, false, true, false, true];
+        assert_eq!(row, expected);
+        assert!(code.get_module(3, 3) && !code.get_module(1, 1) && !code.get_module(7, 7));
+    }
+}
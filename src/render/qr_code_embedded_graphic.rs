@@ -1,4 +1,5 @@
-use crate::render::qr_code::QrCode;
+use crate::render::matrix::{MatrixSymbol, StyledMatrix};
+use crate::render::qr_code::{MicroQrCode, QrCode};
 use embedded_graphics::{
     self,
     draw_target::DrawTarget,
@@ -75,7 +76,11 @@ impl<'a> Drawable for StyledQrCode<'a> {
     where
         D: DrawTarget<Color = Self::Color>,
     {
-        target.draw_iter(StyledQrCodeIterator::new(&self))
+        // The display grows along x, so only clip negative coordinates there
+        let height = target.bounding_box().size.height as i32;
+        target.draw_iter(
+            StyledQrCodeIterator::new(self).filter(|Pixel(p, _)| p.x >= 0 && p.y >= 0 && p.y < height),
+        )
     }
 }
 
@@ -185,3 +190,19 @@ impl<'a> View for StyledQrCode<'a> {
         self.bounding_box()
     }
 }
+
+impl MatrixSymbol for MicroQrCode {
+    fn modules(&self) -> Size {
+        Size::new(self.size() as u32, self.size() as u32)
+    }
+
+    fn get_module(&self, x: i32, y: i32) -> bool {
+        MicroQrCode::get_module(self, x, y)
+    }
+
+    fn quiet_zone(&self) -> u32 {
+        2
+    }
+}
+
+pub type StyledMicroQrCode = StyledMatrix<MicroQrCode>;
@@ -0,0 +1,553 @@
+//! Structured QR code payloads: Wi-Fi credentials, contacts, locations, e-mail and GS1 Digital Link URIs
+use crate::render::barcode::{parse_gs1, BarcodeError};
+use crate::render::ean::check_digit;
+
+/// Invalid input for a payload builder
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadError {
+    /// A required field is empty
+    Missing(&'static str),
+    /// A field has a value the format doesn't allow
+    Invalid { field: &'static str, reason: String },
+    /// Malformed GS1 element string
+    Gs1(BarcodeError),
+}
+
+impl PayloadError {
+    fn invalid(field: &'static str, reason: impl Into<String>) -> Self {
+        PayloadError::Invalid { field, reason: reason.into() }
+    }
+}
+
+impl std::fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::Missing(field) => write!(f, "Missing {field}"),
+            PayloadError::Invalid { field, reason } => write!(f, "Invalid {field}: {reason}"),
+            PayloadError::Gs1(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PayloadError::Gs1(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<BarcodeError> for PayloadError {
+    fn from(barcode_error: BarcodeError) -> Self {
+        PayloadError::Gs1(barcode_error)
+    }
+}
+
+/// Data with a well known text representation that QR code readers act on
+pub trait QrPayload {
+    /// Validate the fields and build the text to encode
+    fn payload(&self) -> Result<String, PayloadError>;
+}
+
+/// Prefix `\` and every character of `special` with a backslash
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Percent-encode everything but the URI unreserved characters
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+fn validate_email(field: &'static str, address: &str) -> Result<(), PayloadError> {
+    match address.split_once('@') {
+        _ if address.chars().any(|c| c.is_whitespace() || c.is_control()) => {
+            Err(PayloadError::invalid(field, format!("{address:?} contains white space")))
+        }
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() && !domain.contains('@') => Ok(()),
+        _ => Err(PayloadError::invalid(field, format!("{address:?} is not an e-mail address"))),
+    }
+}
+
+fn validate_phone(field: &'static str, number: &str) -> Result<(), PayloadError> {
+    let valid = |c: char| c.is_ascii_digit() || " +-()./".contains(c);
+    match number.chars().find(|&c| !valid(c)) {
+        Some(c) => Err(PayloadError::invalid(field, format!("{c:?} in phone number {number:?}"))),
+        None if !number.chars().any(|c| c.is_ascii_digit()) => Err(PayloadError::Missing(field)),
+        None => Ok(()),
+    }
+}
+
+/// Wi-Fi authentication type
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WifiSecurity {
+    /// WPA / WPA2 personal
+    #[default]
+    Wpa,
+    /// WPA3 personal
+    Sae,
+    Wep,
+    /// Open network without password
+    Open,
+}
+
+/// Wi-Fi network credentials, `WIFI:T:WPA;S:ssid;P:password;;`
+#[derive(Debug, Clone, Default)]
+pub struct Wifi {
+    pub ssid: String,
+    pub security: WifiSecurity,
+    pub password: String,
+    /// The network doesn't broadcast its SSID
+    pub hidden: bool,
+}
+
+impl Wifi {
+    pub fn new(ssid: &str, security: WifiSecurity, password: &str) -> Self {
+        Self { ssid: ssid.to_string(), security, password: password.to_string(), hidden: false }
+    }
+
+    fn validate_password(&self) -> Result<(), PayloadError> {
+        let len = self.password.len();
+        let hex = self.password.bytes().all(|b| b.is_ascii_hexdigit());
+        let printable = self.password.bytes().all(|b| (0x20..0x7f).contains(&b));
+        let reason = match self.security {
+            WifiSecurity::Open if len > 0 => "an open network has no password",
+            WifiSecurity::Open => return Ok(()),
+            _ if len == 0 => return Err(PayloadError::Missing("password")),
+            WifiSecurity::Wpa | WifiSecurity::Sae if (8..=63).contains(&len) && printable => return Ok(()),
+            WifiSecurity::Wpa | WifiSecurity::Sae if len == 64 && hex => return Ok(()),
+            WifiSecurity::Wpa | WifiSecurity::Sae => "expected 8 to 63 printable ASCII characters or 64 hex digits",
+            WifiSecurity::Wep if matches!(len, 5 | 13) && printable => return Ok(()),
+            WifiSecurity::Wep if matches!(len, 10 | 26) && hex => return Ok(()),
+            WifiSecurity::Wep => "expected 5 or 13 ASCII characters or 10 or 26 hex digits",
+        };
+        Err(PayloadError::invalid("password", reason))
+    }
+}
+
+impl QrPayload for Wifi {
+    fn payload(&self) -> Result<String, PayloadError> {
+        if self.ssid.is_empty() {
+            return Err(PayloadError::Missing("SSID"));
+        }
+        if self.ssid.len() > 32 {
+            return Err(PayloadError::invalid("SSID", "longer than 32 bytes"));
+        }
+        self.validate_password()?;
+
+        const SPECIAL: &str = ";,:\"";
+        let security = match self.security {
+            WifiSecurity::Wpa => "WPA",
+            WifiSecurity::Sae => "SAE",
+            WifiSecurity::Wep => "WEP",
+            WifiSecurity::Open => "nopass",
+        };
+        let mut payload = format!("WIFI:T:{security};S:{};", escape(&self.ssid, SPECIAL));
+        if self.security != WifiSecurity::Open {
+            payload += &format!("P:{};", escape(&self.password, SPECIAL));
+        }
+        if self.hidden {
+            payload += "H:true;";
+        }
+        payload.push(';');
+        Ok(payload)
+    }
+}
+
+/// Contact card format
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ContactFormat {
+    /// vCard 3.0, understood by every reader
+    #[default]
+    VCard,
+    /// MeCard, shorter so it gives smaller symbols
+    MeCard,
+}
+
+/// A business card, encoded as vCard or MeCard
+#[derive(Debug, Clone, Default)]
+pub struct Contact {
+    pub format: ContactFormat,
+    pub first_name: String,
+    pub last_name: String,
+    pub organization: Option<String>,
+    pub title: Option<String>,
+    pub phones: Vec<String>,
+    pub emails: Vec<String>,
+    pub url: Option<String>,
+    /// Postal address, lines separated by `\n`
+    pub address: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Contact {
+    pub fn new(first_name: &str, last_name: &str) -> Self {
+        Self { first_name: first_name.to_string(), last_name: last_name.to_string(), ..Default::default() }
+    }
+
+    pub fn with_format(mut self, format: ContactFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn validate(&self) -> Result<(), PayloadError> {
+        if self.first_name.trim().is_empty() && self.last_name.trim().is_empty() {
+            return Err(PayloadError::Missing("name"));
+        }
+        self.phones.iter().try_for_each(|phone| validate_phone("phone", phone))?;
+        self.emails.iter().try_for_each(|email| validate_email("email", email))?;
+        match &self.url {
+            Some(url) if url.chars().any(char::is_whitespace) => Err(PayloadError::invalid("url", "contains white space")),
+            _ => Ok(()),
+        }
+    }
+
+    fn vcard(&self) -> String {
+        let value = |v: &str| escape(v, ";,").replace('\n', "\\n");
+        let full_name = format!("{} {}", self.first_name.trim(), self.last_name.trim());
+        let mut lines = vec![
+            "BEGIN:VCARD".to_string(),
+            "VERSION:3.0".to_string(),
+            format!("N:{};{};;;", value(self.last_name.trim()), value(self.first_name.trim())),
+            format!("FN:{}", value(full_name.trim())),
+        ];
+        let mut optional = |name: &str, field: &Option<String>| {
+            if let Some(v) = field.as_deref().filter(|v| !v.is_empty()) {
+                lines.push(format!("{name}:{}", value(v)));
+            }
+        };
+        optional("ORG", &self.organization);
+        optional("TITLE", &self.title);
+        lines.extend(self.phones.iter().map(|phone| format!("TEL:{}", value(phone))));
+        lines.extend(self.emails.iter().map(|email| format!("EMAIL:{}", value(email))));
+        if let Some(url) = &self.url {
+            lines.push(format!("URL:{}", value(url)));
+        }
+        if let Some(address) = self.address.as_deref().filter(|v| !v.is_empty()) {
+            lines.push(format!("ADR:;;{};;;;", value(address)));
+        }
+        if let Some(note) = self.note.as_deref().filter(|v| !v.is_empty()) {
+            lines.push(format!("NOTE:{}", value(note)));
+        }
+        lines.push("END:VCARD".to_string());
+        lines.join("\r\n")
+    }
+
+    fn mecard(&self) -> String {
+        let value = |v: &str| escape(v, ";,:").replace('\n', " ");
+        let name = match (self.last_name.trim(), self.first_name.trim()) {
+            (last, "") => value(last),
+            ("", first) => value(first),
+            (last, first) => format!("{},{}", value(last), value(first)),
+        };
+        let mut fields = vec![format!("N:{name}")];
+        fields.extend(self.organization.iter().map(|org| format!("ORG:{}", value(org))));
+        fields.extend(self.phones.iter().map(|phone| format!("TEL:{}", value(phone))));
+        fields.extend(self.emails.iter().map(|email| format!("EMAIL:{}", value(email))));
+        fields.extend(self.url.iter().map(|url| format!("URL:{}", value(url))));
+        fields.extend(self.address.iter().map(|address| format!("ADR:{}", value(address))));
+        fields.extend(self.note.iter().map(|note| format!("NOTE:{}", value(note))));
+        format!("MECARD:{};;", fields.join(";"))
+    }
+}
+
+impl QrPayload for Contact {
+    fn payload(&self) -> Result<String, PayloadError> {
+        self.validate()?;
+        Ok(match self.format {
+            ContactFormat::VCard => self.vcard(),
+            ContactFormat::MeCard => self.mecard(),
+        })
+    }
+}
+
+/// A web address, only `http` and `https` are accepted
+#[derive(Debug, Clone)]
+pub struct Url(pub String);
+
+impl QrPayload for Url {
+    fn payload(&self) -> Result<String, PayloadError> {
+        let (scheme, rest) = self.0.split_once("://").ok_or(PayloadError::invalid("url", "missing scheme"))?;
+        if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+            return Err(PayloadError::invalid("url", format!("unsupported scheme {scheme:?}")));
+        }
+        if rest.is_empty() || rest.starts_with('/') {
+            return Err(PayloadError::Missing("host"));
+        }
+        if self.0.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(PayloadError::invalid("url", "contains white space"));
+        }
+        Ok(self.0.clone())
+    }
+}
+
+/// A location in WGS 84 degrees, `geo:latitude,longitude`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Geo {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Metres above sea level
+    pub altitude: Option<f64>,
+}
+
+impl Geo {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self { latitude, longitude, altitude: None }
+    }
+}
+
+impl QrPayload for Geo {
+    fn payload(&self) -> Result<String, PayloadError> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(PayloadError::invalid("latitude", format!("{} is outside -90..90", self.latitude)));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(PayloadError::invalid("longitude", format!("{} is outside -180..180", self.longitude)));
+        }
+        Ok(match self.altitude {
+            Some(altitude) if altitude.is_finite() => format!("geo:{},{},{altitude}", self.latitude, self.longitude),
+            Some(altitude) => return Err(PayloadError::invalid("altitude", format!("{altitude}"))),
+            None => format!("geo:{},{}", self.latitude, self.longitude),
+        })
+    }
+}
+
+/// An e-mail to be composed, `mailto:to?subject=...&body=...`
+#[derive(Debug, Clone, Default)]
+pub struct Mailto {
+    pub to: Vec<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+impl Mailto {
+    pub fn new(to: &str) -> Self {
+        Self { to: vec![to.to_string()], ..Default::default() }
+    }
+}
+
+impl QrPayload for Mailto {
+    fn payload(&self) -> Result<String, PayloadError> {
+        if self.to.is_empty() {
+            return Err(PayloadError::Missing("recipient"));
+        }
+        self.to.iter().try_for_each(|address| validate_email("recipient", address))?;
+
+        let to: Vec<String> = self
+            .to
+            .iter()
+            .map(|address| address.split('@').map(percent_encode).collect::<Vec<_>>().join("@"))
+            .collect();
+        // Line breaks in the body must be CRLF
+        let header = |name: &str, value: &String| {
+            format!("{name}={}", percent_encode(&value.replace("\r\n", "\n").replace('\n', "\r\n")))
+        };
+        let headers: Vec<String> = [("subject", &self.subject), ("body", &self.body)]
+            .into_iter()
+            .filter_map(|(name, value)| value.as_ref().map(|v| header(name, v)))
+            .collect();
+
+        let mut payload = format!("mailto:{}", to.join(","));
+        if !headers.is_empty() {
+            payload = format!("{payload}?{}", headers.join("&"));
+        }
+        Ok(payload)
+    }
+}
+
+/// GS1 primary keys with the key qualifiers allowed in the URI path, in path order
+const GS1_PRIMARY_KEYS: [(&str, &[&str]); 12] = [
+    ("01", &["22", "10", "21"]),
+    ("8006", &["22", "10", "21"]),
+    ("8010", &["8011"]),
+    ("414", &["254"]),
+    ("00", &[]),
+    ("253", &[]),
+    ("255", &[]),
+    ("401", &[]),
+    ("402", &[]),
+    ("417", &[]),
+    ("8003", &[]),
+    ("8004", &[]),
+];
+
+/// Resolver used when no domain is given
+pub const GS1_RESOLVER: &str = "https://id.gs1.org";
+
+/// A GS1 Digital Link URI built from an element string like `(01)09506000134352(10)ABC123(17)261231`.
+/// The primary key and its qualifiers form the path, the other elements the query.
+#[derive(Debug, Clone)]
+pub struct Gs1DigitalLink {
+    /// Scheme and host of the resolver, e.g. `https://example.com`
+    pub domain: String,
+    pub elements: String,
+}
+
+impl Gs1DigitalLink {
+    pub fn new(elements: &str) -> Self {
+        Self { domain: GS1_RESOLVER.to_string(), elements: elements.to_string() }
+    }
+
+    pub fn with_domain(mut self, domain: &str) -> Self {
+        self.domain = domain.to_string();
+        self
+    }
+}
+
+/// Pad a GTIN-8, -12 or -13 to 14 digits and verify its check digit
+fn gtin14(value: &str) -> Result<String, PayloadError> {
+    if !matches!(value.len(), 8 | 12 | 13 | 14) || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(PayloadError::invalid("GTIN", format!("{value:?} is not 8, 12, 13 or 14 digits")));
+    }
+    let gtin = format!("{value:0>14}");
+    let digits: Vec<u8> = gtin.bytes().map(|b| b - b'0').collect();
+    let expected = check_digit(&digits[..13]);
+    match digits[13] == expected {
+        true => Ok(gtin),
+        false => Err(BarcodeError::InvalidCheckDigit { expected, found: digits[13] }.into()),
+    }
+}
+
+impl QrPayload for Gs1DigitalLink {
+    fn payload(&self) -> Result<String, PayloadError> {
+        let domain = self.domain.trim_end_matches('/');
+        Url(domain.to_string()).payload().map_err(|_| PayloadError::invalid("domain", format!("{domain:?}")))?;
+
+        let elements = parse_gs1(&self.elements)?;
+        let mut keys = GS1_PRIMARY_KEYS.iter().filter(|(key, _)| elements.iter().any(|(ai, _)| ai == key));
+        let (key, qualifiers) = keys.next().ok_or(PayloadError::Missing("GS1 primary key"))?;
+        if let Some((other, _)) = keys.next() {
+            return Err(PayloadError::invalid("GS1 primary key", format!("both ({key}) and ({other})")));
+        }
+
+        let mut path = String::new();
+        let mut query = Vec::new();
+        for ai in std::iter::once(key).chain(qualifiers.iter()) {
+            if let Some((_, value)) = elements.iter().find(|(a, _)| a == ai) {
+                let value = match *ai {
+                    "01" => gtin14(value)?,
+                    _ => percent_encode(value),
+                };
+                path += &format!("/{ai}/{value}");
+            }
+        }
+        for (ai, value) in &elements {
+            if ai == key || qualifiers.contains(ai) {
+                continue;
+            }
+            query.push(format!("{ai}={}", percent_encode(value)));
+        }
+
+        Ok(match query.is_empty() {
+            true => format!("{domain}{path}"),
+            false => format!("{domain}{path}?{}", query.join("&")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wifi() {
+        let wifi = Wifi::new("Caf\u{e9};Guest", WifiSecurity::Wpa, "pa:ss\\w\"rd");
+        assert_eq!(wifi.payload().unwrap(), "WIFI:T:WPA;S:Caf\u{e9}\\;Guest;P:pa\\:ss\\\\w\\\"rd;;");
+
+        let hidden = Wifi { hidden: true, ..Wifi::new("lab", WifiSecurity::Open, "") };
+        assert_eq!(hidden.payload().unwrap(), "WIFI:T:nopass;S:lab;H:true;;");
+
+        assert_eq!(Wifi::new("", WifiSecurity::Wpa, "password").payload(), Err(PayloadError::Missing("SSID")));
+        assert!(matches!(
+            Wifi::new("lab", WifiSecurity::Wpa, "short").payload(),
+            Err(PayloadError::Invalid { field: "password", .. })
+        ));
+        assert!(Wifi::new("lab", WifiSecurity::Wep, "0123456789").payload().is_ok());
+        assert!(Wifi::new("lab", WifiSecurity::Open, "secret").payload().is_err());
+    }
+
+    #[test]
+    fn contact() {
+        let contact = Contact {
+            organization: Some("Smith, Jones & Co".into()),
+            phones: vec!["+31 20 123 4567".into()],
+            emails: vec!["jane@example.com".into()],
+            address: Some("Main Street 1\n1234 AB Town".into()),
+            ..Contact::new("Jane", "Doe")
+        };
+        assert_eq!(
+            contact.payload().unwrap(),
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;Jane;;;\r\nFN:Jane Doe\r\nORG:Smith\\, Jones & Co\r\n\
+             TEL:+31 20 123 4567\r\nEMAIL:jane@example.com\r\nADR:;;Main Street 1\\n1234 AB Town;;;;\r\nEND:VCARD"
+        );
+        assert_eq!(
+            contact.with_format(ContactFormat::MeCard).payload().unwrap(),
+            "MECARD:N:Doe,Jane;ORG:Smith\\, Jones & Co;TEL:+31 20 123 4567;EMAIL:jane@example.com;\
+             ADR:Main Street 1 1234 AB Town;;"
+        );
+
+        assert_eq!(Contact::default().payload(), Err(PayloadError::Missing("name")));
+        let contact = Contact { emails: vec!["jane.example.com".into()], ..Contact::new("Jane", "") };
+        assert!(matches!(contact.payload(), Err(PayloadError::Invalid { field: "email", .. })));
+        let contact = Contact { phones: vec!["call me".into()], ..Contact::new("Jane", "") };
+        assert!(matches!(contact.payload(), Err(PayloadError::Invalid { field: "phone", .. })));
+    }
+
+    #[test]
+    fn url_geo_mailto() {
+        assert_eq!(Url("https://example.com/a?b=c".into()).payload().unwrap(), "https://example.com/a?b=c");
+        assert!(Url("ftp://example.com".into()).payload().is_err());
+        assert!(Url("https://exa mple.com".into()).payload().is_err());
+
+        assert_eq!(Geo::new(52.3676, 4.9041).payload().unwrap(), "geo:52.3676,4.9041");
+        assert_eq!(Geo { altitude: Some(-2.5), ..Geo::new(-33.5, 0.0) }.payload().unwrap(), "geo:-33.5,0,-2.5");
+        assert!(matches!(Geo::new(91.0, 0.0).payload(), Err(PayloadError::Invalid { field: "latitude", .. })));
+        assert!(Geo::new(0.0, f64::NAN).payload().is_err());
+
+        let mail = Mailto {
+            subject: Some("Order #12 & more".into()),
+            body: Some("Hi,\nthanks".into()),
+            ..Mailto::new("sales+nl@example.com")
+        };
+        assert_eq!(
+            mail.payload().unwrap(),
+            "mailto:sales%2Bnl@example.com?subject=Order%20%2312%20%26%20more&body=Hi%2C%0D%0Athanks"
+        );
+        assert_eq!(Mailto::default().payload(), Err(PayloadError::Missing("recipient")));
+    }
+
+    #[test]
+    fn gs1_digital_link() {
+        let link = Gs1DigitalLink::new("(17)261231(10)AB/12(01)09506000134352(21)X 1");
+        assert_eq!(
+            link.payload().unwrap(),
+            "https://id.gs1.org/01/09506000134352/10/AB%2F12/21/X%201?17=261231"
+        );
+        let link = Gs1DigitalLink::new("(01)4006381333931").with_domain("https://example.com/");
+        assert_eq!(link.payload().unwrap(), "https://example.com/01/04006381333931");
+        assert_eq!(Gs1DigitalLink::new("(00)106141411234567897").payload().unwrap(), "https://id.gs1.org/00/106141411234567897");
+
+        assert_eq!(
+            Gs1DigitalLink::new("(01)09506000134353").payload(),
+            Err(PayloadError::Gs1(BarcodeError::InvalidCheckDigit { expected: 2, found: 3 }))
+        );
+        assert_eq!(Gs1DigitalLink::new("(10)ABC").payload(), Err(PayloadError::Missing("GS1 primary key")));
+        assert!(Gs1DigitalLink::new("(01)09506000134352(00)106141411234567897").payload().is_err());
+        assert!(matches!(Gs1DigitalLink::new("01 0950").payload(), Err(PayloadError::Gs1(_))));
+    }
+}
@@ -0,0 +1,330 @@
+//! QR code segments: mixed encoding modes, ECI and Kanji, and a segmentation optimiser
+use encoding_rs::SHIFT_JIS;
+
+use crate::render::barcode::BarcodeError;
+use crate::render::qr_code::{BitBuffer, DataTooLong, QrCode, QrSegment, QrSegmentMode};
+use crate::render::{QrCodeEcc, Version};
+
+/// ECI assignment number for UTF-8
+pub const ECI_UTF8: u32 = 26;
+
+const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Versions sharing the same character count field widths
+const VERSION_CLASSES: [(u8, u8); 3] = [(1, 9), (10, 26), (27, 40)];
+
+/// A run of data in a single encoding mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Digits, 10 bits per 3 characters
+    Numeric(String),
+    /// `0-9`, `A-Z`, space and `$%*+-./:`, 11 bits per 2 characters
+    Alphanumeric(String),
+    /// Raw bytes, 8 bits each
+    Bytes(Vec<u8>),
+    /// Double byte Shift JIS characters, 13 bits each
+    Kanji(String),
+    /// Extended Channel Interpretation, the character set of the following byte segments
+    Eci(u32),
+}
+
+impl Segment {
+    pub fn numeric(text: &str) -> Result<Self, BarcodeError> {
+        let segment = Segment::Numeric(text.to_string());
+        segment.validate().map(|_| segment)
+    }
+
+    pub fn alphanumeric(text: &str) -> Result<Self, BarcodeError> {
+        let segment = Segment::Alphanumeric(text.to_string());
+        segment.validate().map(|_| segment)
+    }
+
+    pub fn bytes(data: impl Into<Vec<u8>>) -> Self {
+        Segment::Bytes(data.into())
+    }
+
+    pub fn kanji(text: &str) -> Result<Self, BarcodeError> {
+        let segment = Segment::Kanji(text.to_string());
+        segment.validate().map(|_| segment)
+    }
+
+    pub fn eci(assignment: u32) -> Result<Self, BarcodeError> {
+        let segment = Segment::Eci(assignment);
+        segment.validate().map(|_| segment)
+    }
+
+    /// UTF-8 text tagged with its ECI, for readers that default to ISO 8859-1
+    pub fn utf8(text: &str) -> [Self; 2] {
+        [Segment::Eci(ECI_UTF8), Segment::bytes(text)]
+    }
+
+    /// Check that every character can be encoded in the segment's mode
+    pub fn validate(&self) -> Result<(), BarcodeError> {
+        let invalid = |text: &str, valid: &dyn Fn(char) -> bool| match text.chars().find(|&c| !valid(c)) {
+            Some(c) => Err(BarcodeError::InvalidCharacter(c)),
+            None => Ok(()),
+        };
+        match self {
+            Segment::Numeric(text) => invalid(text, &|c| c.is_ascii_digit()),
+            Segment::Alphanumeric(text) => invalid(text, &|c| ALPHANUMERIC.contains(c)),
+            Segment::Kanji(text) => invalid(text, &|c| kanji_value(c).is_some()),
+            Segment::Bytes(_) => Ok(()),
+            Segment::Eci(assignment) if *assignment >= 1_000_000 => Err(BarcodeError::InvalidEci(*assignment)),
+            Segment::Eci(_) => Ok(()),
+        }
+    }
+
+    fn mode(&self) -> QrSegmentMode {
+        match self {
+            Segment::Numeric(_) => QrSegmentMode::Numeric,
+            Segment::Alphanumeric(_) => QrSegmentMode::Alphanumeric,
+            Segment::Bytes(_) => QrSegmentMode::Byte,
+            Segment::Kanji(_) => QrSegmentMode::Kanji,
+            Segment::Eci(_) => QrSegmentMode::Eci,
+        }
+    }
+
+    /// Number of characters (bytes for byte mode) in the character count field
+    fn num_chars(&self) -> usize {
+        match self {
+            Segment::Numeric(text) | Segment::Alphanumeric(text) => text.len(),
+            Segment::Kanji(text) => text.chars().count(),
+            Segment::Bytes(data) => data.len(),
+            Segment::Eci(_) => 0,
+        }
+    }
+
+    /// Bits in a symbol of `version`, including mode indicator and character count
+    pub fn bit_len(&self, version: Version) -> usize {
+        let n = self.num_chars();
+        let data = match self {
+            Segment::Numeric(_) => n / 3 * 10 + [0, 4, 7][n % 3],
+            Segment::Alphanumeric(_) => n / 2 * 11 + n % 2 * 6,
+            Segment::Bytes(_) => n * 8,
+            Segment::Kanji(_) => n * 13,
+            Segment::Eci(assignment) => match assignment {
+                0..=127 => 8,
+                128..=16383 => 16,
+                _ => 24,
+            },
+        };
+        4 + self.mode().num_char_count_bits(version) as usize + data
+    }
+}
+
+/// 13 bit Kanji mode value of a character in the Shift JIS double byte ranges
+fn kanji_value(c: char) -> Option<u16> {
+    let mut utf8 = [0u8; 4];
+    let (bytes, _, errors) = SHIFT_JIS.encode(c.encode_utf8(&mut utf8));
+    let [high, low] = *bytes else { return None };
+    if errors {
+        return None;
+    }
+    let offset = match u16::from_be_bytes([high, low]) {
+        code @ 0x8140..=0x9ffc => code - 0x8140,
+        code @ 0xe040..=0xebbf => code - 0xc140,
+        _ => return None,
+    };
+    Some((offset >> 8) * 0xc0 + (offset & 0xff))
+}
+
+/// Segmentation of `text` with the fewest bits in symbols of `version`'s size class.
+/// Non-ASCII text in byte mode is encoded as UTF-8 and tagged with its ECI.
+pub fn optimal_segments(text: &str, version: Version) -> Vec<Segment> {
+    const MODES: [QrSegmentMode; 4] =
+        [QrSegmentMode::Byte, QrSegmentMode::Alphanumeric, QrSegmentMode::Numeric, QrSegmentMode::Kanji];
+
+    // Costs are in 1/6 bit so numeric (10/3) and alphanumeric (11/2) characters are whole numbers
+    let head = MODES.map(|m| (4 + m.num_char_count_bits(version) as usize) * 6);
+    let cost = |mode: QrSegmentMode, c: char| match mode {
+        QrSegmentMode::Byte => Some(c.len_utf8() * 8 * 6),
+        QrSegmentMode::Alphanumeric => ALPHANUMERIC.contains(c).then_some(33),
+        QrSegmentMode::Numeric => c.is_ascii_digit().then_some(20),
+        _ => kanji_value(c).map(|_| 78),
+    };
+
+    let chars: Vec<char> = text.chars().collect();
+    // For every character and mode it ends in, the mode of that character
+    let mut modes: Vec<[Option<usize>; 4]> = Vec::with_capacity(chars.len());
+    let mut previous = head;
+    for &c in &chars {
+        let mut current = [usize::MAX; 4];
+        let mut from = [None; 4];
+        for (j, &mode) in MODES.iter().enumerate() {
+            if let Some(cost) = cost(mode, c) {
+                current[j] = previous[j] + cost;
+                from[j] = Some(j);
+            }
+        }
+        // Start a new segment after this character
+        let ended = current;
+        for (j, head) in head.iter().enumerate() {
+            for (k, &ended) in ended.iter().enumerate().filter(|(_, &cost)| cost < usize::MAX) {
+                let switched = ended.div_ceil(6) * 6 + head;
+                if switched < current[j] {
+                    current[j] = switched;
+                    from[j] = Some(k);
+                }
+            }
+        }
+        modes.push(from);
+        previous = current;
+    }
+
+    // Walk back from the cheapest final mode
+    let mut mode = (0..MODES.len()).min_by_key(|&j| previous[j]).unwrap_or(0);
+    let mut char_modes = vec![0; chars.len()];
+    for i in (0..chars.len()).rev() {
+        mode = modes[i][mode].unwrap_or(0);
+        char_modes[i] = mode;
+    }
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for i in 1..=chars.len() {
+        if i < chars.len() && char_modes[i] == char_modes[start] {
+            continue;
+        }
+        let run: String = chars[start..i].iter().collect();
+        segments.push(match MODES[char_modes[start]] {
+            QrSegmentMode::Byte => Segment::Bytes(run.into_bytes()),
+            QrSegmentMode::Alphanumeric => Segment::Alphanumeric(run),
+            QrSegmentMode::Numeric => Segment::Numeric(run),
+            _ => Segment::Kanji(run),
+        });
+        start = i;
+    }
+
+    let utf8 = segments.iter().any(|s| matches!(s, Segment::Bytes(data) if !data.is_ascii()));
+    if utf8 {
+        segments.insert(0, Segment::Eci(ECI_UTF8));
+    }
+    segments
+}
+
+/// Call `encode` with the `QrSegment`s of `segments`.
+/// Panics on segments that don't pass `Segment::validate`.
+pub(crate) fn with_qr_segments<R>(segments: &[Segment], encode: impl FnOnce(&[QrSegment]) -> R) -> R {
+    let mut buffers: Vec<Vec<u8>> = segments.iter().map(|s| vec![0u8; s.bit_len(Version::MAX).div_ceil(8)]).collect();
+    let segs: Vec<QrSegment> = segments
+        .iter()
+        .zip(buffers.iter_mut())
+        .map(|(segment, buffer)| match segment {
+            Segment::Numeric(text) => QrSegment::make_numeric(text, buffer),
+            Segment::Alphanumeric(text) => QrSegment::make_alphanumeric(text, buffer),
+            Segment::Bytes(data) => QrSegment::make_bytes(data),
+            Segment::Eci(assignment) => QrSegment::make_eci(*assignment, buffer),
+            Segment::Kanji(text) => {
+                let mut bits = BitBuffer::new(buffer);
+                text.chars().filter_map(kanji_value).for_each(|v| bits.append_bits(v.into(), 13));
+                let len = bits.len();
+                QrSegment::new(QrSegmentMode::Kanji, text.chars().count(), buffer, len)
+            }
+        })
+        .collect();
+    encode(&segs)
+}
+
+/// Data codewords for the smallest version in `min..=max`, see `QrCode::encode_segments_to_codewords`.
+/// Panics on segments that don't pass `Segment::validate`.
+pub(crate) fn encode_codewords(
+    segments: &[Segment],
+    outbuffer: &mut [u8],
+    ecc: QrCodeEcc,
+    min: Version,
+    max: Version,
+    boost_ecc: bool,
+) -> Result<(usize, QrCodeEcc, Version), DataTooLong> {
+    with_qr_segments(segments, |segs| {
+        QrCode::encode_segments_to_codewords(segs, outbuffer, ecc, min, max, boost_ecc)
+    })
+}
+
+/// Like `encode_codewords` with the optimal segmentation of `text` for each version size class
+pub(crate) fn encode_optimal(
+    text: &str,
+    outbuffer: &mut [u8],
+    ecc: QrCodeEcc,
+    min: Version,
+    max: Version,
+    boost_ecc: bool,
+) -> Result<(usize, QrCodeEcc, Version), DataTooLong> {
+    let mut result = Err(DataTooLong::SegmentTooLong);
+    for (first, last) in VERSION_CLASSES {
+        let (first, last) = (min.max(Version::new(first)), max.min(Version::new(last)));
+        if first > last {
+            continue;
+        }
+        let segments = optimal_segments(text, first);
+        result = encode_codewords(&segments, outbuffer, ecc, first, last, boost_ecc);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn data_bits(segment: &Segment) -> usize {
+        segment.bit_len(Version::MIN) - 4 - segment.mode().num_char_count_bits(Version::MIN) as usize
+    }
+
+    #[test]
+    fn segment_lengths() {
+        assert_eq!(data_bits(&Segment::numeric("01234567").unwrap()), 27);
+        assert_eq!(data_bits(&Segment::alphanumeric("AC-42").unwrap()), 28);
+        assert_eq!(data_bits(&Segment::bytes("é")), 16);
+        assert_eq!(Segment::Eci(ECI_UTF8).bit_len(Version::MIN), 12);
+        assert_eq!(Segment::numeric("1").unwrap().bit_len(Version::new(10)), 4 + 12 + 4);
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(Segment::numeric("12a"), Err(BarcodeError::InvalidCharacter('a')));
+        assert_eq!(Segment::alphanumeric("ab"), Err(BarcodeError::InvalidCharacter('a')));
+        assert_eq!(Segment::kanji("A"), Err(BarcodeError::InvalidCharacter('A')));
+        assert_eq!(Segment::eci(1_000_000), Err(BarcodeError::InvalidEci(1_000_000)));
+        assert!(Segment::eci(ECI_UTF8).is_ok());
+    }
+
+    #[test]
+    fn kanji_values() {
+        // Examples from ISO/IEC 18004
+        assert_eq!(kanji_value('点'), Some(0x0d9f));
+        assert_eq!(kanji_value('茗'), Some(0x1aaa));
+        assert_eq!(kanji_value('A'), None);
+        assert_eq!(kanji_value('€'), None);
+    }
+
+    #[test]
+    fn optimiser() {
+        let segments = optimal_segments("PN-0123456789012345", Version::MIN);
+        assert_eq!(
+            segments,
+            [Segment::Alphanumeric("PN-".into()), Segment::Numeric("0123456789012345".into())]
+        );
+
+        let segments = optimal_segments("Grüße", Version::MIN);
+        assert_eq!(segments, [Segment::Eci(ECI_UTF8), Segment::bytes("Grüße")]);
+
+        let segments = optimal_segments("点茗", Version::MIN);
+        assert_eq!(segments, [Segment::Kanji("点茗".into())]);
+
+        // A short digit run isn't worth a segment header
+        let segments = optimal_segments("ab1cd", Version::MIN);
+        assert_eq!(segments, [Segment::bytes("ab1cd")]);
+    }
+
+    #[test]
+    fn smallest_version() {
+        let text = format!("serial:{}", "0123456789".repeat(8));
+        let mut buffer = vec![0u8; Version::MAX.buffer_len()];
+        let bytes = [Segment::bytes(text.as_str())];
+        let plain = encode_codewords(&bytes, &mut buffer, QrCodeEcc::Low, Version::MIN, Version::MAX, false).unwrap();
+        let optimal = encode_optimal(&text, &mut buffer, QrCodeEcc::Low, Version::MIN, Version::MAX, false).unwrap();
+        assert!(optimal.2 < plain.2, "{:?} {:?}", optimal.2, plain.2);
+    }
+}
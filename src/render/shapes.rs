@@ -0,0 +1,242 @@
+//! Lines, rectangles and circles with solid or dashed outlines
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Primitive, Size, Transform},
+    primitives::{Circle, Line, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, RoundedRectangle, StrokeAlignment},
+    Drawable, Pixel,
+};
+use embedded_layout::View;
+
+use crate::render::ops::StrokeOptions;
+
+/// Geometry of a `StyledShape`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Line(Line),
+    /// Rectangle and corner radius in dots
+    Rectangle(Rectangle, u32),
+    Circle(Circle),
+}
+
+/// A shape with its stroke converted to dots, closed shapes keep their outline inside their bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StyledShape {
+    pub shape: Shape,
+    width: u32,
+    dash: Option<(u32, u32)>,
+    fill: bool,
+}
+
+impl StyledShape {
+    pub fn new(shape: Shape, stroke: &StrokeOptions, dpi: u32) -> Self {
+        let dash = stroke.dash.map(|(on, off)| (on.dots(dpi).max(1), off.dots(dpi)));
+        Self { shape, width: stroke.width.dots(dpi), dash, fill: stroke.fill }
+    }
+
+    fn style(&self) -> PrimitiveStyle<BinaryColor> {
+        PrimitiveStyleBuilder::new()
+            .stroke_color(BinaryColor::On)
+            .stroke_width(self.width)
+            .stroke_alignment(StrokeAlignment::Inside)
+            .build()
+    }
+
+    fn draw_styled<D>(&self, style: PrimitiveStyle<BinaryColor>, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        match self.shape {
+            Shape::Line(line) => line.into_styled(style).draw(target),
+            Shape::Rectangle(rect, 0) => rect.into_styled(style).draw(target),
+            Shape::Rectangle(rect, radius) => RoundedRectangle::with_equal_corners(rect, Size::new(radius, radius))
+                .into_styled(style)
+                .draw(target),
+            Shape::Circle(circle) => circle.into_styled(style).draw(target),
+        }
+    }
+
+    /// Distance of `p` along the middle of the outline, clockwise from the top left for closed shapes
+    fn outline_position(&self, p: Point) -> f32 {
+        let (x, y) = (p.x as f32 + 0.5, p.y as f32 + 0.5);
+        let (rect, radius) = match self.shape {
+            Shape::Line(line) => {
+                let delta = line.delta();
+                let length = (delta.x as f32).hypot(delta.y as f32).max(1.0);
+                let from = p - line.start;
+                return (from.x * delta.x + from.y * delta.y) as f32 / length;
+            }
+            Shape::Rectangle(rect, radius) => (rect, radius as f32),
+            Shape::Circle(circle) => (circle.bounding_box(), circle.diameter as f32 / 2.0),
+        };
+
+        // The outline is the inner rectangle between the corner centres grown by the corner radius
+        let inset = self.width as f32 / 2.0;
+        let (left, top) = (rect.top_left.x as f32 + inset, rect.top_left.y as f32 + inset);
+        let (width, height) = (rect.size.width as f32 - 2.0 * inset, rect.size.height as f32 - 2.0 * inset);
+        let radius = (radius - inset).clamp(0.0, width.min(height).max(0.0) / 2.0);
+        let (xl, yt) = (left + radius, top + radius);
+        let (xr, yb) = ((left + width - radius).max(xl), (top + height - radius).max(yt));
+        let (qx, qy) = (x.clamp(xl, xr), y.clamp(yt, yb));
+        let angle = match (x - qx, y - qy) {
+            (0.0, 0.0) => {
+                let edges = [qy - yt, xr - qx, yb - qy, qx - xl];
+                let nearest = (0..4).min_by(|&a, &b| edges[a].total_cmp(&edges[b])).unwrap_or(0);
+                nearest as f32 * FRAC_PI_2
+            }
+            (dx, dy) => dx.atan2(-dy).rem_euclid(TAU),
+        };
+        let (iw, ih) = (xr - xl, yb - yt);
+        let along = match (angle / FRAC_PI_2) as u32 {
+            0 => qx - xl,
+            1 => iw + qy - yt,
+            2 => iw + ih + xr - qx,
+            _ => 2.0 * iw + ih + yb - qy,
+        };
+        along + radius * angle
+    }
+}
+
+/// Clips pixels to the target and drops those in the gaps between dashes
+struct Dashed<'a, D> {
+    target: &'a mut D,
+    shape: &'a StyledShape,
+    dash: Option<(u32, u32)>,
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> Dimensions for Dashed<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> DrawTarget for Dashed<'_, D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let height = self.target.bounding_box().size.height as i32;
+        let (shape, dash) = (self.shape, self.dash);
+        let pixels = pixels.into_iter().filter(|Pixel(p, _)| p.x >= 0 && p.y >= 0 && p.y < height).filter(|Pixel(p, _)| {
+            dash.is_none_or(|(on, off)| shape.outline_position(*p).rem_euclid((on + off) as f32) < on as f32)
+        });
+        self.target.draw_iter(pixels)
+    }
+}
+
+impl Drawable for StyledShape {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        if self.fill && !matches!(self.shape, Shape::Line(_)) {
+            let mut clipped = Dashed { target: &mut *target, shape: self, dash: None };
+            self.draw_styled(PrimitiveStyle::with_fill(BinaryColor::On), &mut clipped)?;
+        }
+        if self.width > 0 {
+            let mut dashed = Dashed { target, shape: self, dash: self.dash };
+            self.draw_styled(self.style(), &mut dashed)?;
+        }
+        Ok(())
+    }
+}
+
+impl Dimensions for StyledShape {
+    fn bounding_box(&self) -> Rectangle {
+        match self.shape {
+            Shape::Line(line) => line.into_styled(self.style()).bounding_box(),
+            Shape::Rectangle(rect, _) => rect,
+            Shape::Circle(circle) => circle.bounding_box(),
+        }
+    }
+}
+
+impl View for StyledShape {
+    fn translate_impl(&mut self, by: Point) {
+        match &mut self.shape {
+            Shape::Line(line) => {
+                Transform::translate_mut(line, by);
+            }
+            Shape::Rectangle(rect, _) => {
+                Transform::translate_mut(rect, by);
+            }
+            Shape::Circle(circle) => {
+                Transform::translate_mut(circle, by);
+            }
+        }
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::display::Display;
+    use crate::render::ops::Length;
+
+    fn stroke(width: u32, dash: Option<(u32, u32)>) -> StrokeOptions {
+        StrokeOptions { width: Length::Dots(width), dash: dash.map(|(on, off)| (on.into(), off.into())), fill: false }
+    }
+
+    fn dots(display: &Display, points: impl IntoIterator<Item = (usize, usize)>) -> Vec<bool> {
+        points.into_iter().map(|(x, y)| display.get(x, y).unwrap()).collect()
+    }
+
+    #[test]
+    fn outline_inside_bounds() {
+        let mut display = Display::new(64, 64);
+        let rect = Rectangle::new(Point::new(10, 10), Size::new(20, 12));
+        let shape = StyledShape::new(Shape::Rectangle(rect, 0), &stroke(3, None), 300);
+        shape.draw(&mut display).unwrap();
+        assert_eq!(shape.bounding_box(), rect);
+        // Three dots wide from each edge inwards, nothing outside or in the middle
+        assert_eq!(dots(&display, (8..16).map(|x| (x, 15))), [false, false, true, true, true, false, false, false]);
+        assert_eq!(dots(&display, (26..32).map(|x| (x, 15))), [false, true, true, true, false, false]);
+        assert_eq!(dots(&display, (8..14).map(|y| (20, y))), [false, false, true, true, true, false]);
+
+        let filled = StrokeOptions { fill: true, ..stroke(0, None) };
+        let circle = StyledShape::new(Shape::Circle(Circle::with_center(Point::new(45, 45), 11)), &filled, 300);
+        circle.draw(&mut display).unwrap();
+        assert_eq!(circle.bounding_box(), Rectangle::new(Point::new(40, 40), Size::new(11, 11)));
+        assert!(display.get(45, 45).unwrap() && display.get(40, 45).unwrap() && !display.get(39, 45).unwrap());
+    }
+
+    #[test]
+    fn dashes() {
+        let mut display = Display::new(64, 64);
+        let line = Line::new(Point::new(0, 5), Point::new(39, 5));
+        StyledShape::new(Shape::Line(line), &stroke(1, Some((4, 2))), 300).draw(&mut display).unwrap();
+        let row = dots(&display, (0..12).map(|x| (x, 5)));
+        assert_eq!(row, [true, true, true, true, false, false, true, true, true, true, false, false]);
+
+        // Dash lengths are measured along the outline, around the corners of a rounded rectangle too
+        let rect = Rectangle::new(Point::new(0, 20), Size::new(40, 40));
+        let shape = StyledShape::new(Shape::Rectangle(rect, 10), &stroke(1, Some((5, 5))), 300);
+        let top_left = shape.outline_position(Point::new(10, 20));
+        let right = shape.outline_position(Point::new(39, 30));
+        let quarter_arc = 9.5 * FRAC_PI_2;
+        assert!(top_left.abs() < 1.0, "{top_left}");
+        assert!((right - (20.0 + quarter_arc)).abs() < 1.0, "{right}");
+        assert!(shape.outline_position(Point::new(0, 45)) > 3.0 * 20.0 + 3.0 * quarter_arc);
+    }
+
+    #[test]
+    fn clipped_to_label() {
+        let mut display = Display::new(16, 16);
+        let line = Line::new(Point::new(-10, -10), Point::new(30, 30));
+        StyledShape::new(Shape::Line(line), &stroke(2, None), 300).draw(&mut display).unwrap();
+        assert!(display.get(8, 8).unwrap());
+        assert_eq!(display.bounding_box().size.height, 16);
+    }
+}
@@ -0,0 +1,126 @@
+//! Vector artwork rasterised at printer resolution
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use embedded_graphics::prelude::Size;
+use image::{DynamicImage, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+use crate::render::bitmap::Bitmap;
+use crate::render::ops::ImageOptions;
+use crate::PTouchError;
+
+/// SVG user units per inch
+const SVG_DPI: f32 = 96.0;
+
+/// System fonts for `<text>` elements, loaded once on first use
+fn fonts() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = usvg::fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// A parsed SVG document for `Render::render_svg`
+///
+/// Shapes and text are rasterised without anti-aliasing, so edges come out as solid dots instead of grey
+/// fringes that dither into noise.
+#[derive(Debug)]
+pub struct Svg {
+    tree: usvg::Tree,
+}
+
+impl Svg {
+    pub fn from_data(data: &[u8]) -> Result<Self, PTouchError> {
+        let options = usvg::Options {
+            shape_rendering: usvg::ShapeRendering::CrispEdges,
+            text_rendering: usvg::TextRendering::OptimizeSpeed,
+            fontdb: fonts(),
+            ..Default::default()
+        };
+        Ok(Self { tree: usvg::Tree::from_data(data, &options)? })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PTouchError> {
+        Self::from_data(&std::fs::read(path)?)
+    }
+
+    /// Document size in user units (1/96 inch)
+    pub fn size(&self) -> (f32, f32) {
+        let size = self.tree.size();
+        (size.width(), size.height())
+    }
+
+    /// Size in dots when printed at the document's own physical size
+    pub fn size_at(&self, dpi: u32) -> Size {
+        let (width, height) = self.size();
+        let scale = dpi as f32 / SVG_DPI;
+        Size::new(((width * scale).round() as u32).max(1), ((height * scale).round() as u32).max(1))
+    }
+
+    /// Rasterise to the largest size with the document's aspect ratio that fits `area`
+    pub fn rasterize(&self, area: Size) -> RgbaImage {
+        let (width, height) = self.size();
+        let scale = (area.width as f32 / width).min(area.height as f32 / height);
+        let size = Size::new(
+            ((width * scale).round() as u32).min(area.width),
+            ((height * scale).round() as u32).min(area.height),
+        );
+        let Some(mut pixmap) = tiny_skia::Pixmap::new(size.width, size.height) else {
+            return RgbaImage::new(0, 0);
+        };
+        let transform = tiny_skia::Transform::from_scale(size.width as f32 / width, size.height as f32 / height);
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+
+        let pixels = pixmap.pixels().iter().flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        });
+        RgbaImage::from_raw(size.width, size.height, pixels.collect()).expect("pixmap size")
+    }
+
+    /// Rasterise to fit `area` and convert to printable dots
+    pub fn to_bitmap(&self, area: Size, options: &ImageOptions) -> Bitmap {
+        let image = self.rasterize(area);
+        let size = Size::new(image.width(), image.height());
+        Bitmap::fit(&DynamicImage::ImageRgba8(image), size, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DISC: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="25.4mm" height="12.7mm" viewBox="0 0 20 10">
+        <circle cx="5" cy="5" r="4.3" fill="black"/>
+        <rect x="11" y="1" width="8" height="8" fill="none" stroke="black" stroke-width="0.7"/>
+    </svg>"#;
+
+    #[test]
+    fn physical_size() {
+        let svg = Svg::from_data(DISC.as_bytes()).unwrap();
+        assert_eq!(svg.size(), (96.0, 48.0));
+        assert_eq!(svg.size_at(300), Size::new(300, 150));
+        assert_eq!(svg.size_at(600), Size::new(600, 300));
+        assert!(matches!(Svg::from_data(b"<svg"), Err(PTouchError::SvgError(_))));
+    }
+
+    #[test]
+    fn crisp_edges() {
+        let svg = Svg::from_data(DISC.as_bytes()).unwrap();
+        let image = svg.rasterize(Size::new(300, 300));
+        assert_eq!(image.dimensions(), (300, 150));
+        assert!(image.pixels().all(|p| p.0[3] == 0 || p.0[3] == 255));
+
+        let bitmap = svg.to_bitmap(Size::new(300, 300), &ImageOptions::default());
+        assert_eq!(bitmap.size(), Size::new(300, 150));
+        // Centre of the disc and the square's outline are printed, its inside is not
+        assert!(bitmap.get(75, 75) && !bitmap.get(2, 2));
+        assert!(bitmap.get(225, 16) && !bitmap.get(225, 75));
+        assert_eq!(svg.rasterize(Size::new(0, 10)).dimensions(), (0, 0));
+    }
+}
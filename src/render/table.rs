@@ -0,0 +1,177 @@
+//! Grid of text cells
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Size},
+    primitives::{Line, Rectangle},
+    Drawable,
+};
+use embedded_layout::View;
+
+use crate::render::ops::{StrokeOptions, TableOptions};
+use crate::render::shapes::{Shape, StyledShape};
+use crate::render::text::StyledText;
+
+/// Rows of text cells with grid lines, laid out by its `TableOptions`
+pub struct Table<'a> {
+    rows: Vec<Vec<&'a str>>,
+    options: &'a TableOptions,
+    pub position: Point,
+    dpi: u32,
+    /// Cell widths including padding
+    columns: Vec<u32>,
+    /// Row height including padding
+    row_height: u32,
+    padding: u32,
+    line: u32,
+}
+
+impl<'a> Table<'a> {
+    pub fn new<R, S>(rows: &'a [R], options: &'a TableOptions, dpi: u32, position: Point) -> Self
+    where
+        R: AsRef<[S]>,
+        S: AsRef<str> + 'a,
+    {
+        let rows: Vec<Vec<&str>> = rows.iter().map(|row| row.as_ref().iter().map(S::as_ref).collect()).collect();
+        let padding = options.padding.dots(dpi);
+        let line = options.grid.as_ref().map_or(0, |grid| grid.width.dots(dpi));
+        let count = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let columns = (0..count)
+            .map(|i| {
+                let content = match options.column_widths.get(i) {
+                    Some(width) => width.dots(dpi),
+                    None => {
+                        let widths = rows.iter().filter_map(|row| row.get(i)).map(|text| options.text.measure(text).width);
+                        widths.max().unwrap_or(0)
+                    }
+                };
+                content + 2 * padding
+            })
+            .collect();
+        let content = options.row_height.map_or(options.text.line_height(), |height| height.dots(dpi));
+        Self { rows, options, position, dpi, columns, row_height: content + 2 * padding, padding, line }
+    }
+
+    /// Left edges of the vertical grid lines, the last one closes the table
+    fn column_edges(&self) -> Vec<i32> {
+        let mut x = self.position.x;
+        let mut edges = vec![x];
+        for width in &self.columns {
+            x += (width + self.line) as i32;
+            edges.push(x);
+        }
+        edges
+    }
+
+    /// Top edges of the horizontal grid lines
+    fn row_edges(&self) -> Vec<i32> {
+        (0..=self.rows.len() as i32).map(|i| self.position.y + i * (self.row_height + self.line) as i32).collect()
+    }
+
+    /// Box for the text of the cell in `row`, `column`
+    pub fn cell(&self, row: usize, column: usize) -> Rectangle {
+        let (x, y) = (self.column_edges()[column], self.row_edges()[row]);
+        let inset = (self.line + self.padding) as i32;
+        let size = Size::new(self.columns[column] - 2 * self.padding, self.row_height - 2 * self.padding);
+        Rectangle::new(Point::new(x + inset, y + inset), size)
+    }
+
+    /// Grid line `width` dots wide covering `from` to `to` along its length, left or top edge at `edge`
+    fn grid_line(&self, grid: &StrokeOptions, vertical: bool, edge: i32, from: i32, to: i32) -> StyledShape {
+        // Thick lines put the extra dot of even widths on their left, seen along the line
+        let centre = edge + (self.line.saturating_sub(1) / 2) as i32;
+        let line = match vertical {
+            true => Line::new(Point::new(centre, from), Point::new(centre, to - 1)),
+            false => Line::new(Point::new(to - 1, centre), Point::new(from, centre)),
+        };
+        StyledShape::new(Shape::Line(line), grid, self.dpi)
+    }
+}
+
+impl Drawable for Table<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let bounds = self.bounding_box();
+        if let (Some(grid), true) = (&self.options.grid, self.line > 0) {
+            let (left, right) = (bounds.top_left.x, bounds.top_left.x + bounds.size.width as i32);
+            let (top, bottom) = (bounds.top_left.y, bounds.top_left.y + bounds.size.height as i32);
+            for x in self.column_edges() {
+                self.grid_line(grid, true, x, top, bottom).draw(target)?;
+            }
+            for y in self.row_edges() {
+                self.grid_line(grid, false, y, left, right).draw(target)?;
+            }
+        }
+        for (r, row) in self.rows.iter().enumerate() {
+            for (c, text) in row.iter().enumerate() {
+                StyledText::new(text, &self.options.text, self.cell(r, c)).draw(target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Dimensions for Table<'_> {
+    fn bounding_box(&self) -> Rectangle {
+        if self.rows.is_empty() || self.columns.is_empty() {
+            return Rectangle::new(self.position, Size::zero());
+        }
+        let width = self.columns.iter().sum::<u32>() + (self.columns.len() as u32 + 1) * self.line;
+        let height = self.rows.len() as u32 * self.row_height + (self.rows.len() as u32 + 1) * self.line;
+        Rectangle::new(self.position, Size::new(width, height))
+    }
+}
+
+impl View for Table<'_> {
+    fn translate_impl(&mut self, by: Point) {
+        self.position += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::display::Display;
+    use crate::render::ops::{FontKind, Length, TextOptions};
+
+    #[test]
+    fn layout() {
+        let options = TableOptions {
+            text: TextOptions { font: FontKind::Font6x8, ..TableOptions::default().text },
+            padding: Length::Dots(2),
+            grid: Some(StrokeOptions { width: Length::Dots(3), ..Default::default() }),
+            ..Default::default()
+        };
+        let rows = [vec!["Part", "Qty"], vec!["M3x8", "1200"], vec!["Nut"]];
+        let table = Table::new(&rows, &options, 300, Point::new(1, 2));
+        // Columns fit "Part" / "M3x8" and "1200", 4 characters of 5 dots, plus padding
+        assert_eq!(table.columns, [20 + 4, 20 + 4]);
+        assert_eq!(table.row_height, 8 + 4);
+        assert_eq!(table.bounding_box(), Rectangle::new(Point::new(1, 2), Size::new(2 * 24 + 3 * 3, 3 * 12 + 4 * 3)));
+        assert_eq!(table.cell(1, 1), Rectangle::new(Point::new(1 + 3 + 24 + 3 + 2, 2 + 3 + 12 + 3 + 2), Size::new(20, 8)));
+
+        let mut display = Display::new(64, 16);
+        table.draw(&mut display).unwrap();
+        // Grid lines are 3 dots wide starting at each edge
+        let row: Vec<bool> = (0..6).map(|x| display.get(x, 30).unwrap()).collect();
+        assert_eq!(row, [false, true, true, true, false, false]);
+        let column: Vec<bool> = (0..6).map(|y| display.get(20, y).unwrap()).collect();
+        assert_eq!(column, [false, false, true, true, true, false]);
+        assert!(display.get(57, 49).unwrap() && display.bounding_box().size.width == 58);
+
+        let widths = vec![Length::Mm(2.0)];
+        let fixed = TableOptions { column_widths: widths, row_height: Some(Length::Dots(20)), grid: None, ..options };
+        let table = Table::new(&rows, &fixed, 300, Point::zero());
+        assert_eq!(table.columns, [24 + 4, 20 + 4]);
+        assert_eq!(table.cell(0, 0), Rectangle::new(Point::new(2, 2), Size::new(24, 20)));
+    }
+}
@@ -0,0 +1,456 @@
+//! `{{field}}` placeholders and conditions in label templates, filled from records such as CSV rows
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Field values of one record, e.g. a CSV row by column name
+pub type Record = BTreeMap<String, String>;
+
+/// Invalid template or record data
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    /// Unclosed or empty placeholder, or a malformed condition
+    Syntax(String),
+    /// The record has no such field and the placeholder no `default`
+    UnknownField(String),
+    UnknownFilter(String),
+    /// Value of a `date` filter that isn't an ISO date, or an unknown directive in its format
+    InvalidDate(String),
+    /// Malformed CSV, `line` is where the record starts
+    Csv { line: usize, reason: String },
+    /// A line of JSON lines that isn't an object
+    JsonLines { line: usize, reason: String },
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Syntax(reason) => write!(f, "Invalid template: {reason}"),
+            TemplateError::UnknownField(name) => write!(f, "Unknown field {name:?}"),
+            TemplateError::UnknownFilter(name) => write!(f, "Unknown filter {name:?}"),
+            TemplateError::InvalidDate(reason) => write!(f, "Invalid date: {reason}"),
+            TemplateError::Csv { line, reason } => write!(f, "CSV line {line}: {reason}"),
+            TemplateError::JsonLines { line, reason } => write!(f, "JSON line {line}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+enum Filter<'a> {
+    Upper,
+    Lower,
+    Trim,
+    Pad(usize),
+    Date(&'a str),
+    Default(&'a str),
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Field { name: &'a str, filters: Vec<Filter<'a>> },
+}
+
+impl<'a> Filter<'a> {
+    fn parse(filter: &'a str) -> Result<Self, TemplateError> {
+        let (name, argument) = match filter.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument)),
+            None => (filter.trim(), None),
+        };
+        match (name, argument) {
+            ("upper", None) => Ok(Filter::Upper),
+            ("lower", None) => Ok(Filter::Lower),
+            ("trim", None) => Ok(Filter::Trim),
+            ("pad", Some(width)) => {
+                let width = width.trim().parse().map_err(|_| TemplateError::Syntax(format!("Invalid width {width:?}")))?;
+                Ok(Filter::Pad(width))
+            }
+            ("date", Some(format)) => Ok(Filter::Date(format)),
+            ("default", Some(text)) => Ok(Filter::Default(text)),
+            _ => Err(TemplateError::UnknownFilter(filter.trim().into())),
+        }
+    }
+
+    fn apply(&self, value: String) -> Result<String, TemplateError> {
+        Ok(match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Trim => value.trim().into(),
+            Filter::Pad(width) => match value.strip_prefix('-') {
+                Some(digits) if is_number(digits) => format!("-{digits:0>width$}", width = width.saturating_sub(1)),
+                _ if is_number(&value) => format!("{value:0>width$}"),
+                _ => format!("{value:<width$}"),
+            },
+            Filter::Date(format) => {
+                let date = DateTime::parse(&value).ok_or_else(|| TemplateError::InvalidDate(format!("{value:?}")))?;
+                date.format(format)?
+            }
+            Filter::Default(_) => value,
+        })
+    }
+}
+
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn parse(template: &str) -> Result<Vec<Part<'_>>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let end = rest[start..].find("}}").ok_or_else(|| TemplateError::Syntax(format!("Unclosed {{{{ in {template:?}")))?;
+        let mut filters = rest[start + 2..start + end].split('|');
+        let name = filters.next().unwrap_or_default().trim();
+        if name.is_empty() {
+            return Err(TemplateError::Syntax(format!("Placeholder without a field in {template:?}")));
+        }
+        let filters = filters.map(Filter::parse).collect::<Result<_, _>>()?;
+        parts.push(Part::Field { name, filters });
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    Ok(parts)
+}
+
+/// Check the placeholders in `template` without filling them
+pub fn check(template: &str) -> Result<(), TemplateError> {
+    parse(template).map(|_| ())
+}
+
+/// Replace the placeholders in `template` with fields of `record`.
+///
+/// A placeholder names a field, optionally followed by filters separated by `|`, e.g. `{{serial|pad:6}}`:
+/// - `upper`, `lower` and `trim`
+/// - `pad:N` zero pads numbers and space pads other text to N characters
+/// - `date:FORMAT` formats an ISO date like `2024-05-01` or `2024-05-01T12:30:00` with `%Y`, `%y`, `%m`, `%d`,
+///   `%b`, `%B`, `%H`, `%M` and `%S`
+/// - `default:TEXT` for a missing or empty field
+///
+/// `today` and `now` are the current date and time (UTC) unless the record has fields of that name.
+pub fn fill(template: &str, record: &Record) -> Result<String, TemplateError> {
+    let mut text = String::with_capacity(template.len());
+    for part in parse(template)? {
+        match part {
+            Part::Text(part) => text.push_str(part),
+            Part::Field { name, filters } => {
+                let mut value = record.get(name).cloned().or_else(|| match name {
+                    "today" => Some(DateTime::now().format("%Y-%m-%d").unwrap_or_default()),
+                    "now" => Some(DateTime::now().format("%Y-%m-%dT%H:%M:%S").unwrap_or_default()),
+                    _ => None,
+                });
+                for filter in &filters {
+                    value = match (filter, value) {
+                        (Filter::Default(text), None) => Some(text.to_string()),
+                        (Filter::Default(text), Some(value)) if value.is_empty() => Some(text.to_string()),
+                        (_, None) => None,
+                        (filter, Some(value)) => Some(filter.apply(value)?),
+                    };
+                }
+                text.push_str(&value.ok_or_else(|| TemplateError::UnknownField(name.into()))?);
+            }
+        }
+    }
+    Ok(text)
+}
+
+enum Condition<'a> {
+    Set(&'a str),
+    Unset(&'a str),
+    Equal(&'a str, &'a str),
+    NotEqual(&'a str, &'a str),
+}
+
+impl<'a> Condition<'a> {
+    fn parse(condition: &'a str) -> Result<Self, TemplateError> {
+        let value = |value: &'a str| value.trim().trim_matches('"');
+        let parsed = match (condition.split_once("!="), condition.split_once("==")) {
+            (Some((field, text)), _) => Condition::NotEqual(field.trim(), value(text)),
+            (None, Some((field, text))) => Condition::Equal(field.trim(), value(text)),
+            (None, None) => match condition.trim().strip_prefix('!') {
+                Some(field) => Condition::Unset(field.trim()),
+                None => Condition::Set(condition.trim()),
+            },
+        };
+        match parsed {
+            Condition::Set(field) | Condition::Unset(field) | Condition::Equal(field, _) | Condition::NotEqual(field, _)
+                if field.is_empty() || field.contains(char::is_whitespace) =>
+            {
+                Err(TemplateError::Syntax(format!("Invalid condition {condition:?}")))
+            }
+            parsed => Ok(parsed),
+        }
+    }
+}
+
+/// Check a condition without evaluating it
+pub fn check_condition(condition: &str) -> Result<(), TemplateError> {
+    Condition::parse(condition).map(|_| ())
+}
+
+/// Evaluate a condition on `record`: `field` holds if the field is set to something other than an empty string,
+/// `0` or `false`, `!field` is the opposite, `field == text` and `field != text` compare its value
+pub fn condition(condition: &str, record: &Record) -> Result<bool, TemplateError> {
+    let value = |field: &str| record.get(field).map(|value| value.trim()).unwrap_or_default();
+    let set = |field: &str| !matches!(value(field), "" | "0" | "false");
+    Ok(match Condition::parse(condition)? {
+        Condition::Set(field) => set(field),
+        Condition::Unset(field) => !set(field),
+        Condition::Equal(field, text) => value(field) == text,
+        Condition::NotEqual(field, text) => value(field) != text,
+    })
+}
+
+/// Records from CSV text with a header row. Fields are separated by `,` or, if the header has more of them, `;`.
+/// Quoted fields may contain separators, line breaks and quotes written as `""`.
+pub fn read_csv(text: &str) -> Result<Vec<Record>, TemplateError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let header = text.lines().next().unwrap_or_default();
+    let separator = match header.matches(';').count() > header.matches(',').count() {
+        true => ';',
+        false => ',',
+    };
+
+    let mut rows = csv_rows(text, separator)?.into_iter();
+    let Some((_, names)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let names: Vec<String> = names.iter().map(|name| name.trim().to_string()).collect();
+    rows.map(|(line, row)| match row.len() == names.len() {
+        true => Ok(names.iter().cloned().zip(row).collect()),
+        false => Err(TemplateError::Csv { line, reason: format!("Expected {} fields, found {}", names.len(), row.len()) }),
+    })
+    .collect()
+}
+
+/// Records from JSON lines, an object per line. Strings are taken as they are, `null` as an empty string and other
+/// values as JSON text.
+#[cfg(feature = "serde")]
+pub fn read_json_lines(text: &str) -> Result<Vec<Record>, TemplateError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let value = |value| match value {
+        serde_json::Value::String(text) => text,
+        serde_json::Value::Null => String::new(),
+        value => value.to_string(),
+    };
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let fields: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)
+                .map_err(|e| TemplateError::JsonLines { line: i + 1, reason: e.to_string() })?;
+            Ok(fields.into_iter().map(|(name, field)| (name, value(field))).collect())
+        })
+        .collect()
+}
+
+/// Non-empty rows and the lines they start on
+fn csv_rows(text: &str, separator: char) -> Result<Vec<(usize, Vec<String>)>, TemplateError> {
+    let mut rows = Vec::new();
+    let (mut row, mut field) = (Vec::new(), String::new());
+    let (mut line, mut start) = (1, 1);
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => {
+                line += usize::from(c == '\n');
+                field.push(c);
+            }
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push((start, std::mem::take(&mut row)));
+                }
+                row.clear();
+                line += 1;
+                start = line;
+            }
+            (false, c) if c == separator => row.push(std::mem::take(&mut field)),
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(TemplateError::Csv { line: start, reason: "Unterminated quoted field".into() });
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push((start, row));
+    }
+    Ok(rows)
+}
+
+const MONTHS: [&str; 12] =
+    ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+struct DateTime {
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl DateTime {
+    fn now() -> Self {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400) as u32);
+
+        // Days since 1970-01-01 to a civil date, Howard Hinnant's algorithm
+        let z = days + 719468;
+        let (era, day_of_era) = (z.div_euclid(146097), z.rem_euclid(146097));
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = (year_of_era + era * 400 + i64::from(month <= 2)) as i32;
+        Self { year, month, day, hour: time / 3600, minute: time / 60 % 60, second: time % 60 }
+    }
+
+    /// `YYYY-MM-DD` with an optional `THH:MM[:SS]` (or a space instead of the `T`), fractions and zones (`Z`,
+    /// `+hh:mm`, `-hh:mm`) are ignored
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (text, None),
+        };
+        let mut date = date.split('-');
+        let (year, month, day) = (date.next()?.parse().ok()?, date.next()?.parse().ok()?, date.next()?.parse().ok()?);
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+        if date.next().is_some() || !(1..=12).contains(&month) || day == 0 || day > days[month as usize - 1] {
+            return None;
+        }
+
+        let (mut hour, mut minute, mut second) = (0, 0, 0);
+        if let Some(time) = time {
+            let time = time.split(['.', 'Z', '+', '-']).next()?;
+            let mut time = time.split(':');
+            hour = time.next()?.parse().ok()?;
+            minute = time.next()?.parse().ok()?;
+            second = time.next().map_or(Some(0), |s| s.parse().ok())?;
+            if hour > 23 || minute > 59 || second > 60 {
+                return None;
+            }
+        }
+        Some(Self { year, month, day, hour, minute, second })
+    }
+
+    fn format(&self, format: &str) -> Result<String, TemplateError> {
+        let mut text = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let month = MONTHS[self.month as usize - 1];
+            match chars.next() {
+                Some('Y') => text.push_str(&self.year.to_string()),
+                Some('y') => text.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('m') => text.push_str(&format!("{:02}", self.month)),
+                Some('d') => text.push_str(&format!("{:02}", self.day)),
+                Some('b') => text.push_str(&month[..3]),
+                Some('B') => text.push_str(month),
+                Some('H') => text.push_str(&format!("{:02}", self.hour)),
+                Some('M') => text.push_str(&format!("{:02}", self.minute)),
+                Some('S') => text.push_str(&format!("{:02}", self.second)),
+                Some('%') => text.push('%'),
+                other => {
+                    let directive = other.map_or("%".into(), |c| format!("%{c}"));
+                    return Err(TemplateError::InvalidDate(format!("Unknown directive {directive:?} in {format:?}")));
+                }
+            }
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(fields: &[(&str, &str)]) -> Record {
+        fields.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn placeholders() {
+        let item = record(&[("name", " Widget "), ("serial", "42"), ("delta", "-7"), ("made", "2024-05-01T08:05")]);
+        assert_eq!(fill("PN {{serial|pad:6}} {{delta|pad:4}}", &item).unwrap(), "PN 000042 -007");
+        assert_eq!(fill("{{name|trim|upper}}|{{name|lower|pad:9}}|", &item).unwrap(), "WIDGET| widget  |");
+        assert_eq!(fill("{{made|date:%d %b %y, %H:%M}}", &item).unwrap(), "01 May 24, 08:05");
+        assert_eq!(fill("{{colour|default:n/a}} {{name|trim|default:x}}", &item).unwrap(), "n/a Widget");
+        assert_eq!(fill("{{today|date:%Y}}", &item).unwrap().len(), 4);
+
+        assert_eq!(fill("{{colour}}", &item), Err(TemplateError::UnknownField("colour".into())));
+        assert_eq!(check("{{name|title}}"), Err(TemplateError::UnknownFilter("title".into())));
+        assert!(matches!(check("{{name"), Err(TemplateError::Syntax(_))));
+        assert!(matches!(check("{{ | upper}}"), Err(TemplateError::Syntax(_))));
+        assert!(matches!(fill("{{name|date:%Y}}", &item), Err(TemplateError::InvalidDate(_))));
+        assert!(matches!(fill("{{made|date:%Q}}", &item), Err(TemplateError::InvalidDate(_))));
+        assert!(DateTime::parse("2023-02-29").is_none() && DateTime::parse("2024-02-29").is_some());
+    }
+
+    #[test]
+    fn dates() {
+        let time = |text| DateTime::parse(text).map(|t| (t.day, t.hour, t.minute, t.second));
+        assert_eq!(time("2024-05-01T08:05:09-05:00"), Some((1, 8, 5, 9)));
+        assert_eq!(time("2024-05-01 08:05-0330"), Some((1, 8, 5, 0)));
+        assert_eq!(time("2024-05-01T08:05:09.250+02:00"), Some((1, 8, 5, 9)));
+        assert_eq!(time("2024-05-01T08:05Z"), Some((1, 8, 5, 0)));
+        assert_eq!(time("2024-05-01T24:00-05:00"), None);
+    }
+
+    #[test]
+    fn conditions() {
+        let item = record(&[("fragile", "yes"), ("hazard", "0"), ("kind", "bolt")]);
+        assert!(condition("fragile", &item).unwrap());
+        assert!(!condition("hazard", &item).unwrap() && !condition("missing", &item).unwrap());
+        assert!(condition("!hazard", &item).unwrap());
+        assert!(condition("kind == bolt", &item).unwrap() && condition(r#"kind != "nut""#, &item).unwrap());
+        assert!(check_condition("two words").is_err() && check_condition("== x").is_err());
+    }
+
+    #[test]
+    fn csv() {
+        let text = "\u{feff}name,serial,notes\r\nWidget,1,\"Fragile, \"\"handle\"\" with\ncare\"\r\n\r\nNut,2,\n";
+        let records = read_csv(text).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["notes"], "Fragile, \"handle\" with\ncare");
+        assert_eq!(records[1], record(&[("name", "Nut"), ("serial", "2"), ("notes", "")]));
+
+        let records = read_csv("name;price\nBolt;0,25").unwrap();
+        assert_eq!(records[0]["price"], "0,25");
+
+        let error = read_csv("a,b\n1,\"2\n3\n").unwrap_err();
+        assert_eq!(error, TemplateError::Csv { line: 2, reason: "Unterminated quoted field".into() });
+        let error = read_csv("a,b\n1,2\n\n1,2,3\n").unwrap_err();
+        assert_eq!(error.to_string(), "CSV line 4: Expected 2 fields, found 3");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_lines() {
+        let text = "{\"name\": \"Widget\", \"serial\": 42, \"notes\": null}\n\n{\"name\": \"Nut\", \"tags\": [1]}\n";
+        let records = read_json_lines(text).unwrap();
+        assert_eq!(records[0], record(&[("name", "Widget"), ("serial", "42"), ("notes", "")]));
+        assert_eq!(records[1], record(&[("name", "Nut"), ("tags", "[1]")]));
+
+        let error = read_json_lines("{\"a\": 1}\n[1, 2]\n").unwrap_err();
+        assert!(matches!(error, TemplateError::JsonLines { line: 2, .. }), "{error}");
+    }
+}
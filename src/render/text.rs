@@ -1,66 +1,568 @@
-// use embedded_graphics::draw_target::DrawTarget;
-// use embedded_graphics::{Drawable, Pixel};
-// use embedded_graphics::geometry::Dimensions;
-// use embedded_graphics::mono_font::MonoTextStyle;
-// use embedded_graphics::pixelcolor::BinaryColor;
-// use embedded_graphics::prelude::{Point, Size};
-// use embedded_graphics::primitives::arc::Points;
-// use embedded_graphics::primitives::Rectangle;
-// use embedded_graphics::text::Text;
-// 
-// struct StyledTextIterator {
-//     inner: StyledText,
-//     points: Points,
-// }
-// 
-// impl StyledTextIterator {
-//     fn new(styled_text: StyledText) -> Self {
-//         Self {
-//             inner: styled_text,
-//             points: styled_text.bounding_box().points(),
-//         }
-//     }
-// }
-// 
-// impl Iterator for StyledTextIterator {
-//     type Item = embedded_graphics::Pixel<BinaryColor>;
-// 
-//     fn next(&mut self) -> Option<Self::Item> {
-//         let point = self.points.next()?;
-//         Some(Pixel(point, self.inner.get_element_translated(point).into()))
-//     }
-// }
-// 
-// 
-// struct StyledText {
-//     pub text: Points,
-//     pub position: Point,
-//     pub width: u32,
-//     pub height: u32,
-//     pub border_size_px: u32,
-// }
-// 
-// impl Drawable for StyledText {
-//     type Color = BinaryColor;
-//     type Output = ();
-// 
-//     fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
-//     where
-//         D: DrawTarget<Color=Self::Color>,
-//     {
-//         target.draw_iter(StyledTextIterator::new(&self))
-//     }
-// }
-// 
-// impl Dimensions for StyledText {
-//     fn bounding_box(&self) -> Rectangle {
-//         let total_width = self.width + 2 * self.border_size_px;
-//         let total_height = self.height + 2 * self.border_size_px;
-// 
-//         Rectangle {
-//             top_left: self.position,
-//             size: Size::new(total_width, total_height),
-//         }
-//     }
-// 
-// 
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    prelude::{Dimensions, Point, Size},
+    primitives::Rectangle,
+    text::{Baseline, Text},
+    Drawable, Pixel,
+};
+use embedded_layout::View;
+
+use crate::render::font::TtfText;
+use crate::render::ops::{FitOptions, FontKind, HAlign, ParagraphOptions, TextOptions, VAlign};
+
+/// A line of text aligned inside `area` according to its `TextOptions`
+pub struct StyledText<'a> {
+    pub text: &'a str,
+    pub options: &'a TextOptions,
+    pub area: Rectangle,
+}
+
+impl<'a> StyledText<'a> {
+    pub fn new(text: &'a str, options: &'a TextOptions, area: Rectangle) -> Self {
+        Self { text, options, area }
+    }
+
+    /// Top left corner of the text after alignment
+    fn position(&self) -> Point {
+        aligned(self.area, self.options.measure(self.text), self.options)
+    }
+}
+
+/// Top left corner of a box of `size` aligned inside `area`
+fn aligned(area: Rectangle, size: Size, options: &TextOptions) -> Point {
+    let free_x = area.size.width as i32 - size.width as i32;
+    let free_y = area.size.height as i32 - size.height as i32;
+
+    let x = match options.h_align {
+        HAlign::Left => 0,
+        HAlign::Centre => free_x / 2,
+        HAlign::Right => free_x,
+    };
+    let y = match options.v_align {
+        VAlign::Top => 0,
+        VAlign::Centre => free_y / 2,
+        VAlign::Bottom => free_y,
+    };
+
+    area.top_left + Point::new(x, y)
+}
+
+impl<'a> Drawable for StyledText<'a> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let position = self.position();
+
+        match &self.options.font {
+            FontKind::TrueType(style) => TtfText::new(self.text, style, position).draw(target),
+            kind => {
+                let (font, scale) = kind.mono().expect("bitmap font");
+                let style = MonoTextStyle::new(font, BinaryColor::On);
+                let text = Text::with_baseline(self.text, Point::zero(), style, Baseline::Top);
+                text.draw(&mut Scaled { target, scale, offset: position })?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> Dimensions for StyledText<'a> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(self.position(), self.options.measure(self.text))
+    }
+}
+
+impl<'a> View for StyledText<'a> {
+    fn translate_impl(&mut self, by: Point) {
+        self.area.top_left += by;
+    }
+
+    fn bounds(&self) -> Rectangle {
+        self.bounding_box()
+    }
+}
+
+/// Text fitted into a rectangle by `fit`, drawn line by line
+#[derive(Debug, Clone)]
+pub struct FittedText {
+    /// Chosen font, for TrueType fonts the size is in the style
+    pub font: FontKind,
+    pub lines: Vec<String>,
+    /// Text had to be shortened to fit
+    pub truncated: bool,
+    /// All lines fit the rectangle, false if the text overflows at the smallest size
+    pub fits: bool,
+    options: TextOptions,
+    area: Rectangle,
+}
+
+/// Pick the largest font size for which `text` fits in `area`.
+/// Bitmap fonts are tried from `FontKind::BITMAP`, TrueType sizes from `fit.max_size` down.
+pub fn fit(text: &str, options: &TextOptions, fit: &FitOptions, area: Rectangle) -> FittedText {
+    let candidates: Vec<FontKind> = match &options.font {
+        FontKind::TrueType(style) => {
+            let steps = ((fit.max_size - fit.min_size) / fit.step.max(0.1)).floor().max(0.0) as u32;
+            (0..=steps)
+                .map(|i| FontKind::TrueType(style.clone().with_size(fit.max_size - i as f32 * fit.step)))
+                .collect()
+        }
+        _ => FontKind::BITMAP.to_vec(),
+    };
+
+    let mut fitted = None;
+    for font in candidates {
+        let options = TextOptions { font, ..options.clone() };
+        let width = fit.wrap.then_some(area.size.width);
+        let lines: Vec<String> = wrap(text, &options, width, false).into_iter().map(|l| l.text).collect();
+        let fits = fits(&lines, &options, area.size);
+        fitted = Some((options, lines));
+        if fits {
+            break;
+        }
+    }
+    let (options, mut lines) = fitted.expect("at least one candidate font");
+
+    let fits = fits(&lines, &options, area.size);
+    let mut truncated = false;
+    if !fits && fit.ellipsis {
+        let max_lines = (area.size.height / options.line_height().max(1)).max(1) as usize;
+        truncated = lines.len() > max_lines;
+        lines.truncate(max_lines);
+
+        let last = lines.len() - 1;
+        for (i, line) in lines.iter_mut().enumerate() {
+            if (i == last && truncated) || options.measure(line).width > area.size.width {
+                *line = ellipsize(line, &options, area.size.width);
+                truncated = true;
+            }
+        }
+    }
+
+    let fits = fits || (truncated && self::fits(&lines, &options, area.size));
+    FittedText { font: options.font.clone(), lines, truncated, fits, options, area }
+}
+
+/// A line produced by `wrap`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub text: String,
+    /// Last line of a paragraph (explicit newline or end of text), not justified
+    pub end_of_paragraph: bool,
+}
+
+/// Split `text` on newlines and, with a `width`, at whitespace so lines stay within it.
+/// With `break_words` words wider than a line are split between characters, without a hyphen.
+pub fn wrap(text: &str, options: &TextOptions, width: Option<u32>, break_words: bool) -> Vec<Line> {
+    let Some(width) = width else {
+        return text.lines().map(|l| Line { text: l.to_string(), end_of_paragraph: true }).collect();
+    };
+    let fits = |s: &str| options.measure(s).width <= width;
+
+    let mut lines = Vec::new();
+    let mut push = |text: String, end_of_paragraph| lines.push(Line { text, end_of_paragraph });
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{line} {word}"),
+            };
+            if fits(&candidate) {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                push(std::mem::take(&mut line), false);
+            }
+            line = word.to_string();
+
+            if break_words && !fits(word) {
+                let mut piece = String::new();
+                for c in word.chars() {
+                    piece.push(c);
+                    if !fits(&piece) && piece.chars().count() > 1 {
+                        piece.pop();
+                        push(std::mem::replace(&mut piece, c.to_string()), false);
+                    }
+                }
+                line = piece;
+            }
+        }
+        push(line, true);
+    }
+    lines
+}
+
+fn fits(lines: &[String], options: &TextOptions, size: Size) -> bool {
+    lines.len() as u32 * options.line_height() <= size.height
+        && lines.iter().all(|line| options.measure(line).width <= size.width)
+}
+
+/// Shorten `line` and append an ellipsis so it fits in `width`
+fn ellipsize(line: &str, options: &TextOptions, width: u32) -> String {
+    // The bitmap fonts are ISO 8859-1 which has no ellipsis character
+    let ellipsis = match options.font {
+        FontKind::TrueType(_) => "\u{2026}",
+        _ => "...",
+    };
+
+    let mut chars: Vec<char> = line.trim_end().chars().collect();
+    loop {
+        let candidate = format!("{}{ellipsis}", chars.iter().collect::<String>().trim_end());
+        if chars.is_empty() || options.measure(&candidate).width <= width {
+            return candidate;
+        }
+        chars.pop();
+    }
+}
+
+impl FittedText {
+    fn paragraph(&self) -> Paragraph {
+        let lines = self.lines.iter().map(|l| Line { text: l.clone(), end_of_paragraph: true }).collect();
+        let options = ParagraphOptions { text: self.options.clone(), ..Default::default() };
+        Paragraph { lines, options, area: self.area }
+    }
+}
+
+impl Drawable for FittedText {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        self.paragraph().draw(target)
+    }
+}
+
+impl Dimensions for FittedText {
+    /// Box around all lines after alignment
+    fn bounding_box(&self) -> Rectangle {
+        self.paragraph().bounding_box()
+    }
+}
+
+/// Wrapped lines of text, aligned as a block inside `area`
+#[derive(Debug, Clone)]
+pub struct Paragraph {
+    pub lines: Vec<Line>,
+    pub options: ParagraphOptions,
+    pub area: Rectangle,
+}
+
+impl Paragraph {
+    /// Wrap `text` at `width`, the paragraph is exactly as high as its lines
+    pub fn new(text: &str, options: &ParagraphOptions, top_left: Point, width: u32) -> Self {
+        let lines = wrap(text, &options.text, Some(width), options.break_words);
+        let mut paragraph = Self { lines, options: options.clone(), area: Rectangle::new(top_left, Size::new(width, 0)) };
+        paragraph.area.size.height = paragraph.height();
+        paragraph
+    }
+
+    /// Distance between the tops of two lines in dots
+    pub fn line_pitch(&self) -> u32 {
+        (self.options.text.line_height() as f32 * self.options.line_spacing).round() as u32
+    }
+
+    /// Height of all lines in dots
+    pub fn height(&self) -> u32 {
+        match self.lines.len() as u32 {
+            0 => 0,
+            n => (n - 1) * self.line_pitch() + self.options.text.line_height(),
+        }
+    }
+
+    /// Draw a justified line word by word, spreading the free space over the gaps
+    fn draw_justified<D>(&self, words: &[&str], area: Rectangle, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let options = TextOptions { h_align: HAlign::Left, v_align: VAlign::Top, ..self.options.text.clone() };
+        let widths: Vec<u32> = words.iter().map(|w| options.measure(w).width).collect();
+        let gaps = words.len() as u32 - 1;
+        let free = area.size.width.saturating_sub(widths.iter().sum());
+
+        let mut x = area.top_left.x;
+        for (i, (word, width)) in words.iter().zip(widths).enumerate() {
+            let word_area = Rectangle::new(Point::new(x, area.top_left.y), Size::new(width, area.size.height));
+            StyledText::new(word, &options, word_area).draw(target)?;
+            x += (width + free / gaps + u32::from((i as u32) < free % gaps)) as i32;
+        }
+        Ok(())
+    }
+}
+
+impl Drawable for Paragraph {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let line_height = self.options.text.line_height();
+        let top = self.bounding_box().top_left.y;
+        let options = TextOptions { v_align: VAlign::Top, ..self.options.text.clone() };
+
+        for (i, line) in self.lines.iter().enumerate() {
+            let top_left = Point::new(self.area.top_left.x, top + (i as u32 * self.line_pitch()) as i32);
+            let area = Rectangle::new(top_left, Size::new(self.area.size.width, line_height));
+
+            let words: Vec<&str> = line.text.split(' ').collect();
+            if self.options.justify && !line.end_of_paragraph && words.len() > 1 {
+                self.draw_justified(&words, area, target)?;
+            } else {
+                StyledText::new(&line.text, &options, area).draw(target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Dimensions for Paragraph {
+    /// Box around all lines after alignment, justified lines span the full width
+    fn bounding_box(&self) -> Rectangle {
+        let width = self
+            .lines
+            .iter()
+            .map(|l| match self.options.justify && !l.end_of_paragraph {
+                true => self.area.size.width,
+                false => self.options.text.measure(&l.text).width,
+            })
+            .max()
+            .unwrap_or(0);
+        let size = Size::new(width, self.height());
+        Rectangle::new(aligned(self.area, size, &self.options.text), size)
+    }
+}
+
+/// Draws every pixel as a `scale` x `scale` block, offset by `offset`.
+/// Only set pixels are forwarded so the background is left untouched.
+struct Scaled<'a, D> {
+    target: &'a mut D,
+    scale: u32,
+    offset: Point,
+}
+
+impl<'a, D: DrawTarget<Color = BinaryColor>> Dimensions for Scaled<'a, D> {
+    fn bounding_box(&self) -> Rectangle {
+        // The display grows along x, so don't limit the width
+        let height = self.target.bounding_box().size.height / self.scale;
+        Rectangle::new(Point::zero(), Size::new(u16::MAX as u32, height))
+    }
+}
+
+impl<'a, D: DrawTarget<Color = BinaryColor>> DrawTarget for Scaled<'a, D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let height = self.target.bounding_box().size.height as i32;
+        let scale = self.scale as i32;
+        let offset = self.offset;
+
+        let blocks = pixels
+            .into_iter()
+            .filter(|Pixel(_, color)| *color == BinaryColor::On)
+            .flat_map(move |Pixel(p, color)| {
+                let origin = offset + p * scale;
+                (0..scale * scale).map(move |i| Pixel(origin + Point::new(i % scale, i / scale), color))
+            })
+            // The display grows along x, so only clip negative coordinates there
+            .filter(|Pixel(p, _)| p.x >= 0 && p.y >= 0 && p.y < height);
+
+        self.target.draw_iter(blocks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::display::Display;
+    use crate::render::font::TtfTextStyle;
+
+    fn bounds(display: &Display) -> Option<Rectangle> {
+        let size = display.size();
+        let on: Vec<Point> = (0..size.width as i32)
+            .flat_map(|x| (0..size.height as i32).map(move |y| Point::new(x, y)))
+            .filter(|p| display.get(p.x as usize, p.y as usize).unwrap())
+            .collect();
+        let min = Point::new(on.iter().map(|p| p.x).min()?, on.iter().map(|p| p.y).min()?);
+        let max = Point::new(on.iter().map(|p| p.x).max()?, on.iter().map(|p| p.y).max()?);
+        Some(Rectangle::with_corners(min, max))
+    }
+
+    #[test]
+    fn alignment() {
+        let area = Rectangle::new(Point::zero(), Size::new(100, 40));
+        let cases = [
+            (HAlign::Left, VAlign::Top, Point::new(0, 0)),
+            (HAlign::Centre, VAlign::Centre, Point::new(44, 14)),
+            (HAlign::Right, VAlign::Bottom, Point::new(88, 28)),
+        ];
+
+        for (h_align, v_align, top_left) in cases {
+            let options = TextOptions { font: FontKind::Font6x12, h_align, v_align };
+            let text = StyledText::new("AA", &options, area);
+            assert_eq!(text.bounding_box(), Rectangle::new(top_left, Size::new(12, 12)));
+        }
+    }
+
+    #[test]
+    fn draws_glyphs() {
+        let options = TextOptions { font: FontKind::Font6x12, h_align: HAlign::Left, v_align: VAlign::Top };
+        let mut display = Display::new(20, 8);
+        StyledText::new("I", &options, Rectangle::new(Point::new(2, 3), Size::new(6, 12)))
+            .draw(&mut display)
+            .unwrap();
+
+        let drawn = bounds(&display).unwrap();
+        assert!(drawn.top_left.x >= 2 && drawn.top_left.y >= 3);
+        assert!(drawn.bottom_right().unwrap().x < 8 && drawn.bottom_right().unwrap().y < 15);
+    }
+
+    #[test]
+    fn bitmap_font_sizes() {
+        // The closest ISO 8859-1 font to each nominal size
+        let sizes = ["20x32", "10x16", "9x15", "6x12", "5x8", "4x6"];
+        for (font, expected) in FontKind::BITMAP.into_iter().zip(sizes) {
+            let size = TextOptions { font, ..Default::default() }.measure("W");
+            assert_eq!(format!("{}x{}", size.width, size.height), expected);
+        }
+    }
+
+    #[test]
+    fn scaled_font() {
+        let area = Rectangle::new(Point::zero(), Size::new(100, 100));
+        let small = TextOptions { font: FontKind::Font12x16, h_align: HAlign::Left, v_align: VAlign::Top };
+        let large = TextOptions { font: FontKind::Font24x32, ..small.clone() };
+
+        let mut a = Display::new(100, 8);
+        let mut b = Display::new(100, 8);
+        StyledText::new("T", &small, area).draw(&mut a).unwrap();
+        StyledText::new("T", &large, area).draw(&mut b).unwrap();
+
+        let (a, b) = (bounds(&a).unwrap(), bounds(&b).unwrap());
+        assert_eq!(b.size, a.size * 2);
+    }
+
+    fn fit_options(wrap: bool) -> FitOptions {
+        FitOptions { wrap, ..Default::default() }
+    }
+
+    #[test]
+    fn fit_bitmap() {
+        let options = TextOptions::default();
+        let area = Rectangle::new(Point::zero(), Size::new(60, 40));
+
+        let short = fit("AB", &options, &fit_options(false), area);
+        assert!(matches!(short.font, FontKind::Font24x32));
+
+        let long = fit("ABCDEFGHIJ", &options, &fit_options(false), area);
+        assert!(matches!(long.font, FontKind::Font6x12));
+        assert!(long.fits && !long.truncated);
+    }
+
+    #[test]
+    fn fit_wrapped() {
+        let area = Rectangle::new(Point::zero(), Size::new(40, 40));
+        let fitted = fit("aa bb cc", &TextOptions::default(), &fit_options(true), area);
+
+        assert!(matches!(fitted.font, FontKind::Font6x12));
+        assert_eq!(fitted.lines, vec!["aa bb", "cc"]);
+        assert_eq!(fitted.bounding_box(), Rectangle::new(Point::new(5, 8), Size::new(30, 24)));
+    }
+
+    #[test]
+    fn fit_ellipsis() {
+        let area = Rectangle::new(Point::zero(), Size::new(30, 10));
+        let fitted = fit("ABCDEFGHIJKLMNOPQRSTUVWXYZ", &TextOptions::default(), &fit_options(true), area);
+        assert!(matches!(fitted.font, FontKind::Font6x6));
+        assert_eq!(fitted.lines, vec!["ABCD..."]);
+        assert!(fitted.truncated && fitted.fits);
+
+        let no_ellipsis = FitOptions { ellipsis: false, ..fit_options(false) };
+        let fitted = fit("ABCDEFGHIJKLMNOPQRSTUVWXYZ", &TextOptions::default(), &no_ellipsis, area);
+        assert!(!fitted.truncated && !fitted.fits);
+    }
+
+    fn texts(lines: &[Line]) -> Vec<&str> {
+        lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wrap_words() {
+        // 6 dots per character
+        let options = TextOptions { font: FontKind::Font6x12, ..Default::default() };
+        let lines = wrap("one two three\nfour", &options, Some(48), true);
+        assert_eq!(texts(&lines), vec!["one two", "three", "four"]);
+        assert_eq!(lines.iter().map(|l| l.end_of_paragraph).collect::<Vec<_>>(), vec![false, true, true]);
+    }
+
+    #[test]
+    fn wrap_long_tokens() {
+        let options = TextOptions { font: FontKind::Font6x12, ..Default::default() };
+        let lines = wrap("PN: 1234567890ABCDEF x", &options, Some(36), true);
+        assert_eq!(texts(&lines), vec!["PN:", "123456", "7890AB", "CDEF x"]);
+
+        let unbroken = wrap("PN: 1234567890ABCDEF", &options, Some(36), false);
+        assert_eq!(texts(&unbroken), vec!["PN:", "1234567890ABCDEF"]);
+    }
+
+    #[test]
+    fn paragraph_bounds() {
+        let text = TextOptions { font: FontKind::Font6x12, h_align: HAlign::Left, v_align: VAlign::Top };
+        let options = ParagraphOptions { text, line_spacing: 1.5, ..Default::default() };
+        let paragraph = Paragraph::new("aa bb cc dd", &options, Point::new(10, 20), 30);
+
+        assert_eq!(texts(&paragraph.lines), vec!["aa bb", "cc dd"]);
+        assert_eq!(paragraph.line_pitch(), 18);
+        assert_eq!(paragraph.bounding_box(), Rectangle::new(Point::new(10, 20), Size::new(30, 30)));
+
+        let justified = ParagraphOptions { justify: true, ..options };
+        let paragraph = Paragraph::new("a b c d", &justified, Point::zero(), 30);
+        assert_eq!(texts(&paragraph.lines), vec!["a b c", "d"]);
+        assert_eq!(paragraph.bounding_box().size, Size::new(30, 30));
+    }
+
+    #[test]
+    fn justified_line() {
+        let text = TextOptions { font: FontKind::Font6x12, h_align: HAlign::Left, v_align: VAlign::Top };
+        let options = ParagraphOptions { text, justify: true, ..Default::default() };
+        let mut display = Display::new(12, 8);
+        let paragraph = Paragraph::new("I I I I", &options, Point::zero(), 40);
+        assert_eq!(texts(&paragraph.lines), vec!["I I I", "I"]);
+        paragraph.draw(&mut display).unwrap();
+
+        // The last word of a justified line ends at the right edge
+        let drawn = bounds(&display).unwrap();
+        assert!(drawn.bottom_right().unwrap().x > 34, "{drawn:?}");
+    }
+
+    #[test]
+    fn fit_truetype() {
+        let font = crate::render::font::test::test_font();
+        let options = TextOptions { font: FontKind::TrueType(TtfTextStyle::new(font, 12.0)), ..Default::default() };
+        let area = Rectangle::new(Point::zero(), Size::new(300, 100));
+
+        let size = |text| match fit(text, &options, &FitOptions::default(), area).font {
+            FontKind::TrueType(style) => style.size,
+            _ => unreachable!(),
+        };
+        let (short, long) = (size("Tag"), size("Asset tag 0001234"));
+        assert!(short > long, "{short} <= {long}");
+        assert!((6.0..=72.0).contains(&long));
+    }
+}
@@ -0,0 +1,436 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::ErrorKind,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+use crate::{
+    commands::Commands,
+    prelude::*,
+    printer::PTouchPrinter,
+    status::{DeviceStatus, ErrorStatus},
+    PTouchError,
+};
+
+/// How long a single status read waits, the worker never blocks longer than this without checking the deadline
+const STATUS_READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the printer may take to report a job as completed
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Finished jobs whose state is kept, older ones are forgotten
+const FINISHED_JOBS: usize = 1024;
+
+/// Identifier handed out for every submitted job
+pub type JobId = u64;
+
+/// Lifecycle of a spooled job
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JobState {
+    /// Waiting for the printer
+    Queued,
+    /// Raster data is being transferred
+    Sending,
+    /// Data is sent, waiting for the printer to report completion
+    Printing,
+    Done,
+    /// Removed from the queue before it was sent
+    Cancelled,
+    /// The printer reported an error, or the transfer failed (`COMMS_ERROR`)
+    Failed(ErrorStatus),
+}
+
+impl JobState {
+    /// Is the job finished, successfully or not
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Done | Self::Cancelled | Self::Failed(_))
+    }
+}
+
+struct Queue {
+    next_id: JobId,
+    pending: VecDeque<(JobId, Vec<Vec<u8>>)>,
+    states: HashMap<JobId, JobState>,
+    /// Communication errors of failed jobs
+    errors: HashMap<JobId, String>,
+    /// Finished jobs, oldest first
+    finished: VecDeque<JobId>,
+    shutdown: bool,
+}
+
+impl Queue {
+    fn set_state(&mut self, id: JobId, state: JobState) {
+        self.states.insert(id, state);
+        if state.is_final() {
+            self.finished.push_back(id);
+            while self.finished.len() > FINISHED_JOBS {
+                if let Some(old) = self.finished.pop_front() {
+                    self.states.remove(&old);
+                    self.errors.remove(&old);
+                }
+            }
+        }
+    }
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        // A panicking producer can not leave the queue in an inconsistent state
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_state(&self, id: JobId, state: JobState) {
+        self.lock().set_state(id, state);
+        self.changed.notify_all();
+    }
+
+    fn fail(&self, id: JobId, error: &PTouchError) {
+        let mut queue = self.lock();
+        queue.errors.insert(id, error.to_string());
+        queue.set_state(id, JobState::Failed(ErrorStatus::COMMS_ERROR));
+        drop(queue);
+        self.changed.notify_all();
+    }
+}
+
+/// Print spooler, owns a printer and prints the jobs of many producers one after another
+pub struct Spooler {
+    handle: SpoolerHandle,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Spooler {
+    /// Start the spooler thread for the given printer
+    pub fn new<D: PTouchInterface + Send + 'static>(printer: PTouchPrinter<D>) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                next_id: 1,
+                pending: VecDeque::new(),
+                states: HashMap::new(),
+                errors: HashMap::new(),
+                finished: VecDeque::new(),
+                shutdown: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let worker_shared = shared.clone();
+        let worker = thread::spawn(move || run(printer, &worker_shared));
+
+        Self {
+            handle: SpoolerHandle { shared },
+            worker: Some(worker),
+        }
+    }
+
+    /// Handle for submitting jobs, can be cloned and sent to other threads
+    pub fn handle(&self) -> SpoolerHandle {
+        self.handle.clone()
+    }
+
+    /// Print the remaining queued jobs and stop the spooler thread
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.handle.shared.lock().shutdown = true;
+        self.handle.shared.changed.notify_all();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Spooler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Clone)]
+pub struct SpoolerHandle {
+    shared: Arc<Shared>,
+}
+
+impl SpoolerHandle {
+    /// Queue rendered raster data (see `Display::render`) for printing
+    pub fn submit(&self, data: Vec<Vec<u8>>) -> JobId {
+        let mut queue = self.shared.lock();
+        let id = queue.next_id;
+        queue.next_id += 1;
+
+        if queue.shutdown {
+            queue.set_state(id, JobState::Cancelled);
+        } else {
+            queue.pending.push_back((id, data));
+            queue.set_state(id, JobState::Queued);
+        }
+        drop(queue);
+
+        self.shared.changed.notify_all();
+        id
+    }
+
+    /// Current state of a job, `None` for unknown ids and long finished jobs
+    pub fn state(&self, id: JobId) -> Option<JobState> {
+        self.shared.lock().states.get(&id).copied()
+    }
+
+    /// Why a job failed with `COMMS_ERROR`, e.g. a lost connection
+    pub fn error(&self, id: JobId) -> Option<String> {
+        self.shared.lock().errors.get(&id).cloned()
+    }
+
+    /// Cancel a job that is still queued, returns false once it is being sent
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut queue = self.shared.lock();
+        let Some(index) = queue.pending.iter().position(|(job, _)| *job == id) else {
+            return false;
+        };
+
+        queue.pending.remove(index);
+        queue.set_state(id, JobState::Cancelled);
+        drop(queue);
+
+        self.shared.changed.notify_all();
+        true
+    }
+
+    /// Block until the job is done, failed or cancelled
+    pub fn wait(&self, id: JobId) -> Option<JobState> {
+        let mut queue = self.shared.lock();
+        loop {
+            match queue.states.get(&id) {
+                Some(state) if state.is_final() => return Some(*state),
+                Some(_) => {}
+                None => return None,
+            }
+            queue = self.shared.changed.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Spooler thread, pops jobs until shut down and the queue is empty
+fn run<D: PTouchInterface>(mut printer: PTouchPrinter<D>, shared: &Shared) {
+    if let Err(e) = printer.interface.set_read_timeout(Some(STATUS_READ_TIMEOUT)) {
+        warn!("Unable to set the read timeout of {}: {e}", printer.interface.name());
+    }
+    loop {
+        let (id, data) = {
+            let mut queue = shared.lock();
+            loop {
+                if let Some(job) = queue.pending.pop_front() {
+                    queue.states.insert(job.0, JobState::Sending);
+                    break job;
+                }
+                if queue.shutdown {
+                    return;
+                }
+                queue = shared.changed.wait(queue).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+        shared.changed.notify_all();
+
+        if let Err(e) = printer.print_data(data) {
+            warn!("Job {id} failed to send: {e:?}");
+            shared.fail(id, &e);
+            continue;
+        }
+        shared.set_state(id, JobState::Printing);
+
+        match wait_for_completion(&mut printer) {
+            Ok(state) => shared.set_state(id, state),
+            Err(e) => {
+                warn!("Job {id}: no completion status from printer: {e:?}");
+                shared.fail(id, &e);
+            }
+        }
+    }
+}
+
+/// Follow the status messages the printer sends while printing. Interfaces without replies (a file) are done once
+/// the data is written.
+fn wait_for_completion<D: PTouchInterface>(printer: &mut PTouchPrinter<D>) -> Result<JobState> {
+    if !printer.interface.reports_status() {
+        return Ok(JobState::Done);
+    }
+    let deadline = Instant::now() + COMPLETION_TIMEOUT;
+    loop {
+        match printer.read_status() {
+            Ok(status) => match status.status_type {
+                DeviceStatus::Completed => return Ok(JobState::Done),
+                DeviceStatus::Error => return Ok(JobState::Failed(status.error_status)),
+                _ => {}
+            },
+            // Printing takes longer than a read timeout
+            Err(PTouchError::IoError(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) && Instant::now() < deadline => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    /// Printer stand-in, status replies are fed by the test
+    struct MockInterface {
+        replies: Receiver<[u8; 32]>,
+        pending: Vec<u8>,
+    }
+
+    impl PTouchInterface for MockInterface {
+        fn name(&self) -> String {
+            "mock".into()
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.pending.is_empty() {
+                match self.replies.recv() {
+                    Ok(reply) => self.pending.extend_from_slice(&reply),
+                    Err(_) => return Ok(0),
+                }
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+
+        fn read_vec(&mut self) -> Result<Vec<u8>> {
+            Ok(std::mem::take(&mut self.pending))
+        }
+
+        fn write(&mut self, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn status(status_type: u8, error: ErrorStatus) -> [u8; 32] {
+        let mut r = [0u8; 32];
+        r[..4].copy_from_slice(&[0x80, 0x20, 0x42, 0x34]);
+        r[8..10].copy_from_slice(&error.bits().to_le_bytes());
+        r[18] = status_type;
+        r
+    }
+
+    fn spooler() -> (Spooler, Sender<[u8; 32]>) {
+        let (tx, replies) = channel();
+        let interface = MockInterface { replies, pending: Vec::new() };
+        (Spooler::new(PTouchPrinter::with_interface(interface)), tx)
+    }
+
+    #[test]
+    fn concurrent_producers() {
+        let (spooler, tx) = spooler();
+        for _ in 0..12 {
+            tx.send(status(0x01, ErrorStatus::empty())).unwrap();
+        }
+
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let handle = spooler.handle();
+                thread::spawn(move || (0..3).map(|_| handle.submit(vec![vec![0u8; 90]])).collect::<Vec<_>>())
+            })
+            .collect();
+
+        let handle = spooler.handle();
+        for producer in producers {
+            for id in producer.join().unwrap() {
+                assert_eq!(handle.wait(id), Some(JobState::Done));
+            }
+        }
+    }
+
+    #[test]
+    fn printer_error() {
+        let (spooler, tx) = spooler();
+        let handle = spooler.handle();
+
+        let id = handle.submit(vec![vec![0u8; 90]]);
+        tx.send(status(0x06, ErrorStatus::empty())).unwrap();
+        tx.send(status(0x02, ErrorStatus::NO_MEDIA)).unwrap();
+
+        assert_eq!(handle.wait(id), Some(JobState::Failed(ErrorStatus::NO_MEDIA)));
+    }
+
+    #[test]
+    fn cancel_queued() {
+        let (spooler, tx) = spooler();
+        let handle = spooler.handle();
+
+        // The first job blocks the spooler until its completion status is sent
+        let first = handle.submit(vec![vec![0u8; 90]]);
+        let second = handle.submit(vec![vec![0u8; 90]]);
+        let third = handle.submit(vec![vec![0u8; 90]]);
+
+        assert!(handle.cancel(second));
+        assert!(!handle.cancel(second));
+        assert_eq!(handle.state(second), Some(JobState::Cancelled));
+
+        tx.send(status(0x01, ErrorStatus::empty())).unwrap();
+        tx.send(status(0x01, ErrorStatus::empty())).unwrap();
+        assert_eq!(handle.wait(first), Some(JobState::Done));
+        assert_eq!(handle.wait(third), Some(JobState::Done));
+        assert!(!handle.cancel(first));
+    }
+
+    #[test]
+    fn lost_connection() {
+        let (spooler, tx) = spooler();
+        let handle = spooler.handle();
+
+        // The printer goes away before reporting completion
+        let id = handle.submit(vec![vec![0u8; 90]]);
+        drop(tx);
+
+        assert_eq!(handle.wait(id), Some(JobState::Failed(ErrorStatus::COMMS_ERROR)));
+        assert_eq!(handle.error(id).as_deref(), Some("Invalid status payload"));
+    }
+
+    #[test]
+    fn file_target() {
+        let path = std::env::temp_dir().join(format!("ql-raster-spooler-{}.bin", std::process::id()));
+        let spooler = Spooler::new(crate::printer::from_file(&path).unwrap());
+        let handle = spooler.handle();
+
+        let id = handle.submit(vec![vec![0u8; 90]]);
+        assert_eq!(handle.wait(id), Some(JobState::Done));
+        drop(spooler);
+        assert!(std::fs::metadata(&path).unwrap().len() > 90);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn finished_jobs_are_forgotten() {
+        let (spooler, tx) = spooler();
+        let handle = spooler.handle();
+
+        // The first job blocks the spooler, the others are cancelled right away
+        let first = handle.submit(vec![vec![0u8; 90]]);
+        let cancelled: Vec<_> = (0..FINISHED_JOBS + 1).map(|_| handle.submit(Vec::new())).collect();
+        for &id in &cancelled {
+            assert!(handle.cancel(id));
+        }
+        assert_eq!(handle.state(cancelled[0]), None);
+        assert_eq!(handle.state(cancelled[1]), Some(JobState::Cancelled));
+
+        tx.send(status(0x01, ErrorStatus::empty())).unwrap();
+        assert_eq!(handle.wait(first), Some(JobState::Done));
+        assert_eq!(handle.state(cancelled[1]), None);
+        assert_eq!(handle.shared.lock().states.len(), FINISHED_JOBS);
+    }
+}
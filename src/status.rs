@@ -6,6 +6,7 @@ use num_enum::{FromPrimitive, IntoPrimitive, TryFromPrimitive};
 
 /// Device status message
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Status {
     pub model: Model,
     pub error_status: ErrorStatus,
@@ -48,6 +49,7 @@ impl From<[u8; 32]> for Status {
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 pub enum Model {
     // Standard 300(600)DPI desk printers
@@ -119,6 +121,14 @@ bitflags! {
     }
 }
 
+/// Serialized as the list of set flag names, like the `Debug` output
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter_names().map(|(s, _v)| s))
+    }
+}
+
 impl Debug for ErrorStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_set()
@@ -129,6 +139,7 @@ impl Debug for ErrorStatus {
 
 /// Kind of media loaded in printer
 #[derive(Copy, Clone, PartialEq, Debug, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 pub enum MediaKind {
     None = 0x00,
@@ -146,6 +157,7 @@ pub enum MediaKind {
 }
 
 #[derive(Copy, Clone, PartialEq, Debug, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 pub enum DeviceStatus {
     Reply = 0x00,
@@ -162,6 +174,7 @@ pub enum DeviceStatus {
 
 /// Tape colour enumerations
 #[derive(Copy, Clone, PartialEq, Debug, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 
 pub enum TapeColour {
@@ -199,6 +212,7 @@ pub enum TapeColour {
 
 /// Text colour enumerations
 #[derive(Copy, Clone, PartialEq, Debug, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 pub enum TextColour {
     White = 0x01,
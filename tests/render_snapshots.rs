@@ -0,0 +1,393 @@
+//! Golden image tests for the renderer.
+//!
+//! Every test renders a label and compares the `Render::to_image` preview against
+//! `tests/snapshots/<name>.png`. On a mismatch the actual image and a diff image
+//! (red: only in actual, blue: only in reference) are written next to the test
+//! binaries and the differing region is printed.
+//!
+//! Run with `PTOUCH_BLESS=1 cargo test --test render_snapshots` to (re)write the references.
+use std::{env, path::PathBuf};
+
+use embedded_graphics::{
+    prelude::{Point, Size},
+    primitives::{Line, Rectangle},
+};
+use image::{DynamicImage, GrayImage, Rgb, RgbImage, Rgba, RgbaImage};
+#[cfg(feature = "serde")]
+use ql_raster::render::label::LabelDescription;
+use ql_raster::render::{
+    aztec::{Aztec, AztecLayers, AztecOptions, StyledAztec},
+    barcode::StyledBarcode,
+    code128::Code128,
+    data_matrix::{DataMatrix, StyledDataMatrix, SymbolShape},
+    ean::{Ean, EanKind, StyledEan},
+    layout::{Node, Padding, Stack},
+    ops::{
+        Dither, FitOptions, FontKind, HAlign, ImageOptions, Length, ParagraphOptions, QrOptions, StrokeOptions, TableOptions,
+        TextOptions, VAlign,
+    },
+    shapes::{Shape, StyledShape},
+    Orientation, QrCodeEcc, Render, RenderConfig,
+};
+
+fn snapshot_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots")
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("snapshots")
+}
+
+fn assert_snapshot(name: &str, render: &Render) {
+    let actual = render.to_image();
+    let path = snapshot_dir().join(format!("{name}.png"));
+
+    if env::var_os("PTOUCH_BLESS").is_some() {
+        std::fs::create_dir_all(snapshot_dir()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&path) {
+        Ok(image) => image.to_luma8(),
+        Err(e) => panic!("No reference for {name} ({e}), run with PTOUCH_BLESS=1 to create it"),
+    };
+
+    if let Some(report) = compare(&expected, &actual) {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{name}.actual.png"));
+        let diff_path = output_dir().join(format!("{name}.diff.png"));
+        actual.save(&actual_path).unwrap();
+        diff_image(&expected, &actual).save(&diff_path).unwrap();
+
+        panic!(
+            "Snapshot {name} differs from {}\n{report}\nactual: {}\ndiff:   {}",
+            path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+/// Describe the differences, `None` if the images are identical
+fn compare(expected: &GrayImage, actual: &GrayImage) -> Option<String> {
+    if expected.dimensions() != actual.dimensions() {
+        return Some(format!(
+            "size differs: expected {:?}, actual {:?}",
+            expected.dimensions(),
+            actual.dimensions()
+        ));
+    }
+
+    let differing: Vec<(u32, u32)> = actual
+        .enumerate_pixels()
+        .filter(|(x, y, p)| expected.get_pixel(*x, *y) != *p)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    if differing.is_empty() {
+        return None;
+    }
+
+    let min_x = differing.iter().map(|p| p.0).min().unwrap();
+    let max_x = differing.iter().map(|p| p.0).max().unwrap();
+    let min_y = differing.iter().map(|p| p.1).min().unwrap();
+    let max_y = differing.iter().map(|p| p.1).max().unwrap();
+
+    let mut report = format!(
+        "{} pixels differ in ({min_x}, {min_y})..=({max_x}, {max_y})\n'+': only in actual, '-': only in reference, '#': both\n",
+        differing.len()
+    );
+
+    // Print (part of) the differing region
+    for y in min_y..=max_y.min(min_y + 40) {
+        for x in min_x..=max_x.min(min_x + 100) {
+            let e = expected.get_pixel(x, y).0[0] < 128;
+            let a = actual.get_pixel(x, y).0[0] < 128;
+            report.push(match (e, a) {
+                (true, true) => '#',
+                (false, true) => '+',
+                (true, false) => '-',
+                (false, false) => '.',
+            });
+        }
+        report.push('\n');
+    }
+
+    Some(report)
+}
+
+fn diff_image(expected: &GrayImage, actual: &GrayImage) -> RgbImage {
+    RgbImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y).0[0] < 128;
+        let e = expected.get_pixel_checked(x, y).is_some_and(|p| p.0[0] < 128);
+        match (e, a) {
+            (true, true) => Rgb([0, 0, 0]),
+            (false, true) => Rgb([255, 0, 0]),
+            (true, false) => Rgb([0, 0, 255]),
+            (false, false) => Rgb([255, 255, 255]),
+        }
+    })
+}
+
+fn small_label() -> RenderConfig {
+    RenderConfig { y: 128, ..Default::default() }
+}
+
+#[test]
+fn text() {
+    let mut render = Render::new(small_label());
+    let area = Rectangle::new(Point::zero(), Size::new(128, 128));
+    render.render_text("Hello Rust!", &TextOptions::default(), area).unwrap();
+    assert_snapshot("text", &render);
+}
+
+#[test]
+fn text_alignment() {
+    let mut render = Render::new(small_label());
+    let area = Rectangle::new(Point::new(4, 4), Size::new(200, 120));
+    let aligned = [
+        ("left top", HAlign::Left, VAlign::Top),
+        ("centre", HAlign::Centre, VAlign::Centre),
+        ("right bottom", HAlign::Right, VAlign::Bottom),
+    ];
+    for (text, h_align, v_align) in aligned {
+        let options = TextOptions { font: FontKind::Font6x12, h_align, v_align };
+        render.render_text(text, &options, area).unwrap();
+    }
+    assert_snapshot("text_alignment", &render);
+}
+
+#[test]
+fn text_fit() {
+    let mut render = Render::new(small_label());
+    let fit = FitOptions { wrap: true, ..Default::default() };
+    let area = Rectangle::new(Point::new(4, 4), Size::new(150, 120));
+    let fitted = render.render_text_fit("Meeting room projector 3", &TextOptions::default(), &fit, area).unwrap();
+    assert!(fitted.fits && !fitted.truncated);
+    assert_snapshot("text_fit", &render);
+}
+
+#[test]
+fn paragraph() {
+    let mut render = Render::new(RenderConfig { y: 200, ..Default::default() });
+    let text = TextOptions { font: FontKind::Font6x12, h_align: HAlign::Left, v_align: VAlign::Top };
+    let options = ParagraphOptions { text, line_spacing: 1.25, justify: true, ..Default::default() };
+    let bounds = render
+        .render_paragraph(
+            "Store dry, between 5 and 25 degrees.\nPart number: QL820NWB-0001-ABCDEFGHIJ",
+            &options,
+            Point::new(4, 4),
+            150,
+        )
+        .unwrap();
+
+    // Callers continue below the returned box
+    let below = Point::new(4, bounds.bottom_right().unwrap().y + 8);
+    render.render_paragraph("Made in Rust", &ParagraphOptions::default(), below, 150).unwrap();
+    assert_snapshot("paragraph", &render);
+}
+
+#[test]
+fn qrcode() {
+    let mut render = Render::new(RenderConfig::default());
+    render.render_qrcode("https://lib.rs/crates/embedded-graphics", &QrOptions::default(), Point::new(1, 0)).unwrap();
+    assert_snapshot("qrcode", &render);
+}
+
+#[test]
+fn qrcode_fit() {
+    let mut render = Render::new(small_label());
+    let options = QrOptions { fit: Some(Size::new(128, 128)), ..Default::default() };
+    let area = render.render_qrcode("https://lib.rs", &options, Point::zero()).unwrap();
+    let inverted = QrOptions { ecc: QrCodeEcc::High, quiet_zone: 1, invert: true, ..options };
+    render.render_qrcode("https://lib.rs", &inverted, Point::new(area.size.width as i32 + 8, 0)).unwrap();
+    assert_snapshot("qrcode_fit", &render);
+}
+
+#[test]
+fn micro_qrcode() {
+    let mut render = Render::new(small_label());
+    let options = QrOptions { micro: true, fit: Some(Size::new(128, 128)), ..Default::default() };
+    let area = render.render_qrcode("PN-4711", &options, Point::zero()).unwrap();
+    render.render_qrcode("0123456789", &options, Point::new(area.size.width as i32 + 8, 0)).unwrap();
+    assert_snapshot("micro_qrcode", &render);
+}
+
+#[test]
+fn image_dithering() {
+    let mut render = Render::new(small_label());
+    // Radial gradient, dark in the centre, fading out through transparency at the edges
+    let gradient = RgbaImage::from_fn(96, 96, |x, y| {
+        let distance = ((x as f32 - 48.0).powi(2) + (y as f32 - 48.0).powi(2)).sqrt() / 48.0;
+        let level = (distance * 255.0).min(255.0) as u8;
+        let alpha = if distance > 0.95 { 0 } else { 255 };
+        Rgba([level, level, level, alpha])
+    });
+    let image = DynamicImage::ImageRgba8(gradient);
+    let dithers = [Dither::Threshold, Dither::FloydSteinberg, Dither::Atkinson, Dither::Ordered];
+    for (i, dither) in dithers.into_iter().enumerate() {
+        let area = Rectangle::new(Point::new(i as i32 * 132, 0), Size::new(128, 128));
+        render.render_image(&image, area, &ImageOptions { dither, ..Default::default() }).unwrap();
+    }
+    let inverted = ImageOptions { invert: true, ..Default::default() };
+    render.render_image(&image, Rectangle::new(Point::new(528, 0), Size::new(64, 128)), &inverted).unwrap();
+    assert_snapshot("image_dithering", &render);
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn svg_artwork() {
+    use ql_raster::render::svg::Svg;
+
+    let mut render = Render::new(small_label());
+    let warning = Svg::from_data(
+        br#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="9mm" viewBox="0 0 100 90">
+            <path d="M50 4 L96 86 H4 Z" fill="none" stroke="black" stroke-width="7" stroke-linejoin="round"/>
+            <rect x="45" y="30" width="10" height="32" rx="3"/>
+            <circle cx="50" cy="73" r="6"/>
+        </svg>"#,
+    )
+    .unwrap();
+    let natural = Rectangle::new(Point::zero(), warning.size_at(300));
+    render.render_svg(&warning, natural, &ImageOptions::default()).unwrap();
+    let options = ImageOptions { h_align: HAlign::Left, ..Default::default() };
+    render.render_svg(&warning, Rectangle::new(Point::new(140, 0), Size::new(200, 64)), &options).unwrap();
+    assert_snapshot("svg_artwork", &render);
+}
+
+#[test]
+fn shapes_and_table() {
+    let mut render = Render::new(small_label());
+    let solid = StrokeOptions::default();
+    let dashed = StrokeOptions { dash: Some((Length::Dots(6), Length::Dots(4))), ..Default::default() };
+    render.render_rect(Rectangle::new(Point::zero(), Size::new(120, 128)), Length::Mm(1.5), &dashed).unwrap();
+    let filled = StrokeOptions { fill: true, ..solid.clone() };
+    let thick = StrokeOptions { width: Length::Dots(3), ..solid };
+    render.render_circle(Point::new(60, 40), Length::Mm(2.5), &filled).unwrap();
+    let ring = StrokeOptions { width: Length::Dots(3), ..dashed.clone() };
+    render.render_circle(Point::new(60, 40), Length::Mm(5.0), &ring).unwrap();
+    render.render_line(Point::new(10, 118), Point::new(110, 80), &thick).unwrap();
+    render.render_rule(126, &StrokeOptions { width: Length::Dots(4), ..dashed }).unwrap();
+
+    let rows = [vec!["Part", "Qty", "Bin"], vec!["M3x8", "1200", "A4"], vec!["M3 nut", "800", "A5"]];
+    let text = TextOptions { font: FontKind::Font6x12, ..TableOptions::default().text };
+    let options = TableOptions { text, ..Default::default() };
+    render.render_table(&rows, &options, Point::new(136, 10)).unwrap();
+    assert_snapshot("shapes_and_table", &render);
+}
+
+#[test]
+fn layout_stacks() {
+    let mut render = Render::new(small_label());
+    let text = TextOptions { font: FontKind::Font6x12, ..Default::default() };
+    let title = TextOptions { font: FontKind::Font12x16, ..Default::default() };
+    let line = Line::new(Point::zero(), Point::new(0, 99));
+    let rule = StyledShape::new(Shape::Line(line), &StrokeOptions::default(), 300);
+    let qr = QrOptions::default();
+    let details = Stack::vertical()
+        .with_spacing(4)
+        .with_alignment(HAlign::Left, VAlign::Top)
+        .push(Node::text("Widget, blue", &title))
+        .push(Node::text("PN 10-2231", &text))
+        .push_weighted(Node::Space(Size::zero()), 1)
+        .push(Node::element(StyledBarcode::from(Code128::encode("10-2231").unwrap()).with_height(36)));
+    let label = Stack::horizontal()
+        .with_spacing(8)
+        .with_padding(Padding::all(6))
+        .push(Node::qrcode("https://example.com/10-2231", &QrOptions { module_size: 3, quiet_zone: 0, ..qr }))
+        .push(Node::element(rule))
+        .push_weighted(details, 1)
+        .push(Node::text("x12", &title));
+    render.render_layout(&label, Rectangle::new(Point::zero(), Size::new(560, 128))).unwrap();
+    assert_snapshot("layout_stacks", &render);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn label_template() {
+    let label: LabelDescription = serde_json::from_str(
+        r#"{ "elements": [
+            { "type": "rect", "x": 0, "y": 0, "width": 560, "height": 128, "radius": 12, "stroke": { "width": 3 } },
+            { "type": "stack", "x": 10, "y": 10, "width": 540, "height": 108, "spacing": 10, "children": [
+                { "type": "qrcode", "data": "https://example.com/10-2231", "height": 108, "quiet_zone": 0 },
+                { "type": "stack", "direction": "vertical", "weight": 1, "spacing": 4, "children": [
+                    { "type": "text", "text": "Widget, blue", "font": "12x16" },
+                    { "type": "line", "width": 200, "stroke": { "width": 1, "dash": [4, 4] } },
+                    { "type": "table", "font": "6x12", "rows": [["PN", "10-2231"], ["Qty", "12"]] }
+                ] },
+                { "type": "circle", "width": "5mm", "stroke": { "fill": true } }
+            ] }
+        ] }"#,
+    )
+    .unwrap();
+    let render = label.render(small_label()).unwrap();
+    assert_snapshot("label_template", &render);
+}
+
+#[test]
+fn code128() {
+    let mut render = Render::new(RenderConfig { y: 200, ..Default::default() });
+    render.render_code128("PN-0001234", Point::new(0, 10)).unwrap();
+    assert_snapshot("code128", &render);
+}
+
+#[test]
+fn code128_gs1() {
+    let mut render = Render::new(RenderConfig { y: 200, ..Default::default() });
+    let barcode = StyledBarcode::from(Code128::gs1("(01)09501101530003(10)AB1").unwrap())
+        .with_module_width(2)
+        .with_height(80)
+        .with_font(FontKind::Font6x12);
+    render.render_barcode(&barcode).unwrap();
+    assert_snapshot("code128_gs1", &render);
+}
+
+#[test]
+fn retail_barcodes() {
+    let mut render = Render::new(RenderConfig { y: 400, ..Default::default() });
+    let ean13 = render.render_ean(EanKind::Ean13, "400638133393", Point::new(0, 0)).unwrap();
+    let right = ean13.top_left.x + ean13.size.width as i32;
+    render.render_ean(EanKind::UpcA, "03600029145", Point::new(right, 0)).unwrap();
+    let mut ean8 = StyledEan::from(Ean::ean8("9638507").unwrap())
+        .with_module_width(2)
+        .with_height(60)
+        .with_font(FontKind::Font8x16);
+    ean8.position = Point::new(0, 300);
+    render.render_barcode(&ean8).unwrap();
+    assert_snapshot("retail_barcodes", &render);
+}
+
+#[test]
+fn data_matrix() {
+    let mut render = Render::new(small_label());
+    let square = render.render_datamatrix("(01)09501101530003(17)261231(10)AB1", Point::new(0, 0)).unwrap();
+    let right = square.top_left.x + square.size.width as i32;
+    let mut rectangle = StyledDataMatrix::from(DataMatrix::encode("PN-0001234", SymbolShape::Rectangle).unwrap())
+        .with_scale(3)
+        .inverted();
+    rectangle.position = Point::new(right + 8, 4);
+    render.render_barcode(&rectangle).unwrap();
+    assert_snapshot("data_matrix", &render);
+}
+
+#[test]
+fn aztec() {
+    let mut render = Render::new(RenderConfig { y: 200, ..Default::default() });
+    let compact = render.render_aztec("Ticket 4711, seat 23A", Point::new(0, 0)).unwrap();
+    let right = compact.top_left.x + compact.size.width as i32;
+    let options = AztecOptions { ecc_percent: 50, layers: Some(AztecLayers::Full(4)) };
+    let mut full = StyledAztec::from(Aztec::encode("https://lib.rs/crates/ql-raster", &options).unwrap()).with_scale(5);
+    full.position = Point::new(right + 8, 0);
+    render.render_barcode(&full).unwrap();
+    assert_snapshot("aztec", &render);
+}
+
+#[test]
+fn qrcode_and_text_horizontal() {
+    let mut render = Render::new(RenderConfig { orientation: Orientation::Horizontal, ..Default::default() });
+    render.render_qrcode("P-touch", &QrOptions::default(), Point::new(1, 0)).unwrap();
+    let options = TextOptions { font: FontKind::Font24x32, ..Default::default() };
+    let area = Rectangle::new(Point::new(400, 250), Size::new(296, 100));
+    render.render_text("QL-820NWB", &options, area).unwrap();
+    assert_snapshot("qrcode_and_text_horizontal", &render);
+}